@@ -0,0 +1,479 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{self, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+
+use crate::ipc::{self, PlaybackState, Request, Response, Status};
+use crate::player::Repeat;
+
+pub const USAGE: &str = "Usage: bragi [OPTIONS] [PATHS...]
+
+Plays the files, playlists and folders, handing them to the player if it runs already.
+
+Options:
+  --enqueue <PATHS...>  Add the paths to the queue without playing them
+  --daemon              Run without the window, controlled through the options below
+  --next                Skip to the next track
+  --previous            Go back to the previous track
+  --play                Resume playback, or start the queue if stopped
+  --pause               Pause playback
+  --toggle              Pause or resume playback
+  --stop                Stop playback
+  --seek <SECONDS>      Seek to the position in the current track
+  --volume <PERCENT>    Set the volume, between 0 and 100
+  --mute                Mute the volume
+  --unmute              Unmute the volume
+  --repeat <MODE>       Set the repeat mode: off, all or one
+  --shuffle <on|off>    Enable or disable shuffle
+  --clear               Stop playback and empty the queue
+  --status              Print the current track and position
+  -h, --help            Print this help
+  -V, --version         Print the version";
+
+/// What the command line asks for
+#[derive(Debug, PartialEq)]
+pub enum Args {
+    /// Starts the player with the request, or hands it to the running player
+    Launch(Request),
+    /// Starts the player without the window, or hands the request to the running player
+    Daemon(Request),
+    /// Controls the running player
+    Remote(Request),
+    Help,
+    Version,
+}
+
+/// Parses the arguments, without the name of the binary
+pub fn parse(args: impl IntoIterator<Item = OsString>) -> anyhow::Result<Args> {
+    let mut paths = Vec::new();
+    let mut remote = None;
+    let mut enqueue = false;
+    let mut daemon = false;
+    let mut options_ended = false;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let option = match arg.to_str() {
+            Some(option) if !options_ended && option.starts_with('-') => option,
+            _ => {
+                paths.push(path::absolute(PathBuf::from(arg))?);
+                continue;
+            }
+        };
+
+        let request = match option {
+            "-h" | "--help" => return Ok(Args::Help),
+            "-V" | "--version" => return Ok(Args::Version),
+            "--" => {
+                options_ended = true;
+                continue;
+            }
+            "--enqueue" => {
+                enqueue = true;
+                continue;
+            }
+            "--daemon" => {
+                daemon = true;
+                continue;
+            }
+            "--next" => Request::Next,
+            "--previous" => Request::Previous,
+            "--play" => Request::Play,
+            "--pause" => Request::Pause,
+            "--toggle" => Request::Toggle,
+            "--stop" => Request::Stop,
+            "--seek" => Request::Seek {
+                position: parse_seconds(&value(&mut args, option)?)?,
+            },
+            "--volume" => Request::Volume {
+                level: parse_percent(&value(&mut args, option)?)?,
+            },
+            "--mute" => Request::Mute { muted: true },
+            "--unmute" => Request::Mute { muted: false },
+            "--repeat" => Request::Repeat {
+                mode: match value(&mut args, option)?.as_str() {
+                    "off" => Repeat::Off,
+                    "all" => Repeat::All,
+                    "one" => Repeat::One,
+                    mode => bail!("Unknown repeat mode: {}", mode),
+                },
+            },
+            "--shuffle" => Request::Shuffle {
+                enabled: match value(&mut args, option)?.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    enabled => bail!("--shuffle expects on or off, not {}", enabled),
+                },
+            },
+            "--clear" => Request::Clear,
+            "--status" => Request::Status,
+            option => bail!("Unknown option: {}", option),
+        };
+
+        if remote.replace(request).is_some() {
+            bail!("Only one control option can be given")
+        }
+    }
+
+    let request = match (remote, enqueue) {
+        (Some(_), true) => bail!("--enqueue cannot be combined with other options"),
+        (Some(_), false) if daemon => bail!("--daemon cannot be combined with other options"),
+        (Some(_), false) if !paths.is_empty() => bail!("Unexpected paths: {:?}", paths),
+        (Some(request), false) => return Ok(Args::Remote(request)),
+        (None, true) if paths.is_empty() => bail!("--enqueue needs at least one path"),
+        (None, true) => Request::Enqueue { paths },
+        (None, false) => Request::Open { paths },
+    };
+
+    if daemon {
+        Ok(Args::Daemon(request))
+    } else {
+        Ok(Args::Launch(request))
+    }
+}
+
+/// Returns the value following the option
+fn value(args: &mut impl Iterator<Item = OsString>, option: &str) -> anyhow::Result<String> {
+    args.next()
+        .ok_or(anyhow!("{} needs a value", option))?
+        .into_string()
+        .map_err(|value| anyhow!("Invalid value for {}: {:?}", option, value))
+}
+
+/// Parses a position given in seconds, or as minutes and seconds, e.g. `1:23`
+fn parse_seconds(value: &str) -> anyhow::Result<f64> {
+    let seconds = value
+        .split(':')
+        .try_fold(0.0, |seconds, part| {
+            part.parse::<f64>().map(|part| seconds * 60.0 + part)
+        })
+        .ok()
+        .filter(|seconds| *seconds >= 0.0)
+        .ok_or(anyhow!("Invalid position: {}", value))?;
+
+    Ok(seconds)
+}
+
+/// Parses a volume level given in percent, e.g. `80` or `80%`
+fn parse_percent(value: &str) -> anyhow::Result<f32> {
+    let level = value
+        .trim_end_matches('%')
+        .parse::<f32>()
+        .ok()
+        .filter(|percent| (0.0..=100.0).contains(percent))
+        .ok_or(anyhow!("Invalid volume: {}", value))?;
+
+    Ok(level / 100.0)
+}
+
+/// Sends the request to the running player and prints its answer
+///
+/// # Returns
+///
+/// * i32 - The exit code of the process
+pub fn run_remote(request: &Request) -> i32 {
+    let response =
+        ipc::send(request).and_then(|response| response.ok_or(anyhow!("Bragi is not running")));
+
+    match response {
+        Ok(Response::Status(status)) => {
+            println!("{}", format_status(&status));
+            0
+        }
+        Ok(Response::Added { count }) => {
+            println!("Added {} tracks", count);
+            0
+        }
+        Ok(Response::Ok) => 0,
+        Ok(Response::Error { message }) => {
+            eprintln!("{}", message);
+            1
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn format_status(status: &Status) -> String {
+    let state = match status.state {
+        PlaybackState::Playing => "Playing",
+        PlaybackState::Paused => "Paused",
+        PlaybackState::Stopped => "Stopped",
+    };
+    let volume = if status.muted {
+        "muted".to_string()
+    } else {
+        format!("{:.0}%", status.volume * 100.0)
+    };
+
+    let Some((index, track)) = &status.track else {
+        return format!(
+            "{}, {} tracks in the queue, volume {}",
+            state, status.queue_length, volume
+        );
+    };
+
+    let mut lines = vec![match &track.artist {
+        Some(artist) => format!("{}: {} - {}", state, artist, track.title),
+        None => format!("{}: {}", state, track.title),
+    }];
+    if let Some(album) = &track.album {
+        lines.push(format!("Album: {}", album));
+    }
+    lines.push(match track.length {
+        Some(length) => format!(
+            "Position: {} / {}",
+            format_time(status.position),
+            format_time(length)
+        ),
+        None => format!("Position: {}", format_time(status.position)),
+    });
+    lines.push(format!(
+        "Track {} of {}, volume {}",
+        index + 1,
+        status.queue_length,
+        volume
+    ));
+
+    lines.join("\n")
+}
+
+/// Formats the seconds as minutes and seconds, with hours for long tracks
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// Hands the request to the running player
+///
+/// # Returns
+///
+/// * bool - Whether a running player took the request, this process should exit then
+pub fn forward(request: &Request) -> bool {
+    match ipc::send(request) {
+        Ok(Some(Response::Error { message })) => {
+            eprintln!("{}", message);
+            true
+        }
+        Ok(Some(_)) => true,
+        Ok(None) => false,
+        Err(err) => {
+            eprintln!("Unable to reach the running player: {}", err);
+            false
+        }
+    }
+}
+
+/// Returns the arguments of the process, without the name of the binary
+pub fn args() -> impl Iterator<Item = OsString> {
+    env::args_os().skip(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, path::PathBuf};
+
+    use super::{format_status, format_time, parse, parse_percent, parse_seconds, Args};
+    use crate::ipc::{PlaybackState, Request, Status, TrackStatus};
+    use crate::player::Repeat;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn parse_paths() {
+        let cwd = std::env::current_dir().unwrap();
+
+        assert_eq!(
+            Args::Launch(Request::Open {
+                paths: vec![PathBuf::from("/music/track.mp3"), cwd.join("album")]
+            }),
+            parse(args(&["/music/track.mp3", "album"])).unwrap()
+        );
+        assert_eq!(
+            Args::Launch(Request::Open { paths: vec![] }),
+            parse(args(&[])).unwrap()
+        );
+        assert_eq!(
+            Args::Launch(Request::Open {
+                paths: vec![cwd.join("--next")]
+            }),
+            parse(args(&["--", "--next"])).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_enqueue() {
+        assert_eq!(
+            Args::Launch(Request::Enqueue {
+                paths: vec![PathBuf::from("/music/a.mp3"), PathBuf::from("/music/b.mp3")]
+            }),
+            parse(args(&["--enqueue", "/music/a.mp3", "/music/b.mp3"])).unwrap()
+        );
+        assert!(parse(args(&["--enqueue"])).is_err());
+    }
+
+    #[test]
+    fn parse_remote() {
+        assert_eq!(
+            Args::Remote(Request::Next),
+            parse(args(&["--next"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Toggle),
+            parse(args(&["--toggle"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Status),
+            parse(args(&["--status"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Previous),
+            parse(args(&["--previous"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Play),
+            parse(args(&["--play"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Pause),
+            parse(args(&["--pause"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Stop),
+            parse(args(&["--stop"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Clear),
+            parse(args(&["--clear"])).unwrap()
+        );
+        assert!(parse(args(&["--next", "--toggle"])).is_err());
+        assert!(parse(args(&["--next", "/music/track.mp3"])).is_err());
+        assert!(parse(args(&["--next", "--enqueue", "/music/track.mp3"])).is_err());
+    }
+
+    #[test]
+    fn parse_remote_values() {
+        assert_eq!(
+            Args::Remote(Request::Seek { position: 83.0 }),
+            parse(args(&["--seek", "1:23"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Volume { level: 0.8 }),
+            parse(args(&["--volume", "80%"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Mute { muted: true }),
+            parse(args(&["--mute"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Mute { muted: false }),
+            parse(args(&["--unmute"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Repeat { mode: Repeat::All }),
+            parse(args(&["--repeat", "all"])).unwrap()
+        );
+        assert_eq!(
+            Args::Remote(Request::Shuffle { enabled: true }),
+            parse(args(&["--shuffle", "on"])).unwrap()
+        );
+        assert!(parse(args(&["--seek"])).is_err());
+        assert!(parse(args(&["--repeat", "twice"])).is_err());
+        assert!(parse(args(&["--shuffle", "yes"])).is_err());
+    }
+
+    #[test]
+    fn seconds() {
+        assert_eq!(42.5, parse_seconds("42.5").unwrap());
+        assert_eq!(3723.0, parse_seconds("1:02:03").unwrap());
+        assert!(parse_seconds("-5").is_err());
+        assert!(parse_seconds("1:xx").is_err());
+    }
+
+    #[test]
+    fn percent() {
+        assert_eq!(0.5, parse_percent("50").unwrap());
+        assert_eq!(1.0, parse_percent("100%").unwrap());
+        assert!(parse_percent("120").is_err());
+        assert!(parse_percent("loud").is_err());
+    }
+
+    #[test]
+    fn parse_daemon() {
+        assert_eq!(
+            Args::Daemon(Request::Open { paths: vec![] }),
+            parse(args(&["--daemon"])).unwrap()
+        );
+        assert_eq!(
+            Args::Daemon(Request::Enqueue {
+                paths: vec![PathBuf::from("/music")]
+            }),
+            parse(args(&["--daemon", "--enqueue", "/music"])).unwrap()
+        );
+        assert!(parse(args(&["--daemon", "--status"])).is_err());
+    }
+
+    #[test]
+    fn parse_other() {
+        assert_eq!(Args::Help, parse(args(&["track.mp3", "--help"])).unwrap());
+        assert_eq!(Args::Version, parse(args(&["-V"])).unwrap());
+        assert!(parse(args(&["--unknown"])).is_err());
+    }
+
+    #[test]
+    fn time() {
+        assert_eq!("0:00", format_time(0.0));
+        assert_eq!("4:05", format_time(245.9));
+        assert_eq!("1:02:03", format_time(3723.0));
+    }
+
+    #[test]
+    fn status() {
+        let mut status = Status {
+            state: PlaybackState::Playing,
+            track: Some((
+                1,
+                TrackStatus {
+                    path: PathBuf::from("/music/track.mp3"),
+                    title: "Title".to_string(),
+                    artist: Some("Artist".to_string()),
+                    album: Some("Album".to_string()),
+                    length: Some(245.0),
+                },
+            )),
+            queue_length: 12,
+            position: 83.4,
+            volume: 0.8,
+            muted: false,
+        };
+
+        assert_eq!(
+            "Playing: Artist - Title\nAlbum: Album\nPosition: 1:23 / 4:05\nTrack 2 of 12, volume 80%",
+            format_status(&status)
+        );
+
+        status.state = PlaybackState::Stopped;
+        status.track = None;
+        status.muted = true;
+        assert_eq!(
+            "Stopped, 12 tracks in the queue, volume muted",
+            format_status(&status)
+        );
+    }
+}
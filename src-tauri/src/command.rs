@@ -1,9 +1,38 @@
-use std::{collections::VecDeque, error::Error, path::PathBuf, time::Duration};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
 
+use anyhow::anyhow;
 use log::{error, warn};
-use tauri::{Emitter, Manager, Runtime, State};
+use tauri::{async_runtime, Emitter, Manager, Runtime, State};
 
-use crate::player::{track::AlbumCover, track::Track, Player};
+use crate::handle::PlayerHandle;
+use crate::ipc::{PlaybackState, Request, Response, Status, TrackStatus};
+use crate::library::{
+    self,
+    browse::{Album, Artist, Genre, LibraryTrack, Page, Paged, Sort, Year},
+    playlists::SavedPlaylist,
+    watcher::Watcher,
+    Library, ScanSummary,
+};
+use crate::menu::{self, Action};
+use crate::player::{
+    replaygain::{ReplayGainMode, ReplayGainSettings},
+    track::AlbumCover,
+    track::Track,
+    volume::{Volume, VolumeCurve},
+    Player, Repeat,
+};
+use crate::playlist::{self, Entry};
+use crate::scan::{scan, ScanOptions};
+use crate::search::{self, SearchCache, SearchResult, SEARCH_LIMIT};
+use crate::session;
+use crate::settings::{self, Settings};
+use crate::shortcut::{self, Keymap};
 
 #[tauri::command]
 pub async fn stop(player: State<'_, Player>) -> Result<(), String> {
@@ -32,11 +61,140 @@ pub async fn get_playlist(player: State<'_, Player>) -> Result<VecDeque<Track>,
     Ok(player.get_playlist().await)
 }
 
+#[tauri::command]
+pub fn current_index(player: State<Player>) -> usize {
+    player.current_index()
+}
+
+#[tauri::command]
+pub fn get_volume(player: State<Player>) -> f32 {
+    player.volume()
+}
+
 #[tauri::command]
 pub fn set_volume(player: State<Player>, volume: f32) {
     player.set_volume(volume);
 }
 
+/// Returns the level, the mute state and the curve of the volume
+#[tauri::command]
+pub fn get_volume_state(player: State<Player>) -> Volume {
+    player.volume_state()
+}
+
+/// Mutes or unmutes, unmuting restores the level from before
+#[tauri::command]
+pub fn set_muted(player: State<Player>, muted: bool) {
+    player.set_muted(muted);
+}
+
+#[tauri::command]
+pub fn get_volume_step(settings: State<Mutex<Settings>>) -> f32 {
+    settings.lock().unwrap().volume_step
+}
+
+/// Sets the change of the level by the volume up and down actions
+///
+/// # Arguments
+///
+/// * 'step' - The change, between 0 and 1
+#[tauri::command]
+pub fn set_volume_step<R: Runtime>(app: tauri::AppHandle<R>, step: f32) -> Result<(), String> {
+    settings::update(&app, |settings| {
+        if !(0.0..=1.0).contains(&step) || step == 0.0 {
+            return Err(anyhow!("Invalid volume step: {}", step));
+        }
+        settings.volume_step = step;
+        Ok(())
+    })
+    .map(|_| ())
+    .inspect_err(|err| error!("{}", err))
+    .map_err(|err| err.to_string())
+}
+
+/// Sets how the volume level maps to the gain, the curve is kept in the settings
+#[tauri::command]
+pub fn set_volume_curve<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    curve: VolumeCurve,
+) -> Result<(), String> {
+    settings::update(&app, |settings| {
+        settings.volume_curve = curve;
+        Ok(())
+    })
+    .map(|_| app.state::<Player>().set_volume_curve(curve))
+    .inspect_err(|err| error!("{}", err))
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn set_crossfade(player: State<Player>, duration: f64) -> Result<(), String> {
+    let duration = Duration::try_from_secs_f64(duration).map_err(|err| err.to_string())?;
+    player
+        .set_crossfade(duration)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_replay_gain(settings: State<Mutex<Settings>>) -> ReplayGainSettings {
+    settings.lock().unwrap().replay_gain
+}
+
+/// Sets which ReplayGain values are applied, the choice is kept in the settings
+#[tauri::command]
+pub fn set_replay_gain<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    mode: ReplayGainMode,
+    preamp: f32,
+) -> Result<(), String> {
+    let replay_gain = ReplayGainSettings { mode, preamp };
+    settings::update(&app, |settings| {
+        settings.replay_gain = replay_gain;
+        Ok(())
+    })
+    .map(|_| app.state::<Player>().set_replay_gain(replay_gain))
+    .inspect_err(|err| error!("{}", err))
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn set_shuffle(
+    player: State<'_, Player>,
+    enabled: bool,
+    seed: Option<u64>,
+) -> Result<(), ()> {
+    player.set_shuffle(enabled, seed).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn is_shuffle(player: State<Player>) -> bool {
+    player.is_shuffle()
+}
+
+#[tauri::command]
+pub fn set_repeat(player: State<Player>, repeat: Repeat) -> Result<(), String> {
+    player
+        .set_repeat(repeat)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_repeat(player: State<Player>) -> Repeat {
+    player.repeat()
+}
+
+#[tauri::command]
+pub async fn analyze_loudness(player: State<'_, Player>) -> Result<usize, String> {
+    player
+        .analyze_loudness()
+        .await
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
 #[tauri::command]
 pub fn playtime(player: State<Player>) -> f64 {
     player.playtime().as_secs_f64()
@@ -66,6 +224,42 @@ pub async fn change_track(player: State<'_, Player>, index: usize) -> Result<(),
     player.change_track(index).await.map_err(|_| ())
 }
 
+#[tauri::command]
+pub async fn remove_track(player: State<'_, Player>, index: usize) -> Result<(), String> {
+    player
+        .remove(index)
+        .await
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn move_track(player: State<'_, Player>, from: usize, to: usize) -> Result<(), String> {
+    player
+        .move_track(from, to)
+        .await
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn insert_after_current(player: State<'_, Player>, path: PathBuf) -> Result<(), String> {
+    player
+        .insert_after_current(path)
+        .await
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn clear_queue(player: State<'_, Player>) -> Result<(), String> {
+    player
+        .clear()
+        .await
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
 #[tauri::command]
 pub async fn get_album_cover(player: State<'_, Player>) -> Result<AlbumCover, ()> {
     player
@@ -91,8 +285,656 @@ pub async fn previous_track(player: State<'_, Player>) -> Result<(), String> {
 
 #[tauri::command]
 pub fn seek(player: State<Player>, pos: f64) -> Result<(), ()> {
-    player
-        .seek(Duration::from_secs_f64(pos))
+    seek_to(&player, pos)
         .inspect_err(|err| error!("{}", err))
         .map_err(|_| ())
 }
+
+/// Seeks in the current track
+///
+/// # Arguments
+///
+/// * 'player' - The player
+/// * 'pos' - The position in seconds
+fn seek_to(player: &Player, pos: f64) -> anyhow::Result<()> {
+    player.seek(Duration::try_from_secs_f64(pos)?)
+}
+
+#[tauri::command]
+pub fn get_library_roots(library: State<Library>) -> Result<Vec<PathBuf>, String> {
+    library
+        .roots()
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn add_library_root<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    path: PathBuf,
+) -> Result<ScanSummary, String> {
+    let root = app
+        .state::<Library>()
+        .add_root(&path)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())?;
+
+    if let Some(watcher) = app.try_state::<Watcher>() {
+        if let Err(err) = watcher.watch(&root) {
+            warn!("Unable to watch {}: {}", root.display(), err);
+        }
+    }
+
+    scan_library(app).await
+}
+
+#[tauri::command]
+pub async fn remove_library_root<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    path: PathBuf,
+) -> Result<(), String> {
+    let removed = app
+        .state::<Library>()
+        .remove_root(&path)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())?;
+
+    if let Some(watcher) = app.try_state::<Watcher>() {
+        if let Err(err) = watcher.unwatch(&path) {
+            warn!("Unable to stop watching {}: {}", path.display(), err);
+        }
+    }
+
+    let summary = ScanSummary {
+        removed,
+        ..Default::default()
+    };
+    app.emit("library_changed", summary)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn scan_library<R: Runtime>(app: tauri::AppHandle<R>) -> Result<ScanSummary, String> {
+    library::rescan(&app)
+        .await
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_artists(
+    library: State<Library>,
+    sort: Option<Sort>,
+    page: Option<Page>,
+) -> Result<Paged<Artist>, String> {
+    library
+        .artists(sort.unwrap_or_default(), page.unwrap_or_default())
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_albums(
+    library: State<Library>,
+    artist: Option<String>,
+    sort: Option<Sort>,
+    page: Option<Page>,
+) -> Result<Paged<Album>, String> {
+    library
+        .albums(
+            artist.as_deref(),
+            sort.unwrap_or_default(),
+            page.unwrap_or_default(),
+        )
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_album_tracks(
+    library: State<Library>,
+    album: String,
+    artist: Option<String>,
+    page: Option<Page>,
+) -> Result<Paged<LibraryTrack>, String> {
+    library
+        .album_tracks(&album, artist.as_deref(), page.unwrap_or_default())
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_genres(
+    library: State<Library>,
+    sort: Option<Sort>,
+    page: Option<Page>,
+) -> Result<Paged<Genre>, String> {
+    library
+        .genres(sort.unwrap_or_default(), page.unwrap_or_default())
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_years(
+    library: State<Library>,
+    sort: Option<Sort>,
+    page: Option<Page>,
+) -> Result<Paged<Year>, String> {
+    library
+        .years(sort.unwrap_or_default(), page.unwrap_or_default())
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn search(
+    library: State<'_, Library>,
+    player: State<'_, Player>,
+    cache: State<'_, SearchCache>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchResult>, String> {
+    let limit = limit.unwrap_or(SEARCH_LIMIT);
+    search::search(&library, &player, &cache, &query, limit)
+        .await
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+/// Adds the tracks of the playlist file to the queue and starts playing if stopped,
+/// returns the number of added tracks
+#[tauri::command]
+pub async fn import_playlist<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    path: PathBuf,
+) -> Result<usize, String> {
+    let entries = playlist::read(&path)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())?;
+
+    let player = app.state::<Player>();
+    let tracks = open_entries(&entries);
+    let added = tracks.len();
+    for track in tracks {
+        player.add(track).await;
+    }
+    start_queue(&app, added)?;
+
+    Ok(added)
+}
+
+/// Opens the tracks listed in a playlist, the ones that cannot be read are skipped
+fn open_entries(entries: &[Entry]) -> Vec<Track> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            entry
+                .open()
+                .inspect_err(|err| warn!("Unable to open {}: {}", entry.path.display(), err))
+                .ok()
+        })
+        .collect()
+}
+
+/// Notifies the frontend of the new tracks and starts playing them if stopped
+///
+/// # Arguments
+///
+/// * 'handle' - Gives access to the player
+/// * 'added' - Number of tracks added to the queue
+fn start_queue<H: PlayerHandle + Clone>(handle: &H, added: usize) -> Result<(), String> {
+    handle.opened().map_err(|err| err.to_string())?;
+
+    if added > 0 && !handle.player().is_playing() {
+        spawn_queue(handle);
+    }
+
+    Ok(())
+}
+
+/// Plays the queue in the background, until it ends or is stopped
+fn spawn_queue<H: PlayerHandle + Clone>(handle: &H) {
+    let handle = handle.clone();
+    async_runtime::spawn(async move {
+        if let Err(err) = handle.player().play_queue().await {
+            error!("{}", err);
+        }
+    });
+}
+
+/// Pauses or resumes playback, the queue is started if it is stopped
+pub async fn toggle<H: PlayerHandle + Clone>(handle: &H) {
+    let player = handle.player();
+    if player.is_playing() && !player.is_paused() {
+        player.pause();
+    } else {
+        resume(handle).await;
+    }
+}
+
+/// Resumes playback, the queue is started if it is stopped
+pub async fn resume<H: PlayerHandle + Clone>(handle: &H) {
+    if handle.player().is_playing() {
+        handle.player().play().await;
+    } else {
+        spawn_queue(handle);
+    }
+}
+
+/// Saves the queue to the playlist file
+///
+/// # Arguments
+///
+/// * 'path' - The path to the playlist file, its extension picks the format
+/// * 'relative' - Whether the paths are written relative to the playlist file
+#[tauri::command]
+pub async fn export_playlist(
+    player: State<'_, Player>,
+    path: PathBuf,
+    relative: bool,
+) -> Result<(), String> {
+    let entries: Vec<Entry> = player
+        .get_playlist()
+        .await
+        .iter()
+        .map(Entry::from)
+        .collect();
+
+    playlist::write(&path, &entries, relative)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_saved_playlists(library: State<Library>) -> Result<Vec<SavedPlaylist>, String> {
+    library
+        .saved_playlists()
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+/// Creates an empty playlist and returns its id
+#[tauri::command]
+pub fn create_saved_playlist(library: State<Library>, name: String) -> Result<i64, String> {
+    library
+        .create_saved_playlist(&name)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn rename_saved_playlist(library: State<Library>, id: i64, name: String) -> Result<(), String> {
+    library
+        .rename_saved_playlist(id, &name)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn delete_saved_playlist(library: State<Library>, id: i64) -> Result<(), String> {
+    library
+        .delete_saved_playlist(id)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_saved_playlist_tracks(library: State<Library>, id: i64) -> Result<Vec<Entry>, String> {
+    library
+        .saved_playlist_tracks(id)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+/// Reads the files and appends them to the playlist, files that cannot be read are skipped
+#[tauri::command]
+pub async fn add_to_saved_playlist(
+    library: State<'_, Library>,
+    id: i64,
+    paths: Vec<PathBuf>,
+) -> Result<(), String> {
+    let entries: Vec<Entry> = paths
+        .iter()
+        .filter_map(|path| {
+            Track::try_new(path)
+                .inspect_err(|err| warn!("Unable to open {}: {}", path.display(), err))
+                .ok()
+        })
+        .map(|track| Entry::from(&track))
+        .collect();
+
+    library
+        .add_to_saved_playlist(id, &entries)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn remove_from_saved_playlist(
+    library: State<Library>,
+    id: i64,
+    index: usize,
+) -> Result<(), String> {
+    library
+        .remove_from_saved_playlist(id, index)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn move_in_saved_playlist(
+    library: State<Library>,
+    id: i64,
+    from: usize,
+    to: usize,
+) -> Result<(), String> {
+    library
+        .move_in_saved_playlist(id, from, to)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+/// Replaces the queue with the tracks of the playlist and starts playing them,
+/// returns the number of tracks in the queue
+#[tauri::command]
+pub async fn load_saved_playlist<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    id: i64,
+) -> Result<usize, String> {
+    let entries = app
+        .state::<Library>()
+        .saved_playlist_tracks(id)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())?;
+
+    let tracks = open_entries(&entries);
+    let added = tracks.len();
+    app.state::<Player>()
+        .replace(tracks)
+        .await
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())?;
+    start_queue(&app, added)?;
+
+    Ok(added)
+}
+
+/// Appends the tracks of the queue to the playlist
+#[tauri::command]
+pub async fn append_queue_to_saved_playlist(
+    library: State<'_, Library>,
+    player: State<'_, Player>,
+    id: i64,
+) -> Result<(), String> {
+    let entries: Vec<Entry> = player
+        .get_playlist()
+        .await
+        .iter()
+        .map(Entry::from)
+        .collect();
+
+    library
+        .add_to_saved_playlist(id, &entries)
+        .inspect_err(|err| error!("{}", err))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_shortcuts(settings: State<Mutex<Settings>>) -> Keymap {
+    settings.lock().unwrap().shortcuts.clone()
+}
+
+/// Binds the shortcut to the action, replacing its previous action
+///
+/// # Arguments
+///
+/// * 'keys' - The shortcut, e.g. `CmdOrCtrl+Right`
+/// * 'action' - The action to run, `None` unbinds the shortcut
+#[tauri::command]
+pub fn set_shortcut<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    keys: String,
+    action: Option<Action>,
+) -> Result<Keymap, String> {
+    settings::update(&app, |settings| {
+        shortcut::parse(&keys)?;
+        match action {
+            Some(action) => settings.shortcuts.insert(keys, action),
+            None => settings.shortcuts.remove(&keys),
+        };
+        Ok(())
+    })
+    .and_then(|settings| {
+        shortcut::apply(&app, &settings)?;
+        Ok(settings.shortcuts)
+    })
+    .inspect_err(|err| error!("{}", err))
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn reset_shortcuts<R: Runtime>(app: tauri::AppHandle<R>) -> Result<Keymap, String> {
+    settings::update(&app, |settings| {
+        settings.shortcuts = shortcut::default_keymap();
+        Ok(())
+    })
+    .and_then(|settings| {
+        shortcut::apply(&app, &settings)?;
+        Ok(settings.shortcuts)
+    })
+    .inspect_err(|err| error!("{}", err))
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn is_global_shortcuts(settings: State<Mutex<Settings>>) -> bool {
+    settings.lock().unwrap().global_shortcuts
+}
+
+#[tauri::command]
+pub fn set_global_shortcuts<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    enabled: bool,
+) -> Result<(), String> {
+    settings::update(&app, |settings| {
+        settings.global_shortcuts = enabled;
+        Ok(())
+    })
+    .and_then(|settings| shortcut::apply(&app, &settings))
+    .inspect_err(|err| error!("{}", err))
+    .map_err(|err| err.to_string())
+}
+
+/// Runs the action of a shortcut caught by the webview, the way the menu runs it
+#[tauri::command]
+pub fn run_action<R: Runtime>(app: tauri::AppHandle<R>, action: Action) {
+    menu::dispatch(&app, action);
+}
+
+/// Restores the saved session and runs the request of the command line that started
+/// the player, its paths are added after the saved queue
+pub async fn launch<H: PlayerHandle + Clone>(handle: &H, request: Request) {
+    match request {
+        Request::Open { paths } | Request::Enqueue { paths } if paths.is_empty() => {
+            session::restore(handle, true).await
+        }
+        request => {
+            session::restore(handle, false).await;
+            if let Response::Error { message } = handle_request(handle, request).await {
+                warn!("{}", message);
+            }
+        }
+    }
+}
+
+/// Answers a request another process sent over the socket, e.g. `bragi --next`,
+/// the same way with the app and in daemon mode
+pub async fn handle_request<H: PlayerHandle + Clone>(handle: &H, request: Request) -> Response {
+    let player = handle.player();
+    let result = match request {
+        Request::Open { paths } => {
+            handle.raise();
+            enqueue_paths(handle, &paths, true)
+                .await
+                .map(|count| Response::Added { count })
+        }
+        Request::Enqueue { paths } => enqueue_paths(handle, &paths, false)
+            .await
+            .map(|count| Response::Added { count }),
+        Request::Next => {
+            player.next().await;
+            Ok(Response::Ok)
+        }
+        Request::Previous => {
+            player.previous().await;
+            Ok(Response::Ok)
+        }
+        Request::Stop => {
+            player.stop().await;
+            Ok(Response::Ok)
+        }
+        Request::Pause => {
+            player.pause();
+            Ok(Response::Ok)
+        }
+        Request::Play => {
+            resume(handle).await;
+            Ok(Response::Ok)
+        }
+        Request::Toggle => {
+            toggle(handle).await;
+            Ok(Response::Ok)
+        }
+        Request::Seek { position } => seek_to(player, position)
+            .map(|_| Response::Ok)
+            .map_err(|err| err.to_string()),
+        Request::Volume { level } => {
+            player.set_volume(level);
+            Ok(Response::Ok)
+        }
+        Request::Mute { muted } => {
+            player.set_muted(muted);
+            Ok(Response::Ok)
+        }
+        Request::Repeat { mode } => player
+            .set_repeat(mode)
+            .map(|_| Response::Ok)
+            .map_err(|err| err.to_string()),
+        Request::Shuffle { enabled } => {
+            player.set_shuffle(enabled, None).await;
+            Ok(Response::Ok)
+        }
+        Request::Clear => player
+            .clear()
+            .await
+            .map(|_| Response::Ok)
+            .map_err(|err| err.to_string()),
+        Request::Status => Ok(Response::Status(status(player).await)),
+    };
+
+    result.unwrap_or_else(|message| Response::Error { message })
+}
+
+/// Adds the files, playlists and folders to the queue, the ones that cannot be read are skipped
+///
+/// # Arguments
+///
+/// * 'handle' - Gives access to the player
+/// * 'paths' - The paths to add, folders are scanned for audio files
+/// * 'play' - Whether to play the first added track right away
+///
+/// # Returns
+///
+/// * usize - The number of added tracks
+pub async fn enqueue_paths<H: PlayerHandle + Clone>(
+    handle: &H,
+    paths: &[PathBuf],
+    play: bool,
+) -> Result<usize, String> {
+    let player = handle.player();
+    let first = player.get_playlist().await.len();
+    let mut added = 0;
+
+    for path in paths {
+        match read_tracks(path) {
+            Ok(tracks) => {
+                added += tracks.len();
+                for track in tracks {
+                    player.add(track).await;
+                }
+            }
+            Err(err) => warn!("Unable to open {}: {}", path.display(), err),
+        }
+    }
+    if added == 0 && !paths.is_empty() {
+        return Err("No tracks could be opened".to_string());
+    }
+
+    if play {
+        if added > 0 {
+            player
+                .change_track(first)
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+        start_queue(handle, added)?;
+    } else {
+        handle.opened().map_err(|err| err.to_string())?;
+    }
+
+    Ok(added)
+}
+
+/// Reads the tracks of a file, playlist or folder, the ones that cannot be read are skipped
+fn read_tracks(path: &Path) -> anyhow::Result<Vec<Track>> {
+    if path.is_dir() {
+        Ok(scan(path, &ScanOptions::default())?
+            .into_iter()
+            .filter_map(|path| {
+                Track::try_new(&path)
+                    .inspect_err(|err| warn!("Unable to open {}: {}", path.display(), err))
+                    .ok()
+            })
+            .collect())
+    } else if playlist::is_playlist(path) {
+        Ok(open_entries(&playlist::read(path)?))
+    } else {
+        Ok(vec![Track::try_new(path)?])
+    }
+}
+
+async fn status(player: &Player) -> Status {
+    let state = if !player.is_playing() {
+        PlaybackState::Stopped
+    } else if player.is_paused() {
+        PlaybackState::Paused
+    } else {
+        PlaybackState::Playing
+    };
+    let track = match state {
+        PlaybackState::Stopped => None,
+        _ => player.current_track().await.map(|track| {
+            (
+                player.current_index(),
+                TrackStatus {
+                    path: track.path().clone(),
+                    title: track.title().to_string(),
+                    artist: track.artist().map(str::to_string),
+                    album: track.album().map(str::to_string),
+                    length: track.length(),
+                },
+            )
+        }),
+    };
+    let volume = player.volume_state();
+
+    Status {
+        state,
+        track,
+        queue_length: player.get_playlist().await.len(),
+        position: player.playtime().as_secs_f64(),
+        volume: volume.level,
+        muted: volume.muted,
+    }
+}
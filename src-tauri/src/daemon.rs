@@ -0,0 +1,144 @@
+use std::{future, path::PathBuf, process, sync::Arc};
+
+use anyhow::Context;
+use log::{info, warn, Level, LevelFilter, Log, Metadata, Record};
+use tauri::async_runtime;
+
+use crate::command;
+use crate::handle::PlayerHandle;
+use crate::ipc::{self, Request};
+#[cfg(target_os = "linux")]
+use crate::mpris::Mpris;
+use crate::player::Player;
+use crate::session::{self, Autosave};
+use crate::settings;
+
+/// Player run by `bragi --daemon`, without the window and controlled through the socket only
+struct Daemon {
+    player: Player,
+    data_dir: PathBuf,
+}
+
+impl PlayerHandle for Arc<Daemon> {
+    fn player(&self) -> &Player {
+        &self.player
+    }
+
+    fn data_dir(&self) -> anyhow::Result<PathBuf> {
+        Ok(self.data_dir.clone())
+    }
+}
+
+/// Writes the log to stderr, which the journal keeps when the daemon runs as a service
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "[{}][{}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Runs the player without the window until the process is stopped, the session and the
+/// settings are shared with the app
+///
+/// # Arguments
+///
+/// * 'request' - The request of the command line, run after the session is restored
+/// * 'identifier' - Identifier of the app in `tauri.conf.json`, naming its data directory
+pub fn run(request: Request, identifier: &str) -> anyhow::Result<()> {
+    if log::set_logger(&Logger).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+
+    let (player, rx) = Player::new().context("Unable to open audio device")?;
+    let daemon = Arc::new(Daemon {
+        player,
+        data_dir: data_dir(identifier)?,
+    });
+    let settings = settings::load(&daemon);
+    daemon.player.set_volume_curve(settings.volume_curve);
+    daemon.player.set_replay_gain(settings.replay_gain);
+
+    let handle = daemon.clone();
+    ipc::serve(move |request| async_runtime::block_on(command::handle_request(&handle, request)))?;
+
+    #[cfg(target_os = "linux")]
+    let mpris = async_runtime::block_on(Mpris::start(None, daemon.clone()))
+        .inspect_err(|err| warn!("Unable to start the MPRIS server: {}", err))
+        .ok();
+
+    let handle = daemon.clone();
+    async_runtime::spawn(async move { command::launch(&handle, request).await });
+    async_runtime::spawn(exit_on_signal(daemon.clone()));
+    info!("Running without the window");
+
+    let mut autosave = Autosave::default();
+    while let Some(event) = async_runtime::block_on(autosave.recv(&daemon, &rx)) {
+        #[cfg(target_os = "linux")]
+        if let Some(mpris) = &mpris {
+            if let Err(err) = async_runtime::block_on(mpris.notify(&event)) {
+                warn!("{}", err);
+            }
+        }
+
+        async_runtime::block_on(autosave.changed(&daemon, &event));
+    }
+
+    Ok(())
+}
+
+/// Returns the data directory of the app, the same one the app resolves
+fn data_dir(identifier: &str) -> anyhow::Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .context("Unable to find the data directory")?
+        .join(identifier))
+}
+
+/// Saves the session and exits once the process is interrupted or terminated, e.g. by systemd
+async fn exit_on_signal(daemon: Arc<Daemon>) {
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                warn!("Unable to listen for SIGTERM: {}", err);
+                future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = future::pending::<()>();
+
+    let interrupt = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            warn!("Unable to listen for Ctrl+C: {}", err);
+            future::pending::<()>().await
+        }
+    };
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+
+    info!("Stopping");
+    session::save(&daemon).await;
+    process::exit(0);
+}
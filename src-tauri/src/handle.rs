@@ -0,0 +1,56 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::bail;
+use log::warn;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::player::Player;
+
+/// Gives access to the player and to what hosts it, so the controls behave the same
+/// with the app and without it
+pub trait PlayerHandle: Send + Sync + 'static {
+    fn player(&self) -> &Player;
+
+    /// Returns the directory the session and the settings are kept in
+    fn data_dir(&self) -> anyhow::Result<PathBuf>;
+
+    /// Tells the frontend tracks were opened, so it reloads the queue
+    fn opened(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Brings the window to the front
+    fn raise(&self) {}
+}
+
+impl<R: Runtime> PlayerHandle for AppHandle<R> {
+    fn player(&self) -> &Player {
+        self.state::<Player>().inner()
+    }
+
+    fn data_dir(&self) -> anyhow::Result<PathBuf> {
+        Ok(self.path().app_data_dir()?)
+    }
+
+    fn opened(&self) -> anyhow::Result<()> {
+        Ok(self.emit("open", ())?)
+    }
+
+    fn raise(&self) {
+        if let Some(window) = self.get_webview_window("main") {
+            if let Err(err) = window.unminimize().and_then(|_| window.set_focus()) {
+                warn!("Unable to focus the window: {}", err);
+            }
+        }
+    }
+}
+
+impl PlayerHandle for Arc<Player> {
+    fn player(&self) -> &Player {
+        self
+    }
+
+    fn data_dir(&self) -> anyhow::Result<PathBuf> {
+        bail!("The player keeps no data")
+    }
+}
@@ -0,0 +1,391 @@
+use std::{
+    env,
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use anyhow::Context;
+use interprocess::local_socket::{prelude::*, GenericFilePath, ListenerOptions, Name, Stream};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::player::Repeat;
+
+/// Overrides the path of the socket, so several players do not share one
+const SOCKET_ENV: &str = "BRAGI_SOCKET";
+/// How long a connection may take to send its request and read the answer
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Request another process sends to the running player, one JSON object per line
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Adds the files, playlists and folders to the queue and plays the first of them
+    Open {
+        paths: Vec<PathBuf>,
+    },
+    /// Adds the files, playlists and folders to the queue
+    Enqueue {
+        paths: Vec<PathBuf>,
+    },
+    Next,
+    Previous,
+    Stop,
+    Pause,
+    /// Resumes playback, starts the queue if stopped
+    Play,
+    /// Pauses or resumes, starts the queue if stopped
+    Toggle,
+    /// Seeks in the current track
+    Seek {
+        /// Position in seconds
+        position: f64,
+    },
+    /// Sets the level of the volume control and unmutes
+    Volume {
+        /// Level between 0 and 1
+        level: f32,
+    },
+    Mute {
+        muted: bool,
+    },
+    Repeat {
+        mode: Repeat,
+    },
+    Shuffle {
+        enabled: bool,
+    },
+    /// Stops the playback and removes every track from the queue
+    Clear,
+    Status,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    /// Number of tracks added by `Open` and `Enqueue`
+    Added {
+        count: usize,
+    },
+    Status(Status),
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackState {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// State of the player reported to `Status` requests
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Status {
+    pub state: PlaybackState,
+    /// Current track and its index in the queue, `None` while stopped
+    pub track: Option<(usize, TrackStatus)>,
+    pub queue_length: usize,
+    /// Position in the current track in seconds
+    pub position: f64,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrackStatus {
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Length in seconds
+    pub length: Option<f64>,
+}
+
+/// Returns the path of the socket the running player listens on, a named pipe on Windows.
+/// The socket is per user, other users cannot control the player.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = env::var_os(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_default();
+    if cfg!(windows) {
+        PathBuf::from(format!(r"\\.\pipe\bragi-{}", user))
+    } else {
+        match env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) => PathBuf::from(dir).join("bragi.sock"),
+            None => env::temp_dir().join(format!("bragi-{}.sock", user)),
+        }
+    }
+}
+
+fn socket_name(path: &Path) -> io::Result<Name<'_>> {
+    path.to_fs_name::<GenericFilePath>()
+}
+
+/// Sends the request to the running player
+///
+/// # Returns
+///
+/// * Option<Response> - The answer of the player, `None` if no player is running
+pub fn send(request: &Request) -> anyhow::Result<Option<Response>> {
+    send_to(&socket_path(), request)
+}
+
+/// Sends the request to the player listening on the socket
+///
+/// # Arguments
+///
+/// * 'path' - The path of the socket
+/// * 'request' - The request to send
+fn send_to(path: &Path, request: &Request) -> anyhow::Result<Option<Response>> {
+    let stream = match Stream::connect(socket_name(path)?) {
+        Ok(stream) => stream,
+        Err(err) => {
+            debug!("No player listening on {}: {}", path.display(), err);
+            return Ok(None);
+        }
+    };
+
+    let mut stream = BufReader::new(stream);
+    let mut message = serde_json::to_string(request)?;
+    message.push('\n');
+    stream.get_mut().write_all(message.as_bytes())?;
+
+    let mut line = String::new();
+    stream
+        .read_line(&mut line)
+        .context("The player did not answer")?;
+
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+/// Listens for requests of other processes on a background thread
+///
+/// # Arguments
+///
+/// * 'handler' - Answers the requests, each connection is handled on its own thread
+pub fn serve(handler: impl Fn(Request) -> Response + Send + Sync + 'static) -> anyhow::Result<()> {
+    serve_at(&socket_path(), handler)
+}
+
+/// Listens for requests on the socket on a background thread
+///
+/// # Arguments
+///
+/// * 'path' - The path of the socket
+/// * 'handler' - Answers the requests, each connection is handled on its own thread
+fn serve_at(
+    path: &Path,
+    handler: impl Fn(Request) -> Response + Send + Sync + 'static,
+) -> anyhow::Result<()> {
+    let listener = match listen(path) {
+        //a player that crashed leaves its socket file behind
+        Err(err) if err.kind() == ErrorKind::AddrInUse && cfg!(unix) => {
+            if Stream::connect(socket_name(path)?).is_ok() {
+                return Err(err).context("Another player is listening already");
+            }
+            std::fs::remove_file(path)?;
+            listen(path)?
+        }
+        listener => listener?,
+    };
+    info!("Listening for requests on {}", path.display());
+
+    let handler = Arc::new(handler);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("Unable to accept connection: {}", err);
+                    continue;
+                }
+            };
+            //a client that never sends its request only blocks its own thread
+            let handler = handler.clone();
+            thread::spawn(move || {
+                if let Err(err) = answer(stream, handler.as_ref()) {
+                    warn!("Unable to answer request: {}", err);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+fn listen(path: &Path) -> io::Result<LocalSocketListener> {
+    let options = ListenerOptions::new().name(socket_name(path)?);
+    #[cfg(unix)]
+    let options = {
+        use interprocess::os::unix::local_socket::ListenerOptionsExt;
+        options.mode(0o600)
+    };
+
+    options.create_sync()
+}
+
+fn answer(stream: Stream, handler: &impl Fn(Request) -> Response) -> io::Result<()> {
+    stream.set_recv_timeout(Some(TIMEOUT))?;
+    stream.set_send_timeout(Some(TIMEOUT))?;
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    stream.read_line(&mut line)?;
+
+    let response = match serde_json::from_str(&line) {
+        Ok(request) => {
+            debug!("Received request: {:?}", request);
+            handler(request)
+        }
+        Err(err) => Response::Error {
+            message: format!("Invalid request: {}", err),
+        },
+    };
+
+    let mut message = serde_json::to_string(&response)?;
+    message.push('\n');
+    stream.get_mut().write_all(message.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use interprocess::local_socket::{traits::Stream as _, Stream};
+    use temp_dir::TempDir;
+
+    use super::{send_to, serve_at, socket_name, Request, Response};
+    use crate::player::Repeat;
+
+    fn round_trip(request: Request, json: &str) {
+        assert_eq!(json, serde_json::to_string(&request).unwrap());
+        assert_eq!(request, serde_json::from_str(json).unwrap());
+    }
+
+    #[test]
+    fn request_format() {
+        let request = Request::Enqueue {
+            paths: vec![PathBuf::from("/music/track.mp3")],
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+
+        assert_eq!(
+            r#"{"command":"enqueue","paths":["/music/track.mp3"]}"#,
+            json
+        );
+        assert_eq!(request, serde_json::from_str(&json).unwrap());
+        assert_eq!(
+            Request::Next,
+            serde_json::from_str(r#"{"command":"next"}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn request_previous() {
+        round_trip(Request::Previous, r#"{"command":"previous"}"#);
+    }
+
+    #[test]
+    fn request_stop() {
+        round_trip(Request::Stop, r#"{"command":"stop"}"#);
+    }
+
+    #[test]
+    fn request_pause() {
+        round_trip(Request::Pause, r#"{"command":"pause"}"#);
+    }
+
+    #[test]
+    fn request_play() {
+        round_trip(Request::Play, r#"{"command":"play"}"#);
+    }
+
+    #[test]
+    fn request_seek() {
+        round_trip(
+            Request::Seek { position: 83.5 },
+            r#"{"command":"seek","position":83.5}"#,
+        );
+    }
+
+    #[test]
+    fn request_volume() {
+        round_trip(
+            Request::Volume { level: 0.25 },
+            r#"{"command":"volume","level":0.25}"#,
+        );
+    }
+
+    #[test]
+    fn request_mute() {
+        round_trip(
+            Request::Mute { muted: true },
+            r#"{"command":"mute","muted":true}"#,
+        );
+    }
+
+    #[test]
+    fn request_repeat() {
+        round_trip(
+            Request::Repeat { mode: Repeat::One },
+            r#"{"command":"repeat","mode":"one"}"#,
+        );
+    }
+
+    #[test]
+    fn request_shuffle() {
+        round_trip(
+            Request::Shuffle { enabled: false },
+            r#"{"command":"shuffle","enabled":false}"#,
+        );
+    }
+
+    #[test]
+    fn request_clear() {
+        round_trip(Request::Clear, r#"{"command":"clear"}"#);
+    }
+
+    #[test]
+    fn send_and_serve() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("bragi.sock");
+
+        assert_eq!(None, send_to(&path, &Request::Next).unwrap());
+
+        serve_at(&path, |request| match request {
+            Request::Enqueue { paths } => Response::Added { count: paths.len() },
+            _ => Response::Ok,
+        })
+        .unwrap();
+        //a client that does not send its request does not hold up the others
+        let _stalled = Stream::connect(socket_name(&path).unwrap()).unwrap();
+
+        assert_eq!(
+            Some(Response::Ok),
+            send_to(&path, &Request::Toggle).unwrap()
+        );
+        assert_eq!(
+            Some(Response::Added { count: 2 }),
+            send_to(
+                &path,
+                &Request::Enqueue {
+                    paths: vec![PathBuf::from("a.mp3"), PathBuf::from("b.mp3")]
+                }
+            )
+            .unwrap()
+        );
+        //the listener took over the socket, a second one cannot
+        assert!(serve_at(&path, |_| Response::Ok).is_err());
+    }
+}
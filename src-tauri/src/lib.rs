@@ -1,2 +1,5 @@
+pub mod handle;
+#[cfg(target_os = "linux")]
+pub mod mpris;
 pub mod player;
 mod util;
@@ -0,0 +1,477 @@
+use std::path::PathBuf;
+
+use rusqlite::{Row, ToSql};
+use serde::{Deserialize, Serialize};
+
+use crate::player::track::Track;
+
+use super::Library;
+
+/// Number of entries in a page if the frontend does not ask for another size
+const PAGE_SIZE: usize = 100;
+
+/// Artist of the tracks, the album artist if the tracks have one
+const ARTIST: &str = "COALESCE(album_artist, artist)";
+
+/// Which part of a listing is returned
+#[derive(Clone, Copy, Deserialize, Debug)]
+#[serde(default)]
+pub struct Page {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            limit: PAGE_SIZE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    Name,
+    Year,
+    /// Number of tracks
+    Tracks,
+}
+
+/// Order of a listing, entries with the same sort key are ordered by name
+#[derive(Clone, Copy, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Sort {
+    pub by: SortBy,
+    pub descending: bool,
+}
+
+impl Sort {
+    /// Returns the ORDER BY clause of a listing
+    ///
+    /// # Arguments
+    ///
+    /// * 'name' - Column the entries are named by
+    fn clause(&self, name: &str) -> String {
+        let column = match self.by {
+            SortBy::Name => format!("{} COLLATE NOCASE", name),
+            SortBy::Year => "year".to_string(),
+            SortBy::Tracks => "tracks".to_string(),
+        };
+        let direction = if self.descending { "DESC" } else { "ASC" };
+
+        format!("ORDER BY {} {}, {} COLLATE NOCASE", column, direction, name)
+    }
+}
+
+/// Page of a listing along with the number of entries in the whole listing
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Paged<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Artist {
+    pub name: String,
+    pub albums: usize,
+    pub tracks: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Album {
+    pub title: String,
+    pub artist: Option<String>,
+    pub year: Option<i32>,
+    pub tracks: usize,
+    /// Total length of the tracks in seconds
+    pub length: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Genre {
+    pub name: String,
+    pub tracks: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Year {
+    pub year: i32,
+    pub tracks: usize,
+}
+
+/// Track stored in the library, serialized like the tracks of the queue
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LibraryTrack {
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<u16>,
+    pub disc_number: Option<u16>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub length: Option<f64>,
+}
+
+impl LibraryTrack {
+    fn read(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            path: PathBuf::from(row.get::<_, String>("path")?),
+            title: row.get("title")?,
+            artist: row.get("artist")?,
+            album: row.get("album")?,
+            album_artist: row.get("album_artist")?,
+            track_number: row.get("track_number")?,
+            disc_number: row.get("disc_number")?,
+            year: row.get("year")?,
+            genre: row.get("genre")?,
+            length: row.get("duration")?,
+        })
+    }
+}
+
+impl From<&Track> for LibraryTrack {
+    fn from(track: &Track) -> Self {
+        Self {
+            path: track.path().clone(),
+            title: track.title().to_string(),
+            artist: track.artist().map(str::to_string),
+            album: track.album().map(str::to_string),
+            album_artist: track.album_artist().map(str::to_string),
+            track_number: track.track_number(),
+            disc_number: track.disc_number(),
+            year: track.year(),
+            genre: track.genre().map(str::to_string),
+            length: track.length(),
+        }
+    }
+}
+
+impl Library {
+    /// Lists the artists, tracks without an artist are left out
+    pub fn artists(&self, sort: Sort, page: Page) -> anyhow::Result<Paged<Artist>> {
+        let sql = format!(
+            "SELECT {artist} AS name, COUNT(DISTINCT album) AS albums, COUNT(*) AS tracks,
+                MIN(year) AS year
+            FROM tracks WHERE {artist} IS NOT NULL GROUP BY {artist}",
+            artist = ARTIST
+        );
+
+        self.paged(&sql, &[], &sort.clause("name"), page, |row| {
+            Ok(Artist {
+                name: row.get("name")?,
+                albums: row.get("albums")?,
+                tracks: row.get("tracks")?,
+            })
+        })
+    }
+
+    /// Lists the albums, tracks without an album are left out
+    ///
+    /// # Arguments
+    ///
+    /// * 'artist' - Only lists the albums of this artist if set
+    /// * 'sort' - Order of the albums
+    /// * 'page' - Part of the listing to return
+    pub fn albums(
+        &self,
+        artist: Option<&str>,
+        sort: Sort,
+        page: Page,
+    ) -> anyhow::Result<Paged<Album>> {
+        let sql = format!(
+            "SELECT album AS title, {artist} AS artist, MAX(year) AS year, COUNT(*) AS tracks,
+                TOTAL(duration) AS length
+            FROM tracks WHERE album IS NOT NULL AND (?1 IS NULL OR {artist} = ?1)
+            GROUP BY {artist}, album",
+            artist = ARTIST
+        );
+
+        self.paged(&sql, &[&artist], &sort.clause("title"), page, |row| {
+            Ok(Album {
+                title: row.get("title")?,
+                artist: row.get("artist")?,
+                year: row.get("year")?,
+                tracks: row.get("tracks")?,
+                length: row.get("length")?,
+            })
+        })
+    }
+
+    /// Lists the tracks of the album ordered by disc and track number
+    ///
+    /// # Arguments
+    ///
+    /// * 'album' - Title of the album
+    /// * 'artist' - Artist of the album, albums of every artist with the title are listed if `None`
+    /// * 'page' - Part of the listing to return
+    pub fn album_tracks(
+        &self,
+        album: &str,
+        artist: Option<&str>,
+        page: Page,
+    ) -> anyhow::Result<Paged<LibraryTrack>> {
+        let sql = format!(
+            "SELECT * FROM tracks WHERE album = ?1 AND (?2 IS NULL OR {} = ?2)",
+            ARTIST
+        );
+        let order = "ORDER BY disc_number, track_number, title COLLATE NOCASE, path";
+
+        self.paged(&sql, &[&album, &artist], order, page, LibraryTrack::read)
+    }
+
+    /// Returns every track of the library ordered by path
+    pub fn tracks(&self) -> anyhow::Result<Vec<LibraryTrack>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT * FROM tracks ORDER BY path")?;
+        let tracks = statement
+            .query_map([], LibraryTrack::read)?
+            .collect::<Result<_, _>>()?;
+
+        Ok(tracks)
+    }
+
+    pub fn genres(&self, sort: Sort, page: Page) -> anyhow::Result<Paged<Genre>> {
+        let sql = "SELECT genre AS name, COUNT(*) AS tracks, MIN(year) AS year
+            FROM tracks WHERE genre IS NOT NULL GROUP BY genre";
+
+        self.paged(sql, &[], &sort.clause("name"), page, |row| {
+            Ok(Genre {
+                name: row.get("name")?,
+                tracks: row.get("tracks")?,
+            })
+        })
+    }
+
+    pub fn years(&self, sort: Sort, page: Page) -> anyhow::Result<Paged<Year>> {
+        let sql = "SELECT year, COUNT(*) AS tracks
+            FROM tracks WHERE year IS NOT NULL GROUP BY year";
+
+        self.paged(sql, &[], &sort.clause("year"), page, |row| {
+            Ok(Year {
+                year: row.get("year")?,
+                tracks: row.get("tracks")?,
+            })
+        })
+    }
+
+    /// Runs the query and returns the requested page of its rows
+    ///
+    /// # Arguments
+    ///
+    /// * 'sql' - The query, without ordering and limits
+    /// * 'params' - Parameters of the query
+    /// * 'order' - ORDER BY clause of the listing
+    /// * 'page' - Part of the listing to return
+    /// * 'map' - Maps a row to an entry
+    fn paged<T>(
+        &self,
+        sql: &str,
+        params: &[&dyn ToSql],
+        order: &str,
+        page: Page,
+        map: impl FnMut(&Row) -> rusqlite::Result<T>,
+    ) -> anyhow::Result<Paged<T>> {
+        let connection = self.connection.lock().unwrap();
+        let total =
+            connection.query_row(&format!("SELECT COUNT(*) FROM ({})", sql), params, |row| {
+                row.get(0)
+            })?;
+
+        let mut statement = connection.prepare(&format!(
+            "{} {} LIMIT {} OFFSET {}",
+            sql, order, page.limit, page.offset
+        ))?;
+        let items = statement
+            .query_map(params, map)?
+            .collect::<Result<_, _>>()?;
+
+        Ok(Paged { items, total })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::params;
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    /// Tracks as (album artist, artist, album, disc, track, year, genre)
+    type Tags<'a> = (
+        Option<&'a str>,
+        &'a str,
+        &'a str,
+        Option<u16>,
+        u16,
+        i32,
+        &'a str,
+    );
+
+    const TRACKS: [Tags; 6] = [
+        (None, "Beta", "First", None, 2, 2001, "Rock"),
+        (None, "Beta", "First", None, 1, 2001, "Rock"),
+        (None, "alpha", "Second", Some(2), 1, 1999, "Jazz"),
+        (None, "alpha", "Second", Some(1), 3, 1999, "Jazz"),
+        (Some("Various"), "Gamma", "Mix", None, 1, 2010, "Rock"),
+        (Some("Various"), "Delta", "Mix", None, 2, 2010, "Pop"),
+    ];
+
+    fn library(dir: &TempDir) -> Library {
+        let library = Library::open(dir.path().join("library.db")).unwrap();
+        {
+            let connection = library.connection.lock().unwrap();
+            for (index, track) in TRACKS.iter().enumerate() {
+                let (album_artist, artist, album, disc, number, year, genre) = track;
+                connection
+                    .execute(
+                        "INSERT INTO tracks (path, title, artist, album, album_artist, track_number,
+                            disc_number, year, genre, duration, mtime, size)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 60.0, 0, 0)",
+                        params![
+                            format!("/music/{}.mp3", index),
+                            format!("track {}", index),
+                            artist,
+                            album,
+                            album_artist,
+                            number,
+                            disc,
+                            year,
+                            genre
+                        ],
+                    )
+                    .unwrap();
+            }
+        }
+        library
+    }
+
+    #[test]
+    fn artists() {
+        let dir = TempDir::new().unwrap();
+
+        let artists = library(&dir)
+            .artists(Sort::default(), Page::default())
+            .unwrap();
+
+        assert_eq!(3, artists.total);
+        let names: Vec<&str> = artists.items.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(vec!["alpha", "Beta", "Various"], names);
+        assert_eq!(1, artists.items[2].albums);
+        assert_eq!(2, artists.items[2].tracks);
+    }
+
+    #[test]
+    fn artists_paged() {
+        let dir = TempDir::new().unwrap();
+        let page = Page {
+            offset: 1,
+            limit: 1,
+        };
+
+        let artists = library(&dir).artists(Sort::default(), page).unwrap();
+
+        assert_eq!(3, artists.total);
+        assert_eq!(1, artists.items.len());
+        assert_eq!("Beta", artists.items[0].name);
+    }
+
+    #[test]
+    fn albums_sorted_by_year() {
+        let dir = TempDir::new().unwrap();
+        let sort = Sort {
+            by: SortBy::Year,
+            descending: true,
+        };
+
+        let albums = library(&dir).albums(None, sort, Page::default()).unwrap();
+
+        let titles: Vec<&str> = albums.items.iter().map(|a| a.title.as_str()).collect();
+        assert_eq!(vec!["Mix", "First", "Second"], titles);
+        assert_eq!(120.0, albums.items[0].length);
+    }
+
+    #[test]
+    fn albums_of_artist() {
+        let dir = TempDir::new().unwrap();
+
+        let albums = library(&dir)
+            .albums(Some("Various"), Sort::default(), Page::default())
+            .unwrap();
+
+        assert_eq!(
+            vec![Album {
+                title: "Mix".to_string(),
+                artist: Some("Various".to_string()),
+                year: Some(2010),
+                tracks: 2,
+                length: 120.0,
+            }],
+            albums.items
+        );
+    }
+
+    #[test]
+    fn album_tracks_ordered_by_number() {
+        let dir = TempDir::new().unwrap();
+        let library = library(&dir);
+
+        let first = library
+            .album_tracks("First", Some("Beta"), Page::default())
+            .unwrap();
+        let second = library
+            .album_tracks("Second", None, Page::default())
+            .unwrap();
+
+        let titles = |tracks: &Paged<LibraryTrack>| -> Vec<String> {
+            tracks.items.iter().map(|t| t.title.clone()).collect()
+        };
+        assert_eq!(vec!["track 1", "track 0"], titles(&first));
+        assert_eq!(vec!["track 3", "track 2"], titles(&second));
+    }
+
+    #[test]
+    fn genres_sorted_by_tracks() {
+        let dir = TempDir::new().unwrap();
+        let sort = Sort {
+            by: SortBy::Tracks,
+            descending: true,
+        };
+
+        let genres = library(&dir).genres(sort, Page::default()).unwrap();
+
+        assert_eq!(
+            vec![
+                Genre {
+                    name: "Rock".to_string(),
+                    tracks: 3
+                },
+                Genre {
+                    name: "Jazz".to_string(),
+                    tracks: 2
+                },
+                Genre {
+                    name: "Pop".to_string(),
+                    tracks: 1
+                },
+            ],
+            genres.items
+        );
+    }
+
+    #[test]
+    fn years() {
+        let dir = TempDir::new().unwrap();
+
+        let years = library(&dir)
+            .years(Sort::default(), Page::default())
+            .unwrap();
+
+        let years: Vec<i32> = years.items.iter().map(|y| y.year).collect();
+        assert_eq!(vec![1999, 2001, 2010], years);
+    }
+}
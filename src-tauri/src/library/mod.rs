@@ -0,0 +1,677 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    ops::AddAssign,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::anyhow;
+use log::{debug, info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::{async_runtime, AppHandle, Emitter, Manager, Runtime};
+
+use crate::player::track::Track;
+use crate::scan::{is_listed, list, ScanOptions};
+
+pub mod browse;
+pub mod playlists;
+mod schema;
+pub mod watcher;
+
+pub const LIBRARY_FILE: &str = "library.db";
+
+/// Tracks found in the root folders, persisted in a SQLite database
+pub struct Library {
+    connection: Mutex<Connection>,
+}
+
+/// Modification time and size of a file, a rescan only reads the files where they changed
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FileStamp {
+    /// Modification time in milliseconds since the Unix epoch
+    mtime: i64,
+    size: i64,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+
+        Ok(Self {
+            mtime,
+            size: metadata.len() as i64,
+        })
+    }
+}
+
+/// What a scan changed in the library
+#[derive(Clone, Serialize, Default, Debug, PartialEq)]
+pub struct ScanSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+impl ScanSummary {
+    pub fn is_changed(&self) -> bool {
+        self.added + self.updated + self.removed > 0
+    }
+}
+
+impl AddAssign for ScanSummary {
+    fn add_assign(&mut self, other: Self) {
+        self.added += other.added;
+        self.updated += other.updated;
+        self.removed += other.removed;
+        self.unchanged += other.unchanged;
+    }
+}
+
+impl Library {
+    /// Opens the database, creating it and its tables if needed
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The path to the database file
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut connection = Connection::open(path)?;
+        schema::migrate(&mut connection)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Returns the folders scanned into the library
+    pub fn roots(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT path FROM roots ORDER BY path")?;
+        let roots = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|path| path.map(PathBuf::from))
+            .collect::<Result<_, _>>()?;
+
+        Ok(roots)
+    }
+
+    /// Adds the folder to the roots and returns its canonical path, it is read by the next scan
+    pub fn add_root(&self, path: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
+        let path = path.as_ref().canonicalize()?;
+        self.connection.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO roots (path) VALUES (?1)",
+            params![path.to_string_lossy()],
+        )?;
+        info!("Added {} to the library", path.display());
+
+        Ok(path)
+    }
+
+    /// Removes the folder from the roots along with its tracks,
+    /// returns the number of removed tracks
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The path to the folder, a folder that no longer exists is matched
+    ///   with the roots as given
+    pub fn remove_root(&self, path: impl AsRef<Path>) -> anyhow::Result<usize> {
+        let path = path.as_ref();
+        let roots = self.roots()?;
+        let path = path
+            .canonicalize()
+            .ok()
+            .filter(|path| roots.contains(path))
+            .or_else(|| roots.into_iter().find(|root| root == path))
+            .ok_or(anyhow!("Not a library folder: {}", path.display()))?;
+        let path = path.as_path();
+
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        transaction.execute(
+            "DELETE FROM roots WHERE path = ?1",
+            params![path.to_string_lossy()],
+        )?;
+        let removed = delete_tracks_under(&transaction, path)?;
+        transaction.commit()?;
+        info!(
+            "Removed {} and its {} tracks from the library",
+            path.display(),
+            removed
+        );
+
+        Ok(removed)
+    }
+
+    /// Scans the roots and updates the tracks whose files were added, changed or removed
+    /// since the last scan. Roots that cannot be read keep their tracks.
+    ///
+    /// # Arguments
+    ///
+    /// * 'options' - Depth limit and hidden file policy of the scan
+    pub fn scan(&self, options: &ScanOptions) -> anyhow::Result<ScanSummary> {
+        let roots = self.roots()?;
+        let known = self.stamps()?;
+        let mut summary = ScanSummary::default();
+        let mut found = HashSet::new();
+        let mut changed = Vec::new();
+
+        for root in &roots {
+            let paths = match list(root, options) {
+                Ok(paths) => paths,
+                Err(err) => {
+                    warn!("Unable to scan {}: {}", root.display(), err);
+                    //the tracks of an unmounted drive are not removed
+                    found.extend(known.keys().filter(|path| path.starts_with(root)).cloned());
+                    continue;
+                }
+            };
+
+            for path in paths {
+                if !found.insert(path.clone()) {
+                    continue;
+                }
+
+                changed.extend(read_changed(&path, known.get(&path), &mut summary));
+            }
+        }
+
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for (track, stamp) in &changed {
+            upsert(&transaction, track, stamp)?;
+        }
+        let missing = known.keys().filter(|path| !found.contains(*path));
+        summary.removed = delete_tracks(&transaction, missing)?;
+        transaction.commit()?;
+
+        info!("Library scanned: {:?}", summary);
+        Ok(summary)
+    }
+
+    /// Updates the tracks at or under the path after the watcher saw it change.
+    /// The path may be a file, or a folder that was moved into a root.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The path to the changed file or folder
+    /// * 'options' - Depth limit and hidden file policy of the scan
+    pub fn refresh(&self, path: &Path, options: &ScanOptions) -> anyhow::Result<ScanSummary> {
+        let mut summary = ScanSummary::default();
+        let Some(root) = self
+            .roots()?
+            .into_iter()
+            .find(|root| path.starts_with(root))
+        else {
+            return Ok(summary);
+        };
+
+        if !path.exists() {
+            let connection = self.connection.lock().unwrap();
+            summary.removed = delete_tracks_under(&connection, path)?;
+            return Ok(summary);
+        }
+
+        let paths = if path.is_dir() {
+            list(path, options)?
+        } else {
+            vec![path.to_path_buf()]
+        };
+        let mut changed = Vec::new();
+        for path in paths.iter().filter(|path| is_listed(&root, path, options)) {
+            let known = self.stamp(path)?;
+            changed.extend(read_changed(path, known.as_ref(), &mut summary));
+        }
+
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for (track, stamp) in &changed {
+            upsert(&transaction, track, stamp)?;
+        }
+        transaction.commit()?;
+
+        Ok(summary)
+    }
+
+    /// Returns the modification time and size of the track as of the last scan
+    fn stamp(&self, path: &Path) -> anyhow::Result<Option<FileStamp>> {
+        let stamp = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT mtime, size FROM tracks WHERE path = ?1",
+                params![path.to_string_lossy()],
+                |row| {
+                    Ok(FileStamp {
+                        mtime: row.get(0)?,
+                        size: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(stamp)
+    }
+
+    /// Returns the modification time and size of every track as of the last scan
+    fn stamps(&self) -> anyhow::Result<HashMap<PathBuf, FileStamp>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT path, mtime, size FROM tracks")?;
+        let stamps = statement
+            .query_map([], |row| {
+                Ok((
+                    PathBuf::from(row.get::<_, String>(0)?),
+                    FileStamp {
+                        mtime: row.get(1)?,
+                        size: row.get(2)?,
+                    },
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(stamps)
+    }
+}
+
+/// Reads the track if its file changed since the last scan and counts it in the summary
+///
+/// # Arguments
+///
+/// * 'path' - The path to the file
+/// * 'known' - Modification time and size of the file as of the last scan
+/// * 'summary' - Summary of the scan in progress
+fn read_changed(
+    path: &Path,
+    known: Option<&FileStamp>,
+    summary: &mut ScanSummary,
+) -> Option<(Track, FileStamp)> {
+    let stamp = FileStamp::read(path)
+        .inspect_err(|err| warn!("Unable to read {}: {}", path.display(), err))
+        .ok()?;
+    if known == Some(&stamp) {
+        summary.unchanged += 1;
+        return None;
+    }
+
+    debug!("Reading {}", path.display());
+    let track = Track::try_new(path)
+        .inspect_err(|err| warn!("Unable to read {}: {}", path.display(), err))
+        .ok()?;
+    match known {
+        Some(_) => summary.updated += 1,
+        None => summary.added += 1,
+    }
+
+    Some((track, stamp))
+}
+
+fn upsert(connection: &Connection, track: &Track, stamp: &FileStamp) -> anyhow::Result<()> {
+    connection.execute(
+        "INSERT INTO tracks (path, title, artist, album, album_artist, track_number, disc_number,
+            year, genre, duration, mtime, size)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        ON CONFLICT (path) DO UPDATE SET title = ?2, artist = ?3, album = ?4, album_artist = ?5,
+            track_number = ?6, disc_number = ?7, year = ?8, genre = ?9, duration = ?10,
+            mtime = ?11, size = ?12",
+        params![
+            track.path().to_string_lossy(),
+            track.title(),
+            track.artist(),
+            track.album(),
+            track.album_artist(),
+            track.track_number(),
+            track.disc_number(),
+            track.year(),
+            track.genre(),
+            track.length(),
+            stamp.mtime,
+            stamp.size,
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Deletes the tracks at the paths, returns the number of deleted tracks
+fn delete_tracks<'a>(
+    connection: &Connection,
+    paths: impl IntoIterator<Item = &'a PathBuf>,
+) -> anyhow::Result<usize> {
+    let mut statement = connection.prepare("DELETE FROM tracks WHERE path = ?1")?;
+    let mut deleted = 0;
+    for path in paths {
+        deleted += statement.execute(params![path.to_string_lossy()])?;
+    }
+
+    Ok(deleted)
+}
+
+/// Deletes the track at the path, or the tracks under it if it is a folder,
+/// returns the number of deleted tracks
+fn delete_tracks_under(connection: &Connection, path: &Path) -> anyhow::Result<usize> {
+    let path = path.to_string_lossy();
+    let folder = path.trim_end_matches(MAIN_SEPARATOR);
+    //the paths under the folder sort between the folder followed by the separator
+    //and the folder followed by the character after the separator
+    let start = format!("{}{}", folder, MAIN_SEPARATOR);
+    let end = format!("{}{}", folder, (MAIN_SEPARATOR as u8 + 1) as char);
+
+    let deleted = connection.execute(
+        "DELETE FROM tracks WHERE path = ?1 OR (path >= ?2 AND path < ?3)",
+        params![path, start, end],
+    )?;
+
+    Ok(deleted)
+}
+
+/// Rescans the library in the background and notifies the frontend if anything changed
+pub async fn rescan<R: Runtime>(app: &AppHandle<R>) -> anyhow::Result<ScanSummary> {
+    let handle = app.clone();
+    let summary = async_runtime::spawn_blocking(move || {
+        handle.state::<Library>().scan(&ScanOptions::default())
+    })
+    .await??;
+
+    if summary.is_changed() {
+        app.emit("library_changed", summary.clone())?;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, thread::sleep, time::Duration};
+
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    fn copy(asset: &str, dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::copy(asset, &path).unwrap();
+        path.canonicalize().unwrap()
+    }
+
+    fn library(dir: &TempDir) -> Library {
+        Library::open(dir.path().join("data").join(LIBRARY_FILE)).unwrap()
+    }
+
+    fn titles(library: &Library) -> Vec<(String, String)> {
+        let connection = library.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT path, title FROM tracks ORDER BY path")
+            .unwrap();
+        let titles = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        titles
+    }
+
+    #[test]
+    fn add_root() {
+        let dir = TempDir::new().unwrap();
+        let library = library(&dir);
+
+        library.add_root(dir.path()).unwrap();
+        library.add_root(dir.path()).unwrap();
+
+        assert_eq!(
+            vec![dir.path().canonicalize().unwrap()],
+            library.roots().unwrap()
+        );
+    }
+
+    #[test]
+    fn add_root_not_found() {
+        let dir = TempDir::new().unwrap();
+        let library = library(&dir);
+
+        assert!(library.add_root(dir.path().join("music")).is_err());
+    }
+
+    #[test]
+    fn scan_stores_metadata() {
+        let dir = TempDir::new().unwrap();
+        let path = copy("tests/assets/track.ogg", &dir.path().join("music"), "a.ogg");
+        let library = library(&dir);
+        library.add_root(dir.path().join("music")).unwrap();
+
+        let summary = library.scan(&ScanOptions::default()).unwrap();
+
+        assert_eq!(1, summary.added);
+        let connection = library.connection.lock().unwrap();
+        let (title, artist, album, duration): (String, String, String, f64) = connection
+            .query_row(
+                "SELECT title, artist, album, duration FROM tracks WHERE path = ?1",
+                params![path.to_string_lossy()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            ("title", "artist", "album", 3.0),
+            (
+                title.as_str(),
+                artist.as_str(),
+                album.as_str(),
+                duration.round()
+            )
+        );
+    }
+
+    #[test]
+    fn rescan_only_changed() {
+        let dir = TempDir::new().unwrap();
+        let music = dir.path().join("music");
+        copy("tests/assets/track.ogg", &music, "a.ogg");
+        let changed = copy("tests/assets/track.ogg", &music, "b.ogg");
+        let removed = copy("tests/assets/track.ogg", &music, "c.ogg");
+        let library = library(&dir);
+        library.add_root(&music).unwrap();
+        library.scan(&ScanOptions::default()).unwrap();
+
+        //the modification time is stored in milliseconds
+        sleep(Duration::from_millis(10));
+        fs::copy("tests/assets/track.wav", &changed).unwrap();
+        fs::remove_file(&removed).unwrap();
+        copy("tests/assets/track.ogg", &music.join("new"), "d.ogg");
+        let summary = library.scan(&ScanOptions::default()).unwrap();
+
+        assert_eq!(
+            ScanSummary {
+                added: 1,
+                updated: 1,
+                removed: 1,
+                unchanged: 1,
+            },
+            summary
+        );
+        assert_eq!(3, titles(&library).len());
+    }
+
+    #[test]
+    fn rescan_unchanged() {
+        let dir = TempDir::new().unwrap();
+        copy("tests/assets/track.ogg", &dir.path().join("music"), "a.ogg");
+        let library = library(&dir);
+        library.add_root(dir.path().join("music")).unwrap();
+        library.scan(&ScanOptions::default()).unwrap();
+
+        let summary = library.scan(&ScanOptions::default()).unwrap();
+
+        assert!(!summary.is_changed());
+        assert_eq!(1, summary.unchanged);
+    }
+
+    #[test]
+    fn scan_keeps_unreadable_root() {
+        let dir = TempDir::new().unwrap();
+        let music = dir.path().join("music");
+        copy("tests/assets/track.ogg", &music, "a.ogg");
+        let library = library(&dir);
+        library.add_root(&music).unwrap();
+        library.scan(&ScanOptions::default()).unwrap();
+
+        fs::rename(&music, dir.path().join("moved")).unwrap();
+        let summary = library.scan(&ScanOptions::default()).unwrap();
+
+        assert_eq!(0, summary.removed);
+        assert_eq!(1, titles(&library).len());
+    }
+
+    #[test]
+    fn remove_root() {
+        let dir = TempDir::new().unwrap();
+        copy("tests/assets/track.ogg", &dir.path().join("a"), "a.ogg");
+        copy("tests/assets/track.ogg", &dir.path().join("b"), "b.ogg");
+        let library = library(&dir);
+        let root = dir.path().join("a").canonicalize().unwrap();
+        library.add_root(&root).unwrap();
+        library.add_root(dir.path().join("b")).unwrap();
+        library.scan(&ScanOptions::default()).unwrap();
+
+        let removed = library.remove_root(&root).unwrap();
+
+        assert_eq!(1, removed);
+        assert_eq!(1, library.roots().unwrap().len());
+        assert_eq!(1, titles(&library).len());
+    }
+
+    #[test]
+    fn remove_root_keeps_sibling() {
+        let dir = TempDir::new().unwrap();
+        copy("tests/assets/track.ogg", &dir.path().join("a"), "a.ogg");
+        let sibling = copy("tests/assets/track.ogg", &dir.path().join("ab"), "b.ogg");
+        let library = library(&dir);
+        library.add_root(dir.path().join("a")).unwrap();
+        library.add_root(dir.path().join("ab")).unwrap();
+        library.scan(&ScanOptions::default()).unwrap();
+
+        assert_eq!(1, library.remove_root(dir.path().join("a")).unwrap());
+
+        let titles = titles(&library);
+        assert_eq!(1, titles.len());
+        assert_eq!(sibling.to_string_lossy(), titles[0].0);
+    }
+
+    #[test]
+    fn remove_root_non_canonical() {
+        let dir = TempDir::new().unwrap();
+        copy("tests/assets/track.ogg", &dir.path().join("a"), "a.ogg");
+        copy("tests/assets/track.ogg", &dir.path().join("b"), "b.ogg");
+        let library = library(&dir);
+        library.add_root(dir.path().join("a")).unwrap();
+        library.add_root(dir.path().join("b")).unwrap();
+        library.scan(&ScanOptions::default()).unwrap();
+
+        let trailing_slash = format!("{}/", dir.path().join("a").display());
+        assert_eq!(1, library.remove_root(trailing_slash).unwrap());
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(dir.path().join("b"), &link).unwrap();
+            assert_eq!(1, library.remove_root(&link).unwrap());
+            assert!(library.roots().unwrap().is_empty());
+            assert!(titles(&library).is_empty());
+        }
+    }
+
+    #[test]
+    fn remove_root_missing_folder() {
+        let dir = TempDir::new().unwrap();
+        let music = dir.path().join("music");
+        copy("tests/assets/track.ogg", &music, "a.ogg");
+        let library = library(&dir);
+        let root = library.add_root(&music).unwrap();
+        library.scan(&ScanOptions::default()).unwrap();
+
+        fs::remove_dir_all(&music).unwrap();
+
+        assert_eq!(1, library.remove_root(&root).unwrap());
+        assert!(library.roots().unwrap().is_empty());
+        assert!(library.remove_root(&root).is_err());
+    }
+
+    #[test]
+    fn refresh_added_and_removed() {
+        let dir = TempDir::new().unwrap();
+        let music = dir.path().join("music");
+        fs::create_dir_all(&music).unwrap();
+        let library = library(&dir);
+        let root = library.add_root(&music).unwrap();
+
+        let path = copy("tests/assets/track.ogg", &root, "a.ogg");
+        let added = library.refresh(&path, &ScanOptions::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let removed = library.refresh(&path, &ScanOptions::default()).unwrap();
+
+        assert_eq!(1, added.added);
+        assert_eq!(1, removed.removed);
+        assert!(titles(&library).is_empty());
+    }
+
+    #[test]
+    fn refresh_moved_folder() {
+        let dir = TempDir::new().unwrap();
+        let library = library(&dir);
+        let root = library.add_root(dir.path()).unwrap();
+        copy("tests/assets/track.ogg", &root.join("album"), "a.ogg");
+        copy("tests/assets/track.ogg", &root.join("album"), "b.ogg");
+        library.scan(&ScanOptions::default()).unwrap();
+
+        fs::rename(root.join("album"), root.join("moved")).unwrap();
+        let removed = library
+            .refresh(&root.join("album"), &ScanOptions::default())
+            .unwrap();
+        let added = library
+            .refresh(&root.join("moved"), &ScanOptions::default())
+            .unwrap();
+
+        assert_eq!(2, removed.removed);
+        assert_eq!(2, added.added);
+        assert!(titles(&library)
+            .iter()
+            .all(|(path, _)| Path::new(path).starts_with(root.join("moved"))));
+    }
+
+    #[test]
+    fn refresh_outside_roots() {
+        let dir = TempDir::new().unwrap();
+        let library = library(&dir);
+        library.add_root(dir.path().join("data")).unwrap();
+        let path = copy("tests/assets/track.ogg", dir.path(), "a.ogg");
+
+        let summary = library.refresh(&path, &ScanOptions::default()).unwrap();
+
+        assert!(!summary.is_changed());
+        assert!(titles(&library).is_empty());
+    }
+
+    #[test]
+    fn reopen() {
+        let dir = TempDir::new().unwrap();
+        copy("tests/assets/track.ogg", &dir.path().join("music"), "a.ogg");
+        library(&dir).add_root(dir.path().join("music")).unwrap();
+        library(&dir).scan(&ScanOptions::default()).unwrap();
+
+        let library = library(&dir);
+
+        assert_eq!(1, titles(&library).len());
+    }
+}
@@ -0,0 +1,397 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{anyhow, bail};
+use log::info;
+use rusqlite::{params, OptionalExtension, Row, Transaction};
+use serde::Serialize;
+
+use crate::{player::track::Span, playlist::Entry};
+
+use super::Library;
+
+/// Playlist saved by the user, its tracks are kept in order
+#[derive(Clone, Serialize, Debug, PartialEq)]
+pub struct SavedPlaylist {
+    pub id: i64,
+    pub name: String,
+    pub tracks: usize,
+    /// Total length in seconds of the tracks with a known length
+    pub length: f64,
+}
+
+impl SavedPlaylist {
+    fn read(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            tracks: row.get(2)?,
+            length: row.get(3)?,
+        })
+    }
+}
+
+impl Library {
+    /// Returns the saved playlists ordered by name
+    pub fn saved_playlists(&self) -> anyhow::Result<Vec<SavedPlaylist>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT playlists.id, playlists.name, COUNT(playlist_tracks.path),
+                COALESCE(SUM(playlist_tracks.duration), 0.0)
+            FROM playlists LEFT JOIN playlist_tracks ON playlist_tracks.playlist_id = playlists.id
+            GROUP BY playlists.id
+            ORDER BY playlists.name COLLATE NOCASE",
+        )?;
+        let playlists = statement
+            .query_map([], SavedPlaylist::read)?
+            .collect::<Result<_, _>>()?;
+
+        Ok(playlists)
+    }
+
+    /// Creates an empty playlist and returns its id
+    pub fn create_saved_playlist(&self, name: &str) -> anyhow::Result<i64> {
+        let name = valid_name(name)?;
+        let connection = self.connection.lock().unwrap();
+        if name_taken(&connection, name, None)? {
+            bail!("A playlist named {} already exists", name);
+        }
+
+        connection.execute("INSERT INTO playlists (name) VALUES (?1)", params![name])?;
+        info!("Created playlist {}", name);
+
+        Ok(connection.last_insert_rowid())
+    }
+
+    pub fn rename_saved_playlist(&self, id: i64, name: &str) -> anyhow::Result<()> {
+        let name = valid_name(name)?;
+        let connection = self.connection.lock().unwrap();
+        if name_taken(&connection, name, Some(id))? {
+            bail!("A playlist named {} already exists", name);
+        }
+
+        let renamed = connection.execute(
+            "UPDATE playlists SET name = ?1 WHERE id = ?2",
+            params![name, id],
+        )?;
+        if renamed == 0 {
+            bail!("Invalid playlist: {}", id);
+        }
+        info!("Renamed playlist {} to {}", id, name);
+
+        Ok(())
+    }
+
+    /// Deletes the playlist along with its tracks
+    pub fn delete_saved_playlist(&self, id: i64) -> anyhow::Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        transaction.execute(
+            "DELETE FROM playlist_tracks WHERE playlist_id = ?1",
+            params![id],
+        )?;
+        let deleted = transaction.execute("DELETE FROM playlists WHERE id = ?1", params![id])?;
+        if deleted == 0 {
+            bail!("Invalid playlist: {}", id);
+        }
+        transaction.commit()?;
+        info!("Deleted playlist {}", id);
+
+        Ok(())
+    }
+
+    /// Returns the tracks of the playlist in order
+    pub fn saved_playlist_tracks(&self, id: i64) -> anyhow::Result<Vec<Entry>> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        read_tracks(&transaction, id)
+    }
+
+    /// Appends the tracks to the end of the playlist
+    pub fn add_to_saved_playlist(&self, id: i64, entries: &[Entry]) -> anyhow::Result<()> {
+        self.edit_saved_playlist(id, |tracks| {
+            tracks.extend_from_slice(entries);
+            Ok(())
+        })?;
+        info!("Added {} tracks to playlist {}", entries.len(), id);
+
+        Ok(())
+    }
+
+    pub fn remove_from_saved_playlist(&self, id: i64, index: usize) -> anyhow::Result<()> {
+        self.edit_saved_playlist(id, |tracks| {
+            if index >= tracks.len() {
+                bail!("Invalid index: {}", index);
+            }
+            tracks.remove(index);
+            Ok(())
+        })?;
+        info!("Removed track {} from playlist {}", index, id);
+
+        Ok(())
+    }
+
+    /// Moves a track to another position in the playlist
+    ///
+    /// # Arguments
+    ///
+    /// * 'id' - The id of the playlist
+    /// * 'from' - Index of the track to move
+    /// * 'to' - Index the track ends up at
+    pub fn move_in_saved_playlist(&self, id: i64, from: usize, to: usize) -> anyhow::Result<()> {
+        self.edit_saved_playlist(id, |tracks| {
+            if from >= tracks.len() || to >= tracks.len() {
+                bail!("Invalid index: {}", from.max(to));
+            }
+            let track = tracks.remove(from);
+            tracks.insert(to, track);
+            Ok(())
+        })?;
+        info!("Moved track {} to {} in playlist {}", from, to, id);
+
+        Ok(())
+    }
+
+    /// Rewrites the tracks of the playlist after the edit, playlists are small enough
+    /// that renumbering every track is simpler than shifting the positions
+    fn edit_saved_playlist(
+        &self,
+        id: i64,
+        edit: impl FnOnce(&mut Vec<Entry>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        let mut tracks = read_tracks(&transaction, id)?;
+        edit(&mut tracks)?;
+
+        transaction.execute(
+            "DELETE FROM playlist_tracks WHERE playlist_id = ?1",
+            params![id],
+        )?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO playlist_tracks (playlist_id, position, path, title, artist,
+                    duration, span_start, span_end)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for (position, track) in tracks.iter().enumerate() {
+                let span = track.span;
+                statement.execute(params![
+                    id,
+                    position,
+                    track.path.to_string_lossy(),
+                    track.title,
+                    track.artist,
+                    track.length,
+                    span.map(|span| span.start.as_secs_f64()),
+                    span.and_then(|span| span.end).map(|end| end.as_secs_f64()),
+                ])?;
+            }
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+}
+
+/// Reads the tracks of the playlist, fails if there is no playlist with the id
+fn read_tracks(transaction: &Transaction, id: i64) -> anyhow::Result<Vec<Entry>> {
+    transaction
+        .query_row(
+            "SELECT id FROM playlists WHERE id = ?1",
+            params![id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .ok_or(anyhow!("Invalid playlist: {}", id))?;
+
+    let mut statement = transaction.prepare(
+        "SELECT path, title, artist, duration, span_start, span_end FROM playlist_tracks
+        WHERE playlist_id = ?1 ORDER BY position",
+    )?;
+    let tracks = statement
+        .query_map(params![id], |row| {
+            let start: Option<f64> = row.get(4)?;
+            let end: Option<f64> = row.get(5)?;
+            Ok(Entry {
+                path: PathBuf::from(row.get::<_, String>(0)?),
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                length: row.get(3)?,
+                span: start.map(|start| Span {
+                    start: Duration::from_secs_f64(start),
+                    end: end.map(Duration::from_secs_f64),
+                }),
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(tracks)
+}
+
+fn valid_name(name: &str) -> anyhow::Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("The playlist name must not be empty");
+    }
+
+    Ok(name)
+}
+
+/// Whether another playlist has the name, names differing only in case count as the same
+fn name_taken(
+    connection: &rusqlite::Connection,
+    name: &str,
+    except: Option<i64>,
+) -> anyhow::Result<bool> {
+    let taken = connection
+        .query_row(
+            "SELECT id FROM playlists WHERE name = ?1 COLLATE NOCASE AND id IS NOT ?2",
+            params![name, except],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?;
+
+    Ok(taken.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    fn entry(name: &str) -> Entry {
+        Entry {
+            path: PathBuf::from(format!("/music/{}.mp3", name)),
+            title: Some(name.to_string()),
+            artist: Some("Artist".to_string()),
+            length: Some(60.0),
+            span: None,
+        }
+    }
+
+    fn titles(library: &Library, id: i64) -> Vec<String> {
+        library
+            .saved_playlist_tracks(id)
+            .unwrap()
+            .into_iter()
+            .map(|track| track.title.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn create_and_list() {
+        let dir = TempDir::new().unwrap();
+        let library = Library::open(dir.path().join("library.db")).unwrap();
+
+        let rock = library.create_saved_playlist("rock").unwrap();
+        let jazz = library.create_saved_playlist(" Jazz ").unwrap();
+        library
+            .add_to_saved_playlist(rock, &[entry("a"), entry("b")])
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                SavedPlaylist {
+                    id: jazz,
+                    name: "Jazz".to_string(),
+                    tracks: 0,
+                    length: 0.0,
+                },
+                SavedPlaylist {
+                    id: rock,
+                    name: "rock".to_string(),
+                    tracks: 2,
+                    length: 120.0,
+                },
+            ],
+            library.saved_playlists().unwrap()
+        );
+    }
+
+    #[test]
+    fn create_invalid_name() {
+        let dir = TempDir::new().unwrap();
+        let library = Library::open(dir.path().join("library.db")).unwrap();
+        library.create_saved_playlist("Rock").unwrap();
+
+        assert!(library.create_saved_playlist(" ").is_err());
+        assert!(library.create_saved_playlist("rock").is_err());
+    }
+
+    #[test]
+    fn rename() {
+        let dir = TempDir::new().unwrap();
+        let library = Library::open(dir.path().join("library.db")).unwrap();
+        let id = library.create_saved_playlist("Rock").unwrap();
+        library.create_saved_playlist("Jazz").unwrap();
+
+        library.rename_saved_playlist(id, "ROCK").unwrap();
+
+        assert!(library.rename_saved_playlist(id, "Jazz").is_err());
+        assert!(library.rename_saved_playlist(id + 10, "Pop").is_err());
+        assert_eq!("ROCK", library.saved_playlists().unwrap()[1].name);
+    }
+
+    #[test]
+    fn delete() {
+        let dir = TempDir::new().unwrap();
+        let library = Library::open(dir.path().join("library.db")).unwrap();
+        let id = library.create_saved_playlist("Rock").unwrap();
+        library.add_to_saved_playlist(id, &[entry("a")]).unwrap();
+
+        library.delete_saved_playlist(id).unwrap();
+
+        assert!(library.saved_playlists().unwrap().is_empty());
+        assert!(library.saved_playlist_tracks(id).is_err());
+        assert!(library.delete_saved_playlist(id).is_err());
+    }
+
+    #[test]
+    fn edit_tracks() {
+        let dir = TempDir::new().unwrap();
+        let library = Library::open(dir.path().join("library.db")).unwrap();
+        let id = library.create_saved_playlist("Rock").unwrap();
+        library
+            .add_to_saved_playlist(id, &[entry("a"), entry("b"), entry("c")])
+            .unwrap();
+
+        library.move_in_saved_playlist(id, 0, 2).unwrap();
+        library.remove_from_saved_playlist(id, 0).unwrap();
+        library.add_to_saved_playlist(id, &[entry("d")]).unwrap();
+
+        assert_eq!(vec!["c", "a", "d"], titles(&library, id));
+        assert!(library.remove_from_saved_playlist(id, 3).is_err());
+        assert!(library.move_in_saved_playlist(id, 0, 3).is_err());
+    }
+
+    #[test]
+    fn keep_spans() {
+        let dir = TempDir::new().unwrap();
+        let library = Library::open(dir.path().join("library.db")).unwrap();
+        let id = library.create_saved_playlist("Live").unwrap();
+        let part = Entry {
+            span: Some(Span {
+                start: Duration::from_secs(90),
+                end: None,
+            }),
+            ..entry("live")
+        };
+
+        library.add_to_saved_playlist(id, &[part]).unwrap();
+
+        let tracks = library.saved_playlist_tracks(id).unwrap();
+        assert_eq!(
+            Some(Span {
+                start: Duration::from_secs(90),
+                end: None,
+            }),
+            tracks[0].span
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use log::info;
+use rusqlite::Connection;
+
+/// Statements bringing the database from one version to the next,
+/// the version is kept in the `user_version` pragma
+const MIGRATIONS: [&str; 3] = [
+    "CREATE TABLE roots (
+        path TEXT PRIMARY KEY NOT NULL
+    );
+    CREATE TABLE tracks (
+        path TEXT PRIMARY KEY NOT NULL,
+        title TEXT NOT NULL,
+        artist TEXT,
+        album TEXT,
+        album_artist TEXT,
+        track_number INTEGER,
+        disc_number INTEGER,
+        year INTEGER,
+        genre TEXT,
+        duration REAL,
+        mtime INTEGER NOT NULL,
+        size INTEGER NOT NULL
+    );
+    CREATE INDEX tracks_artist ON tracks (artist);
+    CREATE INDEX tracks_album ON tracks (album_artist, album);
+    CREATE INDEX tracks_genre ON tracks (genre);
+    CREATE INDEX tracks_year ON tracks (year);",
+    //the browsing queries group the albums by the album artist, falling back to the artist
+    "CREATE INDEX tracks_album_of_artist ON tracks (COALESCE(album_artist, artist), album);",
+    //the tracks of a playlist keep their own metadata, they do not have to be in the library
+    "CREATE TABLE playlists (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE playlist_tracks (
+        playlist_id INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        title TEXT,
+        artist TEXT,
+        duration REAL,
+        span_start REAL,
+        span_end REAL
+    );
+    CREATE INDEX playlist_tracks_playlist ON playlist_tracks (playlist_id, position);",
+];
+
+/// Applies the migrations the database has not seen yet
+pub fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+        info!("Library database migrated to version {}", index + 1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{migrate, MIGRATIONS};
+
+    #[test]
+    fn migrate_once() {
+        let mut connection = Connection::open_in_memory().unwrap();
+
+        migrate(&mut connection).unwrap();
+        migrate(&mut connection).unwrap();
+
+        let version: usize = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(MIGRATIONS.len(), version);
+    }
+}
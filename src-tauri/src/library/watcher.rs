@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, warn};
+use notify::{
+    event::{ModifyKind, RenameMode},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _,
+};
+use tauri::{async_runtime::block_on, AppHandle, Emitter, Manager, Runtime};
+
+use crate::{player::Player, scan::ScanOptions};
+
+use super::{Library, ScanSummary};
+
+/// How long a path has to go without events before it is refreshed, so writing or
+/// copying a file refreshes it once it is complete
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches the library roots, so the library and the queue follow the files
+/// that are created, changed, moved or deleted
+pub struct Watcher {
+    watcher: Mutex<RecommendedWatcher>,
+}
+
+impl Watcher {
+    /// Starts watching the roots of the library
+    pub fn new<R: Runtime>(app: &AppHandle<R>) -> anyhow::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let watcher = Self {
+            watcher: Mutex::new(notify::recommended_watcher(tx)?),
+        };
+
+        for root in app.state::<Library>().roots()? {
+            if let Err(err) = watcher.watch(&root) {
+                warn!("Unable to watch {}: {}", root.display(), err);
+            }
+        }
+
+        let handle = app.clone();
+        thread::spawn(move || {
+            let mut pending = Pending::default();
+            loop {
+                let event = match pending.next_deadline() {
+                    Some(deadline) => {
+                        rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match event {
+                    Ok(Ok(event)) => {
+                        for path in handle_event(&handle, event) {
+                            pending.add(path, Instant::now());
+                        }
+                    }
+                    Ok(Err(err)) => warn!("Unable to watch the library: {}", err),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+
+                let due = pending.take_due(Instant::now());
+                if !due.is_empty() {
+                    refresh(&handle, &due);
+                }
+            }
+        });
+
+        Ok(watcher)
+    }
+
+    /// Watches the folder and its subfolders
+    pub fn watch(&self, path: &Path) -> anyhow::Result<()> {
+        self.watcher
+            .lock()
+            .unwrap()
+            .watch(path, RecursiveMode::Recursive)?;
+        debug!("Watching {}", path.display());
+
+        Ok(())
+    }
+
+    pub fn unwatch(&self, path: &Path) -> anyhow::Result<()> {
+        self.watcher.lock().unwrap().unwatch(path)?;
+        debug!("Stopped watching {}", path.display());
+
+        Ok(())
+    }
+}
+
+/// Paths waiting for their events to settle before they are refreshed
+#[derive(Default)]
+struct Pending {
+    deadlines: HashMap<PathBuf, Instant>,
+}
+
+impl Pending {
+    /// Delays the refresh of the path until it had no events for the debounce window
+    fn add(&mut self, path: PathBuf, now: Instant) {
+        self.deadlines.insert(path, now + DEBOUNCE);
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.values().min().copied()
+    }
+
+    /// Removes and returns the paths whose window is over, sorted
+    fn take_due(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut due: Vec<PathBuf> = self
+            .deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &due {
+            self.deadlines.remove(path);
+        }
+        due.sort();
+
+        due
+    }
+}
+
+/// Follows moved files right away and returns the paths to refresh
+fn handle_event<R: Runtime>(app: &AppHandle<R>, event: Event) -> Vec<PathBuf> {
+    match event.kind {
+        //both paths are only known if the file stayed in a watched folder
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            if let [from, to] = event.paths.as_slice() {
+                if let Err(err) = block_on(app.state::<Player>().relocate(from, to)) {
+                    error!("{}", err);
+                }
+            }
+        }
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {}
+        _ => return Vec::new(),
+    }
+
+    event.paths
+}
+
+fn refresh<R: Runtime>(app: &AppHandle<R>, paths: &[PathBuf]) {
+    let mut summary = ScanSummary::default();
+    for path in paths {
+        match app
+            .state::<Library>()
+            .refresh(path, &ScanOptions::default())
+        {
+            Ok(refreshed) => summary += refreshed,
+            Err(err) => warn!("Unable to update {}: {}", path.display(), err),
+        }
+
+        let available = path.exists();
+        if let Err(err) = block_on(app.state::<Player>().set_available(path, available)) {
+            error!("{}", err);
+        }
+    }
+
+    if summary.is_changed() {
+        debug!("Library updated: {:?}", summary);
+        if let Err(err) = app.emit("library_changed", summary) {
+            error!("{}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coalesce_events_per_path() {
+        let mut pending = Pending::default();
+        let start = Instant::now();
+
+        pending.add(PathBuf::from("b.mp3"), start);
+        pending.add(PathBuf::from("a.mp3"), start);
+        pending.add(PathBuf::from("a.mp3"), start + DEBOUNCE / 2);
+
+        assert_eq!(Some(start + DEBOUNCE), pending.next_deadline());
+        assert!(pending.take_due(start).is_empty());
+        assert_eq!(
+            vec![PathBuf::from("b.mp3")],
+            pending.take_due(start + DEBOUNCE)
+        );
+        assert_eq!(
+            vec![PathBuf::from("a.mp3")],
+            pending.take_due(start + DEBOUNCE * 2)
+        );
+        assert_eq!(None, pending.next_deadline());
+    }
+}
@@ -1,32 +1,128 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use log::error;
+use std::{process, sync::Mutex};
+
+use cli::Args;
+use library::{watcher::Watcher, Library, LIBRARY_FILE};
+use log::{error, warn};
 use menu::{event_handler, menu};
-use player::{Event, Player};
-use tauri::{async_runtime, Emitter, Manager};
+use player::{output::NullOutput, Event, Player};
+use search::SearchCache;
+use session::Autosave;
+use tauri::{async_runtime, Emitter, Manager, RunEvent};
 
+mod cli;
 mod command;
+mod daemon;
+mod handle;
+mod ipc;
+mod library;
 mod menu;
+#[cfg(target_os = "linux")]
+mod mpris;
 mod player;
+mod playlist;
+mod scan;
+mod search;
+mod session;
+mod settings;
+mod shortcut;
 mod util;
 
 fn main() {
+    let context = tauri::generate_context!();
+    let request = match cli::parse(cli::args()) {
+        Ok(Args::Launch(request)) => request,
+        Ok(Args::Daemon(request)) => {
+            if !cli::forward(&request) {
+                //the daemon shares the data directory of the app
+                if let Err(err) = daemon::run(request, &context.config().identifier) {
+                    eprintln!("{:#}", err);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        Ok(Args::Remote(request)) => process::exit(cli::run_remote(&request)),
+        Ok(Args::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Ok(Args::Version) => {
+            println!("bragi {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    //only one player runs, a second launch hands its paths over and focuses the window
+    if cli::forward(&request) {
+        return;
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .plugin(
             tauri_plugin_log::Builder::default()
                 .level(log::LevelFilter::Info)
                 .build(),
         )
         .setup(|app| {
-            let (player, rx) = Player::new().expect("failed to init player");
+            let (player, rx) = Player::new()
+                .or_else(|err| {
+                    warn!("Unable to open audio device, using null output: {}", err);
+                    Player::with_output(NullOutput::default())
+                })
+                .expect("failed to init player");
             let handle = app.handle().clone();
             app.manage(player);
+            app.manage(Library::open(
+                app.path().app_data_dir()?.join(LIBRARY_FILE),
+            )?);
+            app.manage(SearchCache::default());
+            match Watcher::new(app.handle()) {
+                Ok(watcher) => {
+                    app.manage(watcher);
+                }
+                Err(err) => warn!("Unable to watch the library: {}", err),
+            }
+
+            let settings = settings::load(app.handle());
+            let player = app.state::<Player>();
+            player.set_volume_curve(settings.volume_curve);
+            player.set_replay_gain(settings.replay_gain);
+            shortcut::register_global(app.handle(), &settings.shortcuts, settings.global_shortcuts);
+            app.manage(Mutex::new(settings));
+
+            #[cfg(target_os = "linux")]
+            {
+                let handle = app.handle().clone();
+                async_runtime::spawn(async move {
+                    match mpris::Mpris::start(None, handle.clone()).await {
+                        Ok(mpris) => {
+                            handle.manage(mpris);
+                        }
+                        Err(err) => warn!("Unable to start the MPRIS server: {}", err),
+                    }
+                });
+            }
 
             async_runtime::spawn(async move {
-                while let Ok(event) = rx.recv() {
+                let mut autosave = Autosave::default();
+                while let Some(event) = autosave.recv(&handle, &rx).await {
+                    #[cfg(target_os = "linux")]
+                    if let Some(mpris) = handle.try_state::<mpris::Mpris>() {
+                        if let Err(err) = mpris.notify(&event).await {
+                            warn!("{}", err);
+                        }
+                    }
+                    autosave.changed(&handle, &event).await;
+
                     match event {
                         Event::TrackChanged(index) => {
                             if let Err(err) = handle.emit("track_changed", index) {
@@ -38,10 +134,58 @@ fn main() {
                                 error!("{}", err);
                             }
                         }
+                        Event::PlaybackPaused => {
+                            if let Err(err) = handle.emit("playback_paused", ()) {
+                                error!("{}", err);
+                            }
+                        }
+                        Event::PlaybackResumed => {
+                            if let Err(err) = handle.emit("playback_resumed", ()) {
+                                error!("{}", err);
+                            }
+                        }
+                        Event::RepeatChanged(repeat) => {
+                            if let Err(err) = handle.emit("repeat_changed", repeat) {
+                                error!("{}", err);
+                            }
+                        }
+                        Event::ShuffleChanged(enabled) => {
+                            if let Err(err) = handle.emit("shuffle_changed", enabled) {
+                                error!("{}", err);
+                            }
+                            menu::set_shuffle_checked(&handle, enabled);
+                        }
+                        Event::PlaylistChanged => {
+                            if let Err(err) = handle.emit("playlist_changed", ()) {
+                                error!("{}", err);
+                            }
+                        }
+                        Event::VolumeChanged(volume) => {
+                            if let Err(err) = handle.emit("volume_changed", volume) {
+                                error!("{}", err);
+                            }
+                        }
                     }
                 }
             });
 
+            let handle = app.handle().clone();
+            if let Err(err) = ipc::serve(move |request| {
+                async_runtime::block_on(command::handle_request(&handle, request))
+            }) {
+                warn!("Unable to listen for requests: {}", err);
+            }
+
+            let handle = app.handle().clone();
+            async_runtime::spawn(async move { command::launch(&handle, request).await });
+
+            let handle = app.handle().clone();
+            async_runtime::spawn(async move {
+                if let Err(err) = library::rescan(&handle).await {
+                    warn!("Unable to scan the library: {}", err);
+                }
+            });
+
             Ok(())
         })
         .menu(menu())
@@ -52,14 +196,66 @@ fn main() {
             command::pause,
             command::is_playing,
             command::get_playlist,
+            command::current_index,
+            command::get_volume,
             command::set_volume,
+            command::get_volume_state,
+            command::set_muted,
+            command::get_volume_step,
+            command::set_volume_step,
+            command::set_volume_curve,
+            command::set_crossfade,
+            command::get_replay_gain,
+            command::set_replay_gain,
+            command::set_shuffle,
+            command::is_shuffle,
+            command::set_repeat,
+            command::get_repeat,
+            command::analyze_loudness,
             command::playtime,
             command::change_track,
+            command::remove_track,
+            command::move_track,
+            command::insert_after_current,
+            command::clear_queue,
             command::get_album_cover,
             command::next_track,
             command::previous_track,
             command::seek,
+            command::get_library_roots,
+            command::add_library_root,
+            command::remove_library_root,
+            command::scan_library,
+            command::get_artists,
+            command::get_albums,
+            command::get_album_tracks,
+            command::get_genres,
+            command::get_years,
+            command::search,
+            command::import_playlist,
+            command::export_playlist,
+            command::get_saved_playlists,
+            command::create_saved_playlist,
+            command::rename_saved_playlist,
+            command::delete_saved_playlist,
+            command::get_saved_playlist_tracks,
+            command::add_to_saved_playlist,
+            command::remove_from_saved_playlist,
+            command::move_in_saved_playlist,
+            command::load_saved_playlist,
+            command::append_queue_to_saved_playlist,
+            command::get_shortcuts,
+            command::set_shortcut,
+            command::reset_shortcuts,
+            command::is_global_shortcuts,
+            command::set_global_shortcuts,
+            command::run_action,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                async_runtime::block_on(session::save(app));
+            }
+        });
 }
@@ -1,52 +1,232 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
 
 use log::{debug, error};
+use serde::{Deserialize, Serialize};
 use tauri::{
-    menu::{Menu, MenuEvent, MenuItemBuilder, SubmenuBuilder},
+    menu::{CheckMenuItem, CheckMenuItemBuilder, Menu, MenuEvent, MenuItemBuilder, SubmenuBuilder},
     AppHandle, Manager, Runtime,
 };
 use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 
 use crate::command;
 use crate::player::Player;
+use crate::playlist;
+use crate::scan::{scan, ScanOptions, EXTENSIONS};
+use crate::settings::{self, Settings};
+use crate::shortcut::{accelerator, Keymap};
+
+/// Distance the seek actions move the position by
+const SEEK_STEP: Duration = Duration::from_secs(5);
+
+/// Action run by a menu item or a shortcut, the serialized name is the id of the menu item
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Open,
+    OpenFolder,
+    OpenPlaylist,
+    ExportPlaylist,
+    /// Exports the queue with absolute paths, for players that do not resolve relative ones
+    ExportPlaylistAbsolute,
+    AddLibraryFolder,
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Previous,
+    Next,
+    SeekForward,
+    SeekBackward,
+    Shuffle,
+    Repeat,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+}
+
+impl Action {
+    const ALL: [Action; 20] = [
+        Action::Quit,
+        Action::Open,
+        Action::OpenFolder,
+        Action::OpenPlaylist,
+        Action::ExportPlaylist,
+        Action::ExportPlaylistAbsolute,
+        Action::AddLibraryFolder,
+        Action::Play,
+        Action::Pause,
+        Action::PlayPause,
+        Action::Stop,
+        Action::Previous,
+        Action::Next,
+        Action::SeekForward,
+        Action::SeekBackward,
+        Action::Shuffle,
+        Action::Repeat,
+        Action::VolumeUp,
+        Action::VolumeDown,
+        Action::Mute,
+    ];
+
+    /// Returns the id of the menu item of the action
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Open => "open",
+            Action::OpenFolder => "open_folder",
+            Action::OpenPlaylist => "open_playlist",
+            Action::ExportPlaylist => "export_playlist",
+            Action::ExportPlaylistAbsolute => "export_playlist_absolute",
+            Action::AddLibraryFolder => "add_library_folder",
+            Action::Play => "play",
+            Action::Pause => "pause",
+            Action::PlayPause => "play_pause",
+            Action::Stop => "stop",
+            Action::Previous => "previous",
+            Action::Next => "next",
+            Action::SeekForward => "seek_forward",
+            Action::SeekBackward => "seek_backward",
+            Action::Shuffle => "shuffle",
+            Action::Repeat => "repeat",
+            Action::VolumeUp => "volume_up",
+            Action::VolumeDown => "volume_down",
+            Action::Mute => "mute",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+}
 
-const EXTENSIONS: [&str; 2] = ["mp3", "flac"];
+/// Handle of the Shuffle item, so its check mark follows shuffle changed by other controls
+pub struct ShuffleItem<R: Runtime>(Mutex<CheckMenuItem<R>>);
 
 pub fn menu<R>() -> impl FnOnce(&AppHandle<R>) -> tauri::Result<Menu<R>> + Send
 where
     R: Runtime,
 {
-    |app| {
-        let quit = MenuItemBuilder::new("Quit").id("quit").build(app)?;
-        let open = MenuItemBuilder::new("Open Files").id("open").build(app)?;
-        let open_folder = MenuItemBuilder::new("Open Folder")
-            .id("open_folder")
-            .build(app)?;
-        let submenu_file = SubmenuBuilder::new(app, "File")
-            .items(&[&open, &open_folder, &quit])
-            .build()?;
-
-        let play = MenuItemBuilder::new("Play").id("play").build(app)?;
-        let pause = MenuItemBuilder::new("Pause").id("pause").build(app)?;
-        let stop = MenuItemBuilder::new("Stop").id("stop").build(app)?;
-        let previous = MenuItemBuilder::new("Previous").id("previous").build(app)?;
-        let next = MenuItemBuilder::new("Next").id("next").build(app)?;
-        let submenu_playback = SubmenuBuilder::new(app, "Playback")
-            .items(&[&play, &pause, &stop, &previous, &next])
-            .build()?;
-
-        let volume_up = MenuItemBuilder::new("Volume Up")
-            .id("volume_up")
-            .build(app)?;
-        let volume_down = MenuItemBuilder::new("Volume Down")
-            .id("volume_down")
-            .build(app)?;
-        let mute = MenuItemBuilder::new("Mute").id("mute").build(app)?;
-        let submenu_volume = SubmenuBuilder::new(app, "Volume")
-            .items(&[&volume_up, &volume_down, &mute])
-            .build()?;
-
-        Menu::with_items(app, &[&submenu_file, &submenu_playback, &submenu_volume])
+    |app| build(app, &settings::load(app).shortcuts)
+}
+
+/// Builds the menu, the items show the shortcuts of their actions
+///
+/// # Arguments
+///
+/// * 'keymap' - The bindings the accelerators are taken from
+pub fn build<R: Runtime>(app: &AppHandle<R>, keymap: &Keymap) -> tauri::Result<Menu<R>> {
+    let item = |title: &str, action: Action| {
+        let builder = MenuItemBuilder::new(title).id(action.id());
+        match accelerator(keymap, action) {
+            Some(accelerator) => builder.accelerator(accelerator),
+            None => builder,
+        }
+        .build(app)
+    };
+
+    let quit = item("Quit", Action::Quit)?;
+    let open = item("Open Files", Action::Open)?;
+    let open_folder = item("Open Folder", Action::OpenFolder)?;
+    let open_playlist = item("Open Playlist", Action::OpenPlaylist)?;
+    let export_playlist = item("Export Playlist", Action::ExportPlaylist)?;
+    let export_playlist_absolute = item(
+        "Export Playlist (absolute paths)",
+        Action::ExportPlaylistAbsolute,
+    )?;
+    let add_library_folder = item("Add Folder to Library", Action::AddLibraryFolder)?;
+    let submenu_file = SubmenuBuilder::new(app, "File")
+        .items(&[
+            &open,
+            &open_folder,
+            &open_playlist,
+            &export_playlist,
+            &export_playlist_absolute,
+            &add_library_folder,
+            &quit,
+        ])
+        .build()?;
+
+    let play = item("Play", Action::Play)?;
+    let pause = item("Pause", Action::Pause)?;
+    let play_pause = item("Play/Pause", Action::PlayPause)?;
+    let stop = item("Stop", Action::Stop)?;
+    let previous = item("Previous", Action::Previous)?;
+    let next = item("Next", Action::Next)?;
+    let seek_forward = item("Seek Forward", Action::SeekForward)?;
+    let seek_backward = item("Seek Backward", Action::SeekBackward)?;
+    let shuffle = CheckMenuItemBuilder::new("Shuffle").id(Action::Shuffle.id());
+    let shuffle = match accelerator(keymap, Action::Shuffle) {
+        Some(accelerator) => shuffle.accelerator(accelerator),
+        None => shuffle,
+    }
+    .checked(
+        app.try_state::<Player>()
+            .is_some_and(|player| player.is_shuffle()),
+    )
+    .build(app)?;
+    //the menu is built again when the shortcuts change
+    match app.try_state::<ShuffleItem<R>>() {
+        Some(item) => *item.0.lock().unwrap() = shuffle.clone(),
+        None => {
+            app.manage(ShuffleItem(Mutex::new(shuffle.clone())));
+        }
+    }
+    let repeat = item("Repeat", Action::Repeat)?;
+    let submenu_playback = SubmenuBuilder::new(app, "Playback")
+        .items(&[&play, &pause, &play_pause, &stop, &previous, &next])
+        .separator()
+        .items(&[&seek_forward, &seek_backward])
+        .separator()
+        .items(&[&shuffle, &repeat])
+        .build()?;
+
+    let volume_up = item("Volume Up", Action::VolumeUp)?;
+    let volume_down = item("Volume Down", Action::VolumeDown)?;
+    let mute = item("Mute", Action::Mute)?;
+    let submenu_volume = SubmenuBuilder::new(app, "Volume")
+        .items(&[&volume_up, &volume_down, &mute])
+        .build()?;
+
+    Menu::with_items(app, &[&submenu_file, &submenu_playback, &submenu_volume])
+}
+
+/// Asks for the playlist file and saves the queue to it
+///
+/// # Arguments
+///
+/// * 'relative' - Whether the paths are written relative to the playlist file
+fn export_playlist<R: Runtime>(app: AppHandle<R>, relative: bool) {
+    app.dialog()
+        .file()
+        .add_filter("M3U8", &["m3u8"])
+        .add_filter("PLS", &["pls"])
+        .add_filter("XSPF", &["xspf"])
+        .set_file_name("playlist.m3u8")
+        .save_file(move |path_buf| match path_buf {
+            Some(path) => {
+                tauri::async_runtime::spawn(async move {
+                    let path = path.into_path().expect("Not a file path");
+                    let player = app.state::<Player>();
+                    if let Err(err) = command::export_playlist(player, path, relative).await {
+                        error!("{}", err);
+                    }
+                });
+            }
+            None => debug!("Nothing selected"),
+        });
+}
+
+/// Checks or unchecks the Shuffle item
+pub fn set_shuffle_checked<R: Runtime>(app: &AppHandle<R>, checked: bool) {
+    if let Some(item) = app.try_state::<ShuffleItem<R>>() {
+        if let Err(err) = item.0.lock().unwrap().set_checked(checked) {
+            error!("{}", err);
+        }
     }
 }
 
@@ -54,132 +234,201 @@ pub fn event_handler<R>() -> impl Fn(&AppHandle<R>, MenuEvent) + Send + Sync + '
 where
     R: Runtime,
 {
-    |app: &AppHandle<R>, event: MenuEvent| {
-        let handle = app.clone();
-        match event.id().0.as_ref() {
-            "quit" => {
-                std::process::exit(0);
-            }
-            "open" => app
-                .dialog()
-                .file()
-                .add_filter("Audio", &EXTENSIONS)
-                .pick_files(move |file_paths| {
-                    match file_paths {
-                        Some(paths) => {
-                            tauri::async_runtime::spawn(async move {
-                                let path_bufs = paths
-                                    .iter()
-                                    .map(|entry| {
-                                        entry.clone().into_path().expect("Not a file path")
-                                    })
-                                    .collect();
-
-                                if let Err(err) = command::play_queue(&handle, path_bufs).await {
-                                    error!("{}", err);
-                                };
-                            });
+    |app: &AppHandle<R>, event: MenuEvent| match Action::from_id(event.id().0.as_ref()) {
+        Some(action) => dispatch(app, action),
+        None => error!("Unknown event"),
+    }
+}
+
+/// Runs the action, the menu items and the shortcuts all go through here
+pub fn dispatch<R: Runtime>(app: &AppHandle<R>, action: Action) {
+    let handle = app.clone();
+    match action {
+        Action::Quit => {
+            //exits through the run loop, so the session is saved
+            app.exit(0);
+        }
+        Action::Open => app
+            .dialog()
+            .file()
+            .add_filter("Audio", &EXTENSIONS)
+            .pick_files(move |file_paths| {
+                match file_paths {
+                    Some(paths) => {
+                        tauri::async_runtime::spawn(async move {
+                            let path_bufs = paths
+                                .iter()
+                                .map(|entry| entry.clone().into_path().expect("Not a file path"))
+                                .collect();
+
+                            if let Err(err) = command::play_queue(&handle, path_bufs).await {
+                                error!("{}", err);
+                            };
+                        });
+                    }
+                    None => debug!("Nothing selected"),
+                };
+            }),
+        Action::OpenFolder => app
+            .dialog()
+            .file()
+            .pick_folder(move |path_buf| match path_buf {
+                Some(path) => {
+                    tauri::async_runtime::spawn(async move {
+                        let paths: Vec<PathBuf> = open_folder(path.as_path().unwrap())
+                            .inspect_err(|err| error!("{}", err))
+                            .unwrap_or_default();
+
+                        if paths.is_empty() {
+                            handle
+                                .dialog()
+                                .message("No audio files found")
+                                .title("Open Folder")
+                                .kind(MessageDialogKind::Error)
+                                .blocking_show();
+                            return;
+                        }
+
+                        if let Err(err) = command::play_queue(&handle, paths).await {
+                            error!("{}", err);
+                        };
+                    });
+                }
+                None => debug!("Nothing selected"),
+            }),
+        Action::OpenPlaylist => app
+            .dialog()
+            .file()
+            .add_filter("Playlist", &playlist::EXTENSIONS)
+            .pick_file(move |path_buf| match path_buf {
+                Some(path) => {
+                    tauri::async_runtime::spawn(async move {
+                        let path = path.into_path().expect("Not a file path");
+                        if let Err(err) = command::import_playlist(handle, path).await {
+                            error!("{}", err);
                         }
-                        None => debug!("Nothing selected"),
-                    };
-                }),
-            "open_folder" => app
-                .dialog()
+                    });
+                }
+                None => debug!("Nothing selected"),
+            }),
+        Action::ExportPlaylist => export_playlist(handle, true),
+        Action::ExportPlaylistAbsolute => export_playlist(handle, false),
+        Action::AddLibraryFolder => {
+            app.dialog()
                 .file()
                 .pick_folder(move |path_buf| match path_buf {
                     Some(path) => {
                         tauri::async_runtime::spawn(async move {
-                            let paths: Vec<PathBuf> = open_folder(path.as_path().unwrap()).unwrap();
-
-                            if paths.is_empty() {
-                                handle
-                                    .dialog()
-                                    .message("No audio files found")
-                                    .title("Open Folder")
-                                    .kind(MessageDialogKind::Error)
-                                    .blocking_show();
-                                return;
-                            }
-
-                            if let Err(err) = command::play_queue(&handle, paths).await {
+                            let path = path.into_path().expect("Not a folder path");
+                            if let Err(err) = command::add_library_root(handle, path).await {
                                 error!("{}", err);
-                            };
+                            }
                         });
                     }
                     None => debug!("Nothing selected"),
-                }),
-            "play" => {
-                tauri::async_runtime::spawn(async move {
-                    if let Err(err) = command::play(handle.state::<Player>()).await {
-                        error!("{}", err);
-                    }
-                });
-            }
-            "pause" => {
-                command::pause(app.state::<Player>());
-            }
-            "stop" => {
-                tauri::async_runtime::spawn(async move {
-                    if let Err(err) = command::stop(handle.state::<Player>()).await {
-                        error!("{}", err);
-                    }
-                });
-            }
-            "previous" => {
-                tauri::async_runtime::spawn(async move {
-                    if let Err(err) = command::previous_track(handle.state::<Player>()).await {
-                        error!("{}", err);
-                    }
-                });
-            }
-            "next" => {
-                tauri::async_runtime::spawn(async move {
-                    if let Err(err) = command::next_track(handle.state::<Player>()).await {
-                        error!("{}", err);
-                    }
-                });
+                })
+        }
+        Action::Play => {
+            tauri::async_runtime::spawn(async move {
+                if let Err(err) = command::play(handle.state::<Player>()).await {
+                    error!("{}", err);
+                }
+            });
+        }
+        Action::Pause => {
+            command::pause(app.state::<Player>());
+        }
+        Action::PlayPause => {
+            tauri::async_runtime::spawn(async move { command::toggle(&handle).await });
+        }
+        Action::Stop => {
+            tauri::async_runtime::spawn(async move {
+                if let Err(err) = command::stop(handle.state::<Player>()).await {
+                    error!("{}", err);
+                }
+            });
+        }
+        Action::Previous => {
+            tauri::async_runtime::spawn(async move {
+                if let Err(err) = command::previous_track(handle.state::<Player>()).await {
+                    error!("{}", err);
+                }
+            });
+        }
+        Action::Next => {
+            tauri::async_runtime::spawn(async move {
+                if let Err(err) = command::next_track(handle.state::<Player>()).await {
+                    error!("{}", err);
+                }
+            });
+        }
+        Action::SeekForward => seek_by(app, SEEK_STEP.as_secs_f64()),
+        Action::SeekBackward => seek_by(app, -SEEK_STEP.as_secs_f64()),
+        Action::Shuffle => {
+            tauri::async_runtime::spawn(async move {
+                let player = handle.state::<Player>();
+                player.set_shuffle(!player.is_shuffle(), None).await;
+            });
+        }
+        Action::Repeat => {
+            let player = app.state::<Player>();
+            if let Err(err) = player.set_repeat(player.repeat().cycle()) {
+                error!("{}", err);
             }
-            //TODO: Volume event handlers
-            _ => error!("Unknown event"),
+        }
+        Action::VolumeUp => {
+            let step = app.state::<Mutex<Settings>>().lock().unwrap().volume_step;
+            app.state::<Player>().step_volume(step);
+        }
+        Action::VolumeDown => {
+            let step = app.state::<Mutex<Settings>>().lock().unwrap().volume_step;
+            app.state::<Player>().step_volume(-step);
+        }
+        Action::Mute => {
+            let player = app.state::<Player>();
+            player.set_muted(!player.is_muted());
         }
     }
 }
 
-/// Opens a folder and returns the list of file paths that match the valid extensions
+/// Seeks from the current position, seeking past the end of the track skips to the next one
 ///
 /// # Arguments
 ///
-/// * 'path' - The path to the folder
+/// * 'offset' - Seconds to seek by, negative to seek backward
+fn seek_by<R: Runtime>(app: &AppHandle<R>, offset: f64) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let player = handle.state::<Player>();
+        if !player.is_playing() {
+            return;
+        }
+
+        let position = (player.playtime().as_secs_f64() + offset).max(0.0);
+        let length = player
+            .current_track()
+            .await
+            .and_then(|track| track.length());
+        if length.is_some_and(|length| position >= length) {
+            player.next().await;
+        } else if let Err(err) = player.seek(Duration::from_secs_f64(position)) {
+            error!("{}", err);
+        }
+    });
+}
+
+/// Opens a folder and returns the list of file paths that match the valid extensions,
+/// including the ones in its subfolders
 ///
-/// # Returns
+/// # Arguments
 ///
-/// * Vec<PathBuf> - The list of file paths
+/// * 'path' - The path to the folder
 ///
-/// # Examples
+/// # Returns
 ///
-/// ```
-/// use bragi::menu::open_folder;
-/// use std::path::Path;
-/// let path = Path::new("/path/to/folder");
-/// let files = open_folder(path).unwrap();
+/// * Vec<PathBuf> - The list of file paths in play order
 fn open_folder(path: impl AsRef<Path>) -> anyhow::Result<Vec<PathBuf>> {
-    let paths = path
-        .as_ref()
-        .read_dir()?
-        .filter_map(|file| file.ok().map(|file| file.path()))
-        .filter(|path| path.extension().is_some())
-        .filter(|path| {
-            EXTENSIONS.contains(
-                &path
-                    .extension()
-                    .unwrap_or_default()
-                    .to_str()
-                    .unwrap_or_default(),
-            )
-        })
-        .collect();
-
-    Ok(paths)
+    scan(path, &ScanOptions::default())
 }
 
 #[cfg(test)]
@@ -192,6 +441,15 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_action_ids() {
+        for action in Action::ALL {
+            assert_eq!(serde_json::to_value(action).unwrap(), action.id());
+            assert_eq!(Some(action), Action::from_id(action.id()));
+        }
+        assert_eq!(None, Action::from_id("unknown"));
+    }
+
     #[test]
     fn test_open_folder() {
         let dir = TempDir::new().unwrap();
@@ -0,0 +1,572 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use log::{error, info};
+use tauri::async_runtime;
+use zbus::{
+    connection, fdo, interface,
+    zvariant::{ObjectPath, OwnedObjectPath, Value},
+    Connection, SignalContext,
+};
+
+use crate::handle::PlayerHandle;
+use crate::player::{track::Track, Event, Player, Repeat};
+
+/// Well-known name of the player on the session bus
+pub const BUS_NAME: &str = "org.mpris.MediaPlayer2.bragi";
+/// Path every MPRIS interface is served at
+pub const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+/// Id of the current track when nothing is playing
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+/// Prefix of the track ids, followed by the id of the entry in the queue
+const TRACK_PREFIX: &str = "/org/bragi/track/";
+
+/// MPRIS2 server letting desktop media controls, `playerctl` and media keys control the player
+pub struct Mpris {
+    connection: Connection,
+}
+
+impl Mpris {
+    /// Serves the MPRIS interfaces
+    ///
+    /// # Arguments
+    ///
+    /// * 'address' - Address of the bus to serve on, the session bus if `None`
+    /// * 'handle' - Gives access to the player the interfaces control
+    pub async fn start(address: Option<&str>, handle: impl PlayerHandle) -> anyhow::Result<Self> {
+        let builder = match address {
+            Some(address) => connection::Builder::address(address)?,
+            None => connection::Builder::session()?,
+        };
+        let handle: Arc<dyn PlayerHandle> = Arc::new(handle);
+        let connection = builder
+            .name(BUS_NAME)?
+            .serve_at(OBJECT_PATH, MediaPlayer2)?
+            .serve_at(
+                OBJECT_PATH,
+                MediaPlayer2Player {
+                    handle: handle.clone(),
+                },
+            )?
+            .serve_at(OBJECT_PATH, MediaPlayer2TrackList { handle })?
+            .build()
+            .await?;
+        info!("MPRIS server started as {}", BUS_NAME);
+
+        Ok(Self { connection })
+    }
+
+    /// Signals the clients the properties the event changed
+    pub async fn notify(&self, event: &Event) -> anyhow::Result<()> {
+        let server = self.connection.object_server();
+        let player = server
+            .interface::<_, MediaPlayer2Player>(OBJECT_PATH)
+            .await?;
+        let context = player.signal_context();
+
+        match event {
+            Event::TrackChanged(_) | Event::PlaybackStopped => {
+                let player = player.get().await;
+                player.metadata_changed(context).await?;
+                player.playback_status_changed(context).await?;
+            }
+            Event::PlaybackPaused | Event::PlaybackResumed => {
+                player.get().await.playback_status_changed(context).await?;
+            }
+            Event::RepeatChanged(_) => {
+                player.get().await.loop_status_changed(context).await?;
+            }
+            Event::ShuffleChanged(_) => {
+                player.get().await.shuffle_changed(context).await?;
+            }
+            Event::VolumeChanged(_) => {
+                player.get().await.volume_changed(context).await?;
+            }
+            Event::PlaylistChanged => {
+                let track_list = server
+                    .interface::<_, MediaPlayer2TrackList>(OBJECT_PATH)
+                    .await?;
+                let (tracks, current) = {
+                    let interface = track_list.get().await;
+                    let player = interface.handle.player();
+                    let ids = player.track_ids().await;
+                    let current = match ids.get(player.current_index()) {
+                        Some(id) => track_id(*id),
+                        None => no_track(),
+                    };
+                    (ids.into_iter().map(track_id).collect(), current)
+                };
+                MediaPlayer2TrackList::track_list_replaced(
+                    track_list.signal_context(),
+                    tracks,
+                    current,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Starts playing the queue from the start if it is stopped
+fn start_queue(handle: &Arc<dyn PlayerHandle>) {
+    let handle = handle.clone();
+    async_runtime::spawn(async move {
+        if let Err(err) = handle.player().play_queue().await {
+            error!("{}", err);
+        }
+    });
+}
+
+fn track_id(id: u64) -> OwnedObjectPath {
+    ObjectPath::try_from(format!("{}{}", TRACK_PREFIX, id))
+        .expect("Invalid track id")
+        .into()
+}
+
+fn no_track() -> OwnedObjectPath {
+    ObjectPath::from_static_str_unchecked(NO_TRACK).into()
+}
+
+/// Returns the id of the queue entry the track id refers to, `None` for ids of other players
+fn entry_id(track_id: &ObjectPath) -> Option<u64> {
+    track_id.as_str().strip_prefix(TRACK_PREFIX)?.parse().ok()
+}
+
+/// Returns the index of the queued track with the id, `None` for removed or unknown tracks
+async fn track_index(player: &Player, track_id: &ObjectPath<'_>) -> Option<usize> {
+    player.track_index(entry_id(track_id)?).await
+}
+
+fn microseconds(duration: Duration) -> i64 {
+    duration.as_micros() as i64
+}
+
+/// Describes the track with the `xesam` and `mpris` metadata keys
+fn metadata(id: u64, track: &Track) -> HashMap<String, Value<'static>> {
+    let mut metadata = HashMap::new();
+    metadata.insert("mpris:trackid".to_string(), Value::from(track_id(id)));
+    metadata.insert(
+        "xesam:title".to_string(),
+        Value::from(track.title().to_string()),
+    );
+    metadata.insert(
+        "xesam:url".to_string(),
+        Value::from(format!("file://{}", track.path().display())),
+    );
+    if let Some(length) = track.length() {
+        metadata.insert(
+            "mpris:length".to_string(),
+            Value::from(microseconds(Duration::from_secs_f64(length))),
+        );
+    }
+    if let Some(artist) = track.artist() {
+        metadata.insert(
+            "xesam:artist".to_string(),
+            Value::from(vec![artist.to_string()]),
+        );
+    }
+    if let Some(album) = track.album() {
+        metadata.insert("xesam:album".to_string(), Value::from(album.to_string()));
+    }
+    if let Some(album_artist) = track.album_artist() {
+        metadata.insert(
+            "xesam:albumArtist".to_string(),
+            Value::from(vec![album_artist.to_string()]),
+        );
+    }
+    if let Some(genre) = track.genre() {
+        metadata.insert(
+            "xesam:genre".to_string(),
+            Value::from(vec![genre.to_string()]),
+        );
+    }
+    if let Some(number) = track.track_number() {
+        metadata.insert("xesam:trackNumber".to_string(), Value::from(number as i32));
+    }
+    if let Some(number) = track.disc_number() {
+        metadata.insert("xesam:discNumber".to_string(), Value::from(number as i32));
+    }
+
+    metadata
+}
+
+/// The `org.mpris.MediaPlayer2` interface, describing the application
+struct MediaPlayer2;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl MediaPlayer2 {
+    /// The window is managed by the desktop, it is not raised over D-Bus
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "Bragi".to_string()
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> String {
+        "bragi".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.Player` interface, controlling the playback
+struct MediaPlayer2Player {
+    handle: Arc<dyn PlayerHandle>,
+}
+
+impl MediaPlayer2Player {
+    fn player(&self) -> &Player {
+        self.handle.player()
+    }
+
+    /// Seeks in the current track, positions past its end skip to the next track
+    async fn seek_to(&self, position: i64, context: &SignalContext<'_>) -> fdo::Result<()> {
+        let position = Duration::from_micros(position.max(0) as u64);
+        let length = self
+            .player()
+            .current_track()
+            .await
+            .and_then(|track| track.length())
+            .map(Duration::from_secs_f64);
+        if length.is_some_and(|length| position > length) {
+            self.player().next().await;
+            return Ok(());
+        }
+
+        self.player()
+            .seek(position)
+            .map_err(|err| fdo::Error::Failed(err.to_string()))?;
+        Self::seeked(context, microseconds(position)).await?;
+
+        Ok(())
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl MediaPlayer2Player {
+    async fn next(&self) {
+        self.player().next().await;
+    }
+
+    async fn previous(&self) {
+        self.player().previous().await;
+    }
+
+    fn pause(&self) {
+        self.player().pause();
+    }
+
+    async fn play_pause(&self) {
+        let player = self.player();
+        if !player.is_playing() {
+            start_queue(&self.handle);
+        } else if player.is_paused() {
+            player.play().await;
+        } else {
+            player.pause();
+        }
+    }
+
+    async fn stop(&self) {
+        self.player().stop().await;
+    }
+
+    async fn play(&self) {
+        if self.player().is_playing() {
+            self.player().play().await;
+        } else {
+            start_queue(&self.handle);
+        }
+    }
+
+    /// Seeks forward or backward from the current position
+    ///
+    /// # Arguments
+    ///
+    /// * 'offset' - Microseconds to seek by, negative to seek backward
+    async fn seek(
+        &self,
+        offset: i64,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+    ) -> fdo::Result<()> {
+        if !self.player().is_playing() {
+            return Ok(());
+        }
+
+        let position = microseconds(self.player().playtime()) + offset;
+        self.seek_to(position, &context).await
+    }
+
+    /// Seeks to the position if the track is still the current one
+    ///
+    /// # Arguments
+    ///
+    /// * 'track_id' - Id of the track the position is in
+    /// * 'position' - Position in microseconds
+    async fn set_position(
+        &self,
+        track_id: ObjectPath<'_>,
+        position: i64,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+    ) -> fdo::Result<()> {
+        let current = self.player().is_playing()
+            && track_index(self.player(), &track_id).await == Some(self.player().current_index());
+        if !current || position < 0 {
+            return Ok(());
+        }
+
+        self.seek_to(position, &context).await
+    }
+
+    fn open_uri(&self, _uri: String) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(
+            "Opening URIs is not supported".to_string(),
+        ))
+    }
+
+    #[zbus(signal)]
+    async fn seeked(context: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        let status = if !self.player().is_playing() {
+            "Stopped"
+        } else if self.player().is_paused() {
+            "Paused"
+        } else {
+            "Playing"
+        };
+
+        status.to_string()
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> String {
+        let status = match self.player().repeat() {
+            Repeat::Off => "None",
+            Repeat::All => "Playlist",
+            Repeat::One => "Track",
+        };
+
+        status.to_string()
+    }
+
+    #[zbus(property)]
+    fn set_loop_status(&self, status: String) {
+        let repeat = match status.as_str() {
+            "Playlist" => Repeat::All,
+            "Track" => Repeat::One,
+            _ => Repeat::Off,
+        };
+        if let Err(err) = self.player().set_repeat(repeat) {
+            error!("{}", err);
+        }
+    }
+
+    /// Playback is always at the normal rate
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn set_rate(&self, _rate: f64) {}
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.player().is_shuffle()
+    }
+
+    #[zbus(property)]
+    async fn set_shuffle(&self, shuffle: bool) {
+        self.player().set_shuffle(shuffle, None).await;
+    }
+
+    #[zbus(property)]
+    async fn metadata(&self) -> HashMap<String, Value<'static>> {
+        let player = self.player();
+        let index = player.current_index();
+        match (
+            player.get_playlist().await.get(index),
+            player.track_ids().await.get(index),
+        ) {
+            (Some(track), Some(id)) if player.is_playing() => metadata(*id, track),
+            _ => HashMap::from([("mpris:trackid".to_string(), Value::from(no_track()))]),
+        }
+    }
+
+    /// MPRIS has no mute state, a muted player reports no volume
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        let volume = self.player().volume_state();
+        if volume.muted {
+            0.0
+        } else {
+            volume.level as f64
+        }
+    }
+
+    #[zbus(property)]
+    fn set_volume(&self, volume: f64) {
+        self.player().set_volume(volume as f32);
+    }
+
+    /// Position in the current track in microseconds, clients poll it rather than being signalled
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        microseconds(self.player().playtime())
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.TrackList` interface, listing the queue
+struct MediaPlayer2TrackList {
+    handle: Arc<dyn PlayerHandle>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.TrackList")]
+impl MediaPlayer2TrackList {
+    async fn get_tracks_metadata(
+        &self,
+        track_ids: Vec<OwnedObjectPath>,
+    ) -> Vec<HashMap<String, Value<'static>>> {
+        let player = self.handle.player();
+        let playlist = player.get_playlist().await;
+        let ids = player.track_ids().await;
+
+        track_ids
+            .iter()
+            .filter_map(|track_id| {
+                let id = entry_id(track_id)?;
+                let index = ids.iter().position(|i| *i == id)?;
+                playlist.get(index).map(|track| metadata(id, track))
+            })
+            .collect()
+    }
+
+    /// The queue is edited in the app, `CanEditTracks` is false
+    fn add_track(&self, _uri: String, _after_track: ObjectPath<'_>, _set_as_current: bool) {}
+
+    fn remove_track(&self, _track_id: ObjectPath<'_>) {}
+
+    async fn go_to(&self, track_id: ObjectPath<'_>) -> fdo::Result<()> {
+        let player = self.handle.player();
+        let index = track_index(player, &track_id)
+            .await
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Unknown track: {}", track_id)))?;
+
+        player
+            .change_track(index)
+            .await
+            .map_err(|err| fdo::Error::InvalidArgs(err.to_string()))?;
+        if !player.is_playing() {
+            start_queue(&self.handle);
+        }
+
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn track_list_replaced(
+        context: &SignalContext<'_>,
+        tracks: Vec<OwnedObjectPath>,
+        current_track: OwnedObjectPath,
+    ) -> zbus::Result<()>;
+
+    #[zbus(property(emits_changed_signal = "invalidates"))]
+    async fn tracks(&self) -> Vec<OwnedObjectPath> {
+        let ids = self.handle.player().track_ids().await;
+        ids.into_iter().map(track_id).collect()
+    }
+
+    #[zbus(property)]
+    fn can_edit_tracks(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::zvariant::ObjectPath;
+
+    use super::{entry_id, track_id};
+
+    #[test]
+    fn track_ids() {
+        assert_eq!("/org/bragi/track/12", track_id(12).as_str());
+        assert_eq!(Some(12), entry_id(&track_id(12)));
+        assert_eq!(
+            None,
+            entry_id(&ObjectPath::from_static_str_unchecked(
+                "/org/mpris/MediaPlayer2/TrackList/NoTrack"
+            ))
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use std::{io::ErrorKind, path::Path, time::Duration};
+
+use anyhow::anyhow;
+use log::warn;
+use rodio::{source::SeekError, Source};
+use symphonia::core::{
+    audio::{SampleBuffer, SignalSpec},
+    codecs::{self, DecoderOptions},
+    errors::Error,
+    formats::{FormatReader, SeekMode, SeekTo},
+    units::{Time, TimeBase},
+};
+
+use super::{probe, track::Span};
+
+/// How many corrupted packets in a row are skipped before the decoding stops
+const MAX_DECODE_ERRORS: usize = 3;
+
+/// Source decoding the default track of a file with symphonia.
+/// Unlike `rodio::Decoder` it opens the file itself, so readers that need the length
+/// of the file, like the MP4 one, work too.
+pub struct Decoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn codecs::Decoder>,
+    track_id: u32,
+    spec: SignalSpec,
+    /// Samples of the last decoded packet, `None` once the track ends
+    buffer: Option<SampleBuffer<f32>>,
+    /// Index of the next sample in the buffer
+    position: usize,
+    /// Index in the buffer the samples of the span end at
+    end: usize,
+    /// Start of the span, positions are relative to it
+    start: Duration,
+    /// Timestamp the span ends at, `None` if it plays until the end of the file
+    end_ts: Option<u64>,
+    /// Unit of the timestamps, they count frames if `None`
+    time_base: Option<TimeBase>,
+    total_duration: Option<Duration>,
+}
+
+impl Decoder {
+    /// Opens the file and decodes the first packet of the span, the decoder ends
+    /// with the span and seeks within it
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The path to the audio file
+    /// * 'span' - The part of the file to decode, `None` for the whole file
+    pub fn new(path: impl AsRef<Path>, span: Option<Span>) -> anyhow::Result<Self> {
+        let span = span.unwrap_or_default();
+        let format = probe::probe(path)?.format;
+        let track = format
+            .default_track()
+            .ok_or(anyhow!("No audio track found"))?;
+        let track_id = track.id;
+        let total_duration = track
+            .codec_params
+            .time_base
+            .zip(track.codec_params.n_frames)
+            .map(|(time_base, n_frames)| time_base.calc_time(n_frames))
+            .map(|time| Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+            .or(span.end)
+            .map(|total| {
+                span.end
+                    .unwrap_or(total)
+                    .min(total)
+                    .saturating_sub(span.start)
+            });
+        let time_base = track.codec_params.time_base.or(track
+            .codec_params
+            .sample_rate
+            .map(|rate| TimeBase::new(1, rate)));
+        let end_ts = span.end.and_then(|end| {
+            if time_base.is_none() {
+                warn!(
+                    "Unable to end the track at {:?}, the file has no time base",
+                    end
+                );
+            }
+            time_base.map(|time_base| time_base.calc_timestamp(to_time(end)))
+        });
+        let decoder = probe::codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+        let mut source = Self {
+            format,
+            decoder,
+            track_id,
+            spec: SignalSpec::new(0, Default::default()),
+            buffer: None,
+            position: 0,
+            end: 0,
+            start: Duration::ZERO,
+            end_ts,
+            time_base,
+            total_duration,
+        };
+        if span.start.is_zero() {
+            source.decode_next(0)?;
+        } else {
+            source.seek_to(span.start)?;
+            source.start = span.start;
+        }
+        if source.buffer.is_none() {
+            return Err(anyhow!("No audio packets found"));
+        }
+
+        Ok(source)
+    }
+
+    /// Decodes packets until one has samples at or after the given timestamp,
+    /// the buffer is emptied once the track ends
+    fn decode_next(&mut self, from_ts: u64) -> anyhow::Result<()> {
+        let mut errors = 0;
+
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(err)) if err.kind() == ErrorKind::UnexpectedEof => {
+                    self.buffer = None;
+                    return Ok(());
+                }
+                Err(Error::ResetRequired) => {
+                    self.buffer = None;
+                    return Ok(());
+                }
+                Err(err) => return Err(err.into()),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            if self.end_ts.is_some_and(|end_ts| packet.ts() >= end_ts) {
+                self.buffer = None;
+                return Ok(());
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(err)) if errors < MAX_DECODE_ERRORS => {
+                    warn!("Skipping corrupted packet: {}", err);
+                    errors += 1;
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            //skips the frames before the seek target
+            let rate = decoded.spec().rate;
+            let skip = to_frames(from_ts.saturating_sub(packet.ts()), self.time_base, rate);
+            if decoded.frames() <= skip {
+                continue;
+            }
+            //the packet may reach past the end of the span
+            let frames = self.end_ts.map_or(decoded.frames(), |end_ts| {
+                decoded
+                    .frames()
+                    .min(to_frames(end_ts - packet.ts(), self.time_base, rate))
+            });
+            if frames <= skip {
+                self.buffer = None;
+                return Ok(());
+            }
+
+            self.spec = *decoded.spec();
+            let channels = self.spec.channels.count();
+            let buffer = match &mut self.buffer {
+                Some(buffer) if buffer.capacity() >= decoded.capacity() * channels => buffer,
+                _ => self
+                    .buffer
+                    .insert(SampleBuffer::new(decoded.capacity() as u64, self.spec)),
+            };
+            buffer.copy_interleaved_ref(decoded);
+            self.position = skip * channels;
+            self.end = frames * channels;
+
+            return Ok(());
+        }
+    }
+
+    /// Returns the samples of the last decoded packet that were not played yet
+    fn remaining(&self) -> &[f32] {
+        self.buffer
+            .as_ref()
+            .map_or(&[], |buffer| &buffer.samples()[self.position..self.end])
+    }
+
+    /// Seeks to the position in the file, regardless of the span
+    fn seek_to(&mut self, pos: Duration) -> anyhow::Result<()> {
+        let seek = SeekTo::Time {
+            time: to_time(pos),
+            track_id: Some(self.track_id),
+        };
+        let seeked = self.format.seek(SeekMode::Accurate, seek)?;
+        self.decoder.reset();
+
+        self.decode_next(seeked.required_ts)
+    }
+}
+
+/// Converts a duration in timestamp units to a number of frames, containers like MP4
+/// and Matroska count the timestamps in their own units rather than in frames
+///
+/// # Arguments
+///
+/// * 'ts' - The duration in units of the time base
+/// * 'time_base' - The time base of the track, `None` if the timestamps count frames
+/// * 'rate' - The sample rate of the decoded frames
+fn to_frames(ts: u64, time_base: Option<TimeBase>, rate: u32) -> usize {
+    match time_base {
+        Some(time_base) => {
+            (ts as u128 * time_base.numer as u128 * rate as u128 / time_base.denom as u128) as usize
+        }
+        None => ts as usize,
+    }
+}
+
+fn to_time(duration: Duration) -> Time {
+    Time::new(duration.as_secs(), duration.subsec_nanos() as f64 / 1e9)
+}
+
+impl Iterator for Decoder {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        let sample = *self.remaining().first()?;
+        self.position += 1;
+
+        //the next packet is decoded right away, so the frame length is never zero mid-track
+        if self.remaining().is_empty() {
+            if let Err(err) = self.decode_next(0) {
+                warn!("Unable to decode: {}", err);
+                self.buffer = None;
+            }
+        }
+
+        Some(sample)
+    }
+}
+
+impl Source for Decoder {
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.remaining().len())
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.spec.channels.count() as u16
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.spec.rate
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.seek_to(self.start + pos)
+            .map_err(|err| SeekError::Other(err.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use rodio::Source;
+    use temp_dir::TempDir;
+
+    use super::Decoder;
+    use crate::player::track::Span;
+
+    #[test]
+    fn decode_every_format() {
+        for path in [
+            "tests/assets/track.mp3",
+            "tests/assets/track.flac",
+            "tests/assets/track.wav",
+            "tests/assets/track.ogg",
+            "tests/assets/track.opus",
+            "tests/assets/track.aac",
+            "tests/assets/track.m4a",
+            "tests/assets/track_alac.m4a",
+        ] {
+            let decoder = Decoder::new(path, None).unwrap();
+            let per_second = decoder.sample_rate() as usize * decoder.channels() as usize;
+
+            let samples = decoder.count();
+
+            assert!(samples > per_second, "{} has no samples", path);
+        }
+    }
+
+    #[test]
+    fn seek() {
+        let mut decoder = Decoder::new("tests/assets/track.wav", None).unwrap();
+        let per_second = decoder.sample_rate() as usize * decoder.channels() as usize;
+
+        decoder.try_seek(Duration::from_secs(1)).unwrap();
+
+        assert_eq!(per_second, decoder.count());
+    }
+
+    #[test]
+    fn seek_m4a() {
+        for path in ["tests/assets/track.m4a", "tests/assets/track_timescale.m4a"] {
+            let total = Decoder::new(path, None).unwrap().count();
+            let mut decoder = Decoder::new(path, None).unwrap();
+            let per_second = decoder.sample_rate() as usize * decoder.channels() as usize;
+
+            decoder.try_seek(Duration::from_secs(1)).unwrap();
+
+            //the packets may hold more frames than the track lists
+            assert_eq!(total - per_second, decoder.count(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn seek_opus() {
+        let path = "tests/assets/track.opus";
+        let total = Decoder::new(path, None).unwrap().count();
+        let mut decoder = Decoder::new(path, None).unwrap();
+        let per_second = decoder.sample_rate() as usize * decoder.channels() as usize;
+
+        decoder.try_seek(Duration::from_secs(1)).unwrap();
+
+        //the seek lands within the 20 ms packet holding the position
+        let packet = per_second / 50;
+        assert!((total - per_second).abs_diff(decoder.count()) <= packet);
+    }
+
+    #[test]
+    fn seek_past_end() {
+        let mut decoder = Decoder::new("tests/assets/track.wav", None).unwrap();
+
+        assert!(decoder.try_seek(Duration::from_secs(10)).is_err());
+    }
+
+    #[test]
+    fn decode_span() {
+        let span = Span {
+            start: Duration::from_millis(500),
+            end: Some(Duration::from_millis(1250)),
+        };
+        let decoder = Decoder::new("tests/assets/track.wav", Some(span)).unwrap();
+        let per_second = decoder.sample_rate() as usize * decoder.channels() as usize;
+
+        assert_eq!(Some(Duration::from_millis(750)), decoder.total_duration());
+        assert_eq!(per_second * 3 / 4, decoder.count());
+    }
+
+    #[test]
+    fn decode_span_until_end() {
+        let span = Span {
+            start: Duration::from_secs(1),
+            end: None,
+        };
+        let decoder = Decoder::new("tests/assets/track.wav", Some(span)).unwrap();
+        let per_second = decoder.sample_rate() as usize * decoder.channels() as usize;
+
+        assert_eq!(per_second, decoder.count());
+    }
+
+    #[test]
+    fn seek_within_span() {
+        let span = Span {
+            start: Duration::from_millis(500),
+            end: Some(Duration::from_millis(1500)),
+        };
+        let mut decoder = Decoder::new("tests/assets/track.wav", Some(span)).unwrap();
+        let per_second = decoder.sample_rate() as usize * decoder.channels() as usize;
+
+        decoder.try_seek(Duration::from_millis(500)).unwrap();
+
+        assert_eq!(per_second / 2, decoder.count());
+    }
+
+    #[test]
+    fn decode_invalid() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("invalid.mp3");
+        fs::write(&path, "invalid").unwrap();
+
+        assert!(Decoder::new(&path, None).is_err());
+    }
+}
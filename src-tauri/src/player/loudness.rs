@@ -0,0 +1,241 @@
+use std::{collections::VecDeque, f64::consts::PI};
+
+/// Loudness of a full scale signal, see ITU-R BS.1770
+const LOUDNESS_OFFSET: f64 = -0.691;
+/// Blocks quieter than this are ignored
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks quieter than the ungated loudness by this amount are ignored
+const RELATIVE_GATE: f64 = -10.0;
+/// Gating blocks are 400 ms long and overlap by 75%, so every block spans 4 sub-blocks
+const SUB_BLOCKS_PER_BLOCK: usize = 4;
+const SUB_BLOCK_MILLIS: u64 = 100;
+
+/// Second order IIR filter in direct form I
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    /// High shelf boosting frequencies above ~1.5 kHz, the head diffraction model of K-weighting
+    fn pre_filter(sample_rate: f64) -> Self {
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Self {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            ..Default::default()
+        }
+    }
+
+    /// High pass cutting frequencies below ~38 Hz, the RLB weighting curve of K-weighting
+    fn rlb_filter(sample_rate: f64) -> Self {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Self {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            ..Default::default()
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// Measures the integrated loudness of a signal as defined by EBU R128
+pub struct LoudnessMeter {
+    channels: usize,
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    sub_block_frames: usize,
+    frames: usize,
+    sum: f64,
+    sub_blocks: VecDeque<f64>,
+    blocks: Vec<f64>,
+    peak: f32,
+}
+
+impl LoudnessMeter {
+    /// Creates a meter for an interleaved signal
+    ///
+    /// # Arguments
+    ///
+    /// * 'channels' - Number of channels of the signal
+    /// * 'sample_rate' - Sample rate of the signal
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        let rate = sample_rate as f64;
+        let weights = (0..channels)
+            .map(|channel| match (channels, channel) {
+                //LFE is not taken into account and surround channels are boosted
+                (6, 3) => 0.0,
+                (6, 4..=5) => 1.41,
+                _ => 1.0,
+            })
+            .collect();
+
+        Self {
+            channels,
+            filters: vec![[Biquad::pre_filter(rate), Biquad::rlb_filter(rate)]; channels],
+            weights,
+            sub_block_frames: (sample_rate as u64 * SUB_BLOCK_MILLIS / 1000) as usize,
+            frames: 0,
+            sum: 0.0,
+            sub_blocks: VecDeque::with_capacity(SUB_BLOCKS_PER_BLOCK),
+            blocks: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    /// Feeds interleaved samples to the meter
+    pub fn process(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                self.peak = self.peak.max(sample.abs());
+
+                let [pre, rlb] = &mut self.filters[channel];
+                let filtered = rlb.process(pre.process(*sample as f64));
+                self.sum += self.weights[channel] * filtered * filtered;
+            }
+
+            self.frames += 1;
+            if self.frames == self.sub_block_frames {
+                self.push_sub_block();
+            }
+        }
+    }
+
+    fn push_sub_block(&mut self) {
+        if self.sub_blocks.len() == SUB_BLOCKS_PER_BLOCK {
+            self.sub_blocks.pop_front();
+        }
+        self.sub_blocks
+            .push_back(self.sum / self.sub_block_frames as f64);
+        self.sum = 0.0;
+        self.frames = 0;
+
+        if self.sub_blocks.len() == SUB_BLOCKS_PER_BLOCK {
+            let power = self.sub_blocks.iter().sum::<f64>() / SUB_BLOCKS_PER_BLOCK as f64;
+            self.blocks.push(power);
+        }
+    }
+
+    /// Returns the gated integrated loudness in LUFS,
+    /// or `None` if the signal is too short or silent
+    pub fn integrated(&self) -> Option<f64> {
+        let absolute = gated_mean(&self.blocks, |power| loudness(power) > ABSOLUTE_GATE)?;
+        let relative_gate = loudness(absolute) + RELATIVE_GATE;
+
+        gated_mean(&self.blocks, |power| {
+            let block = loudness(power);
+            block > ABSOLUTE_GATE && block > relative_gate
+        })
+        .map(loudness)
+    }
+
+    /// Returns the highest absolute sample value
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+}
+
+fn loudness(power: f64) -> f64 {
+    LOUDNESS_OFFSET + 10.0 * power.log10()
+}
+
+fn gated_mean(blocks: &[f64], gate: impl Fn(f64) -> bool) -> Option<f64> {
+    let (sum, count) = blocks
+        .iter()
+        .filter(|power| gate(**power))
+        .fold((0.0, 0), |(sum, count), power| (sum + power, count + 1));
+
+    (count > 0).then(|| sum / count as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::LoudnessMeter;
+
+    fn sine(channels: usize, sample_rate: u32, seconds: u32, amplitude: f32) -> Vec<f32> {
+        (0..sample_rate * seconds)
+            .flat_map(|i| {
+                let sample = amplitude * (2.0 * PI * 1000.0 * i as f32 / sample_rate as f32).sin();
+                std::iter::repeat_n(sample, channels)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn integrated_stereo_sine() {
+        let mut meter = LoudnessMeter::new(2, 48000);
+
+        meter.process(&sine(2, 48000, 10, 10f32.powf(-23.0 / 20.0)));
+
+        let actual = meter.integrated().unwrap();
+        assert!((actual + 23.0).abs() < 0.1, "{}", actual);
+    }
+
+    #[test]
+    fn integrated_mono_sine_44100() {
+        let mut meter = LoudnessMeter::new(1, 44100);
+
+        meter.process(&sine(1, 44100, 10, 1.0));
+
+        let actual = meter.integrated().unwrap();
+        assert!((actual + 3.01).abs() < 0.1, "{}", actual);
+    }
+
+    #[test]
+    fn integrated_silence() {
+        let mut meter = LoudnessMeter::new(2, 48000);
+
+        meter.process(&vec![0.0; 48000 * 2 * 2]);
+
+        assert_eq!(None, meter.integrated());
+    }
+
+    #[test]
+    fn integrated_too_short() {
+        let mut meter = LoudnessMeter::new(2, 48000);
+
+        meter.process(&sine(2, 48000, 0, 1.0));
+
+        assert_eq!(None, meter.integrated());
+    }
+
+    #[test]
+    fn peak() {
+        let mut meter = LoudnessMeter::new(1, 48000);
+
+        meter.process(&[0.1, -0.5, 0.25]);
+
+        assert_eq!(0.5, meter.peak());
+    }
+}
@@ -1,80 +1,221 @@
-use log::{debug, info};
+use log::{debug, info, warn};
 use std::{
     collections::VecDeque,
-    io::BufReader,
-    path::Path,
+    mem,
+    path::{Path, PathBuf},
     sync::{
-        atomic::AtomicBool,
-        mpsc::{self, Receiver, Sender},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Mutex,
     },
     time::Duration,
 };
 
-use anyhow::anyhow;
-use rodio::{OutputStream, Sink};
-use tauri::async_runtime::RwLock;
+use anyhow::{anyhow, bail};
+use rodio::{dynamic_mixer, Sink, Source};
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime;
 
 use self::{
-    playtime::Playtime,
+    decoder::Decoder,
+    output::{Output, RodioOutput},
     queue::Queue,
-    track::{AlbumCover, Track},
+    replaygain::{ReplayGain, ReplayGainSettings},
+    source::{CancelHandle, Counted, FadeHandle, Fader, PositionHandle, Queued, Signalled},
+    track::{AlbumCover, Span, Track},
+    volume::{Volume, VolumeCurve},
 };
 
-mod playtime;
+mod decoder;
+mod loudness;
+mod opus;
+pub mod output;
+mod probe;
 mod queue;
+pub mod replaygain;
+mod source;
 pub mod track;
+pub mod volume;
+
+/// Channel count the sinks are mixed at before reaching the output
+const MIX_CHANNELS: u16 = 2;
+/// Sample rate the sinks are mixed at before reaching the output
+const MIX_SAMPLE_RATE: u32 = 48_000;
+/// Maximum duration of the crossfade between two tracks
+pub const MAX_CROSSFADE: Duration = Duration::from_secs(12);
+/// How often the queue checks whether the crossfade should start
+const CROSSFADE_POLL: Duration = Duration::from_millis(100);
 
 pub struct Player {
-    _stream: StreamWrapper,
-    sink: Sink,
+    _output: Box<dyn Output>,
+    /// Latency of the output, the positions of the tracks subtract it
+    latency: Duration,
+    /// Two sinks, so the upcoming track can fade in while the current one fades out
+    sinks: [Sink; 2],
+    active: AtomicUsize,
+    /// Whether the track on the other sink is fading out
+    fading: AtomicBool,
     queue: Queue,
-    playtime: RwLock<Playtime>,
+    /// Position of the current track, counted from the samples played
+    position: Mutex<PositionHandle>,
     event_handler: Sender<Event>,
+    /// Track waiting in the sink behind the current one, until the current one ends
+    preloaded: Mutex<Option<Appended>>,
+    started: Mutex<Option<Sender<PathBuf>>>,
     is_playing: AtomicBool,
+    skipped: AtomicBool,
+    crossfade: AtomicU64,
+    replay_gain: Mutex<ReplayGainSettings>,
+    repeat: Mutex<Repeat>,
+    resume_at: Mutex<Option<Duration>>,
+    volume: Mutex<Volume>,
 }
 
 impl Player {
+    /// Creates a player that plays through the default audio device
     pub fn new() -> anyhow::Result<(Self, Receiver<Event>)> {
-        let (stream, handle) = OutputStream::try_default()?;
+        Self::with_output(RodioOutput::try_default()?)
+    }
+
+    /// Creates a player that plays through the given output backend
+    ///
+    /// # Arguments
+    ///
+    /// * 'output' - The backend consuming the samples, e.g. `NullOutput` for headless playback
+    pub fn with_output(
+        mut output: impl Output + 'static,
+    ) -> anyhow::Result<(Self, Receiver<Event>)> {
+        let (mixer_controller, mixer) = dynamic_mixer::mixer(MIX_CHANNELS, MIX_SAMPLE_RATE);
+        let sinks = [(); 2].map(|_| {
+            let (sink, source) = Sink::new_idle();
+            mixer_controller.add(source);
+            sink
+        });
+        output.attach(Box::new(mixer))?;
 
-        let sink = Sink::try_new(&handle)?;
         let event_handler = mpsc::channel();
         let player = Self {
-            _stream: StreamWrapper(stream),
-            sink,
+            latency: output.latency(),
+            _output: Box::new(output),
+            sinks,
+            active: AtomicUsize::new(0),
+            fading: AtomicBool::new(false),
             queue: Queue::new(),
-            playtime: RwLock::new(Playtime::default()),
+            position: Mutex::new(PositionHandle::default()),
             event_handler: event_handler.0,
+            preloaded: Mutex::new(None),
+            started: Mutex::new(None),
             is_playing: AtomicBool::new(false),
+            skipped: AtomicBool::new(false),
+            crossfade: AtomicU64::new(0),
+            replay_gain: Mutex::new(ReplayGainSettings::default()),
+            repeat: Mutex::new(Repeat::default()),
+            resume_at: Mutex::new(None),
+            volume: Mutex::new(Volume::default()),
         };
 
         Ok((player, event_handler.1))
     }
 
     pub async fn open(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
-        self.queue.add(Track::try_new(path.as_ref())?).await;
+        self.add(Track::try_new(path.as_ref())?).await;
         Ok(())
     }
 
+    /// Appends an already read track to the queue
+    pub async fn add(&self, track: Track) {
+        self.queue.add(track).await;
+    }
+
+    /// Returns a receiver of the files the sinks start playing, sent once their first sample
+    /// is played. It replaces the previous receiver and misses the tracks appended before.
+    pub fn watch_started(&self) -> Receiver<PathBuf> {
+        let (tx, rx) = mpsc::channel();
+        *self.started.lock().unwrap() = Some(tx);
+        rx
+    }
+
     pub async fn play_queue(&self) -> anyhow::Result<()> {
         info!("Starting a queue");
-        self.is_playing
-            .store(true, std::sync::atomic::Ordering::Relaxed);
-        while let Some(track) = self.queue.next().await {
+        self.is_playing.store(true, Ordering::Relaxed);
+        let mut preloaded: Option<Appended> = None;
+        let mut failed = 0;
+        let mut replayed = false;
+
+        while let Some(track) = self.next_track().await {
             if !self.is_playing() {
                 break;
             }
 
-            let file = std::fs::File::open(track.path())?;
-            self.sink.append(rodio::Decoder::new(BufReader::new(file))?);
-            self.event_handler
-                .send(Event::TrackChanged(self.queue.current()))?;
-            self.play().await;
+            let index = self.queue.current();
+            let skipped = self.skipped.swap(false, Ordering::Relaxed);
+            //skipped after the track ended, the skip applies to its replay
+            if mem::take(&mut replayed) && skipped {
+                preloaded = None;
+                continue;
+            }
+            let current = match preloaded.take() {
+                Some(appended) if appended.plays(&track) && !skipped => appended,
+                _ => {
+                    //flushes the preloaded track if the queue was moved or edited
+                    self.stop_sinks();
+                    match self.append(&track, Duration::ZERO) {
+                        Ok(appended) => appended,
+                        Err(err) => {
+                            warn!("Unable to play {}: {}", &track.path().display(), err);
+                            //the watcher may not have reported the file missing yet
+                            if track.is_available() && !track.path().exists() {
+                                self.set_available(track.path(), false).await?;
+                            }
+                            failed += 1;
+                            //every track failed, repeating would never end
+                            if failed >= self.queue.get_playlist().await.len() {
+                                break;
+                            }
+                            continue;
+                        }
+                    }
+                }
+            };
+            failed = 0;
+            //skipped before the track was appended, stopping the sinks missed it
+            if self.skipped.swap(false, Ordering::Relaxed) {
+                self.stop_sinks();
+                continue;
+            }
+
+            *self.position.lock().unwrap() = current.position.clone();
+            //resumes before announcing the track, so a pause right after the change sticks
+            let resume_at = self.resume_at.lock().unwrap().take();
+            if resume_at.is_none() {
+                self.play().await;
+            }
+            self.event_handler.send(Event::TrackChanged(index))?;
+            if let Some(position) = resume_at {
+                self.pause_at(position).await?;
+            }
 
             info!("Playing {}", &track.path().to_string_lossy());
 
-            self.sink.sleep_until_end();
-            self.next().await;
+            let crossfade = self.crossfade();
+            preloaded = match track.length() {
+                Some(length) if !crossfade.is_zero() => {
+                    self.crossfade_into_next(current, Duration::from_secs_f64(length), crossfade)
+                        .await
+                }
+                _ => {
+                    *self.preloaded.lock().unwrap() = self.preload().await;
+                    let _ = current.finished.recv();
+                    self.preloaded.lock().unwrap().take()
+                }
+            };
+            self.reset_position();
+
+            //skipping still moves on to the next track
+            replayed = self.repeat() == Repeat::One && !self.skipped.load(Ordering::Relaxed);
+            if replayed {
+                self.queue.replay_current();
+            }
         }
 
         self.stop().await;
@@ -83,57 +224,425 @@ impl Player {
         Ok(())
     }
 
+    /// Starts the queue from the given track, paused at the given position
+    ///
+    /// # Arguments
+    ///
+    /// * 'index' - Index of the track in the queue
+    /// * 'position' - Position in the track to resume from
+    pub async fn restore(&self, index: usize, position: Duration) -> anyhow::Result<()> {
+        self.queue.change_current(index).await?;
+        self.sinks.iter().for_each(Sink::pause);
+        *self.resume_at.lock().unwrap() = Some(position);
+
+        self.play_queue().await
+    }
+
+    async fn pause_at(&self, position: Duration) -> anyhow::Result<()> {
+        if let Err(err) = self.sink().try_seek(position) {
+            warn!("Unable to seek to {:?}: {}", position, err);
+        }
+
+        self.event_handler.send(Event::PlaybackPaused)?;
+        info!("Paused at {:?}", position);
+
+        Ok(())
+    }
+
+    /// Advances the queue, wrapping around to the start if repeat-all is enabled
+    async fn next_track(&self) -> Option<Track> {
+        if let Some(track) = self.queue.next().await {
+            return Some(track);
+        }
+
+        if self.repeat() == Repeat::All {
+            debug!("Repeating the queue");
+            self.queue.reset();
+            return self.queue.next().await;
+        }
+
+        None
+    }
+
+    /// Returns the track played after the current one
+    async fn upcoming(&self) -> Option<Track> {
+        match self.repeat() {
+            Repeat::One => self.queue.current_track().await,
+            Repeat::All => match self.queue.peek().await {
+                Some((_, track)) => Some(track),
+                None => self.queue.first().await.map(|(_, track)| track),
+            },
+            Repeat::Off => self.queue.peek().await.map(|(_, track)| track),
+        }
+    }
+
+    /// Returns the sink the current track is played on
+    fn sink(&self) -> &Sink {
+        &self.sinks[self.active.load(Ordering::Relaxed)]
+    }
+
+    fn stop_sinks(&self) {
+        self.sinks.iter().for_each(Sink::stop);
+    }
+
+    /// Appends the track to the active sink
+    ///
+    /// # Arguments
+    ///
+    /// * 'track' - The track to decode
+    /// * 'fade_in' - Duration of the fade in at the start of the track
+    fn append(&self, track: &Track, fade_in: Duration) -> anyhow::Result<Appended> {
+        if !track.is_available() {
+            bail!("File is missing");
+        }
+
+        let gain = track
+            .replay_gain()
+            .factor(&self.replay_gain.lock().unwrap());
+        let decoder = Decoder::new(track.path(), track.span())?.amplify(gain);
+        let (source, position) = Counted::new(decoder, self.latency);
+        let (source, fade) = Fader::new(source, fade_in);
+        let (source, finished) = Signalled::new(source);
+        let (started, path) = (self.started.lock().unwrap().clone(), track.path().clone());
+        let (source, cancel) = Queued::new(source, move || {
+            if let Some(started) = started {
+                let _ = started.send(path);
+            }
+        });
+        self.sink().append(source);
+
+        Ok(Appended {
+            path: track.path().clone(),
+            span: track.span(),
+            finished,
+            fade,
+            position,
+            cancel,
+        })
+    }
+
+    /// Appends the upcoming track right after the current one, so the sink
+    /// switches to it without a gap
+    async fn preload(&self) -> Option<Appended> {
+        let track = self.upcoming().await?;
+
+        self.append(&track, Duration::ZERO)
+            .inspect(|_| debug!("Preloaded {}", &track.path().to_string_lossy()))
+            .inspect_err(|err| warn!("Unable to preload {}: {}", &track.path().display(), err))
+            .ok()
+    }
+
+    /// Drops the preloaded track from the sink if an edit of the queue changed the upcoming
+    /// track, the queue appends the new one once the current track ends
+    async fn flush_preload(&self) {
+        let upcoming = self.upcoming().await;
+        let mut preloaded = self.preloaded.lock().unwrap();
+
+        let stale = match (preloaded.as_ref(), &upcoming) {
+            (Some(appended), Some(track)) => !appended.plays(track),
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if stale {
+            if let Some(appended) = preloaded.take() {
+                appended.cancel.cancel();
+                debug!("Flushed the preloaded {}", appended.path.display());
+            }
+        }
+    }
+
+    /// Waits until the current track reaches the crossfade point, then starts the
+    /// upcoming track on the other sink while the current one fades out. Returns once
+    /// the upcoming track took over, or `None` if the current track ended or was
+    /// skipped before the crossfade point.
+    async fn crossfade_into_next(
+        &self,
+        current: Appended,
+        length: Duration,
+        crossfade: Duration,
+    ) -> Option<Appended> {
+        loop {
+            let remaining = length.saturating_sub(self.playtime());
+            if remaining <= crossfade {
+                break;
+            }
+
+            //wakes up periodically, as seeking and pausing move the crossfade point
+            match current
+                .finished
+                .recv_timeout((remaining - crossfade).min(CROSSFADE_POLL))
+            {
+                Err(RecvTimeoutError::Timeout) => continue,
+                _ => return None,
+            }
+        }
+
+        let Some(track) = self.upcoming().await else {
+            let _ = current.finished.recv();
+            return None;
+        };
+
+        let fading = self.active.fetch_xor(1, Ordering::Relaxed);
+        match self.append(&track, crossfade) {
+            Ok(next) => {
+                self.fading.store(true, Ordering::Relaxed);
+                current.fade.fade_out(crossfade);
+                info!("Crossfading into {}", &track.path().to_string_lossy());
+                let _ = current.finished.recv();
+                self.fading.store(false, Ordering::Relaxed);
+                Some(next)
+            }
+            Err(err) => {
+                warn!(
+                    "Unable to crossfade into {}: {}",
+                    &track.path().display(),
+                    err
+                );
+                self.active.store(fading, Ordering::Relaxed);
+                let _ = current.finished.recv();
+                None
+            }
+        }
+    }
+
     pub async fn play(&self) {
-        self.playtime.write().await.play();
-        self.sink.play();
+        let paused = self.is_paused();
+        self.sinks.iter().for_each(Sink::play);
         info!("Sink resumed");
+
+        if paused {
+            if let Err(err) = self.event_handler.send(Event::PlaybackResumed) {
+                warn!("{}", err);
+            }
+        }
     }
 
     pub async fn next(&self) {
-        *self.playtime.write().await = Playtime::default();
-        self.sink.stop();
+        self.reset_position();
+        self.skipped.store(true, Ordering::Relaxed);
+        self.stop_sinks();
         info!("Switching to next track");
     }
 
     pub async fn previous(&self) {
-        *self.playtime.write().await = Playtime::default();
+        self.reset_position();
         self.queue.change_to_previous().await;
-        self.sink.stop();
+        self.skipped.store(true, Ordering::Relaxed);
+        self.stop_sinks();
         info!("Switching to previous track");
     }
 
     pub async fn stop(&self) {
-        *self.playtime.write().await = Playtime::default();
-        self.sink.stop();
-        self.is_playing
-            .store(false, std::sync::atomic::Ordering::Relaxed);
+        self.reset_position();
+        self.skipped.store(true, Ordering::Relaxed);
+        self.stop_sinks();
+        self.is_playing.store(false, Ordering::Relaxed);
         self.queue.reset();
         self.event_handler.send(Event::PlaybackStopped).unwrap();
         info!("Sink stopped");
     }
 
     pub fn pause(&self) {
-        self.playtime.blocking_write().pause();
-        self.sink.pause();
+        self.sinks.iter().for_each(Sink::pause);
         info!("Sink paused");
+
+        if let Err(err) = self.event_handler.send(Event::PlaybackPaused) {
+            warn!("{}", err);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.sink().is_paused()
     }
 
+    /// Returns the position in the current track, as far as its samples reached the output
     pub fn playtime(&self) -> Duration {
-        self.playtime.blocking_read().time()
+        self.position.lock().unwrap().get()
+    }
+
+    fn reset_position(&self) {
+        *self.position.lock().unwrap() = PositionHandle::default();
+    }
+
+    /// Returns the index of the current track in the queue
+    pub fn current_index(&self) -> usize {
+        self.queue.current()
     }
 
     pub fn is_playing(&self) -> bool {
-        self.is_playing.load(std::sync::atomic::Ordering::Relaxed)
+        self.is_playing.load(Ordering::Relaxed)
     }
 
     pub async fn get_playlist(&self) -> VecDeque<Track> {
         self.queue.get_playlist().await
     }
 
+    /// Returns the ids of the queued tracks, an id stays the same while the queue is edited
+    pub async fn track_ids(&self) -> Vec<u64> {
+        self.queue.ids().await
+    }
+
+    /// Returns the index of the queued track with the id, `None` once it was removed
+    pub async fn track_index(&self, id: u64) -> Option<usize> {
+        self.queue.index_of(id).await
+    }
+
+    /// Returns the level of the volume control, it is kept while muted
+    pub fn volume(&self) -> f32 {
+        self.volume.lock().unwrap().level
+    }
+
+    /// Returns the level, the mute state and the curve of the volume
+    pub fn volume_state(&self) -> Volume {
+        *self.volume.lock().unwrap()
+    }
+
+    /// Sets the level of the volume control and unmutes
+    ///
+    /// # Arguments
+    ///
+    /// * 'volume' - The level, clamped between 0 and 1
     pub fn set_volume(&self, volume: impl Into<f32>) {
-        let volume_f32: f32 = volume.into();
-        self.sink.set_volume(volume_f32);
-        debug!("Volume changed to: {}", volume_f32)
+        let level = volume.into().clamp(0.0, 1.0);
+        self.change_volume(|volume| {
+            volume.level = level;
+            volume.muted = false;
+        });
+    }
+
+    /// Raises or lowers the level of the volume control and unmutes
+    ///
+    /// # Arguments
+    ///
+    /// * 'step' - Change of the level, negative to lower it
+    pub fn step_volume(&self, step: f32) {
+        self.change_volume(|volume| {
+            volume.level = (volume.level + step).clamp(0.0, 1.0);
+            volume.muted = false;
+        });
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.change_volume(|volume| volume.muted = muted);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.volume.lock().unwrap().muted
+    }
+
+    pub fn set_volume_curve(&self, curve: VolumeCurve) {
+        self.change_volume(|volume| volume.curve = curve);
+    }
+
+    /// Applies the change to the sinks and sends `Event::VolumeChanged` if anything changed
+    fn change_volume(&self, change: impl FnOnce(&mut Volume)) {
+        let changed = {
+            let mut volume = self.volume.lock().unwrap();
+            let previous = *volume;
+            change(&mut volume);
+            self.sinks
+                .iter()
+                .for_each(|sink| sink.set_volume(volume.gain()));
+            (*volume != previous).then_some(*volume)
+        };
+
+        if let Some(volume) = changed {
+            debug!("Volume changed to: {:?}", volume);
+            if let Err(err) = self.event_handler.send(Event::VolumeChanged(volume)) {
+                warn!("{}", err);
+            }
+        }
+    }
+
+    /// Sets the duration of the crossfade between consecutive tracks,
+    /// `Duration::ZERO` switches to gapless playback
+    pub fn set_crossfade(&self, duration: impl Into<Duration>) -> anyhow::Result<()> {
+        let duration = duration.into();
+        if duration > MAX_CROSSFADE {
+            bail!(
+                "Crossfade must not exceed {} seconds",
+                MAX_CROSSFADE.as_secs()
+            );
+        }
+
+        self.crossfade
+            .store(duration.as_millis() as u64, Ordering::Relaxed);
+        debug!("Crossfade changed to: {:?}", duration);
+
+        Ok(())
+    }
+
+    pub fn crossfade(&self) -> Duration {
+        Duration::from_millis(self.crossfade.load(Ordering::Relaxed))
+    }
+
+    /// Changes how ReplayGain is applied, takes effect from the next track
+    pub fn set_replay_gain(&self, settings: ReplayGainSettings) {
+        *self.replay_gain.lock().unwrap() = settings;
+        debug!("ReplayGain changed to: {:?}", settings);
+    }
+
+    /// Enables or disables shuffle, the current track keeps playing
+    ///
+    /// # Arguments
+    ///
+    /// * 'enabled' - Whether the queue should be played in random order
+    /// * 'seed' - Seed of the play order, a random one is used if `None`
+    pub async fn set_shuffle(&self, enabled: bool, seed: Option<u64>) {
+        self.queue.set_shuffle(enabled, seed).await;
+        self.flush_preload().await;
+
+        if let Err(err) = self.event_handler.send(Event::ShuffleChanged(enabled)) {
+            warn!("{}", err);
+        }
+    }
+
+    pub fn is_shuffle(&self) -> bool {
+        self.queue.is_shuffle()
+    }
+
+    /// Changes the repeat mode, takes effect when the current track ends
+    pub fn set_repeat(&self, repeat: Repeat) -> anyhow::Result<()> {
+        *self.repeat.lock().unwrap() = repeat;
+        self.event_handler.send(Event::RepeatChanged(repeat))?;
+        info!("Repeat changed to: {:?}", repeat);
+
+        Ok(())
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        *self.repeat.lock().unwrap()
+    }
+
+    /// Measures the loudness of the queued tracks without ReplayGain tags
+    /// and returns the number of analyzed tracks
+    pub async fn analyze_loudness(&self) -> anyhow::Result<usize> {
+        let mut analyzed = 0;
+
+        for track in self.queue.get_playlist().await {
+            if !track.replay_gain().is_empty() {
+                continue;
+            }
+
+            let path = track.path().clone();
+            let result = async_runtime::spawn_blocking(move || ReplayGain::analyze(path)).await?;
+            match result {
+                Ok(replay_gain) => {
+                    info!("Analyzed {}: {:?}", track.path().display(), replay_gain);
+                    //the track may have been moved or removed during the analysis
+                    let updated = self
+                        .queue
+                        .update(&track, |track| track.set_replay_gain(replay_gain))
+                        .await;
+                    if updated > 0 {
+                        analyzed += 1;
+                    }
+                }
+                Err(err) => warn!("Unable to analyze {}: {}", track.path().display(), err),
+            }
+        }
+
+        Ok(analyzed)
     }
 
     pub async fn change_track(&self, index: usize) -> anyhow::Result<()> {
@@ -144,6 +653,118 @@ impl Player {
         Ok(())
     }
 
+    /// Removes the track from the queue, skipping to the next one if it is playing
+    pub async fn remove(&self, index: usize) -> anyhow::Result<()> {
+        let playing = self.is_playing() && self.queue.current() == index;
+        let track = self.queue.remove(index).await?;
+        info!("Removed {} from queue", track.path().display());
+
+        if playing {
+            self.next().await;
+        } else {
+            self.flush_preload().await;
+        }
+        self.event_handler.send(Event::PlaylistChanged)?;
+
+        Ok(())
+    }
+
+    /// Moves the track to another position in the queue, the current track keeps playing
+    ///
+    /// # Arguments
+    ///
+    /// * 'from' - Index of the track to move
+    /// * 'to' - Index the track ends up at
+    pub async fn move_track(&self, from: usize, to: usize) -> anyhow::Result<()> {
+        self.queue.move_track(from, to).await?;
+        self.flush_preload().await;
+        info!("Moved track from {} to {}", from, to);
+        self.event_handler.send(Event::PlaylistChanged)?;
+
+        Ok(())
+    }
+
+    /// Marks the queued tracks at or under the path as available or not,
+    /// unavailable tracks are skipped by the queue
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The path to a file, or to a folder for every track in it
+    /// * 'available' - Whether the files can be played
+    pub async fn set_available(&self, path: &Path, available: bool) -> anyhow::Result<()> {
+        let changed = self.queue.set_available(path, available).await;
+        if changed > 0 {
+            info!(
+                "Marked {} tracks at {} as available: {}",
+                changed,
+                path.display(),
+                available
+            );
+            self.event_handler.send(Event::PlaylistChanged)?;
+        }
+
+        Ok(())
+    }
+
+    /// Points the queued tracks at or under the path to the location the files were moved to
+    pub async fn relocate(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        let moved = self.queue.relocate(from, to).await;
+        if moved > 0 {
+            info!(
+                "Moved {} tracks from {} to {}",
+                moved,
+                from.display(),
+                to.display()
+            );
+            self.event_handler.send(Event::PlaylistChanged)?;
+        }
+
+        Ok(())
+    }
+
+    /// Opens the file and queues it to play right after the current track
+    pub async fn insert_after_current(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let index = self
+            .queue
+            .insert_after_current(Track::try_new(path.as_ref())?)
+            .await;
+        self.flush_preload().await;
+        info!("Inserted {} at {}", path.as_ref().display(), index);
+        self.event_handler.send(Event::PlaylistChanged)?;
+
+        Ok(())
+    }
+
+    /// Replaces the tracks of the queue, a playing queue moves on to the first new track
+    pub async fn replace(&self, tracks: Vec<Track>) -> anyhow::Result<()> {
+        self.queue.clear().await;
+        for track in tracks {
+            self.queue.add(track).await;
+        }
+        info!("Queue replaced");
+        self.event_handler.send(Event::PlaylistChanged)?;
+
+        if self.is_playing() {
+            self.next().await;
+        }
+
+        Ok(())
+    }
+
+    /// Stops the playback and removes every track from the queue
+    pub async fn clear(&self) -> anyhow::Result<()> {
+        self.stop().await;
+        self.queue.clear().await;
+        info!("Queue cleared");
+        self.event_handler.send(Event::PlaylistChanged)?;
+
+        Ok(())
+    }
+
+    pub async fn current_track(&self) -> Option<Track> {
+        self.queue.current_track().await
+    }
+
     pub async fn get_album_cover(&self) -> anyhow::Result<AlbumCover> {
         self.queue
             .current_track()
@@ -154,21 +775,64 @@ impl Player {
 
     pub fn seek(&self, pos: impl Into<Duration>) -> anyhow::Result<()> {
         let duration = pos.into();
-        self.sink
+        //cuts off the tail of a crossfade in progress, the other sink may hold nothing else
+        if self.fading.load(Ordering::Relaxed) {
+            self.sinks[self.active.load(Ordering::Relaxed) ^ 1].stop();
+        }
+        self.sink()
             .try_seek(duration)
-            .inspect(|_| self.playtime.blocking_write().change(duration))
             .map_err(|err| anyhow!("{}", err))
     }
 }
 
-#[allow(dead_code)]
-struct StreamWrapper(OutputStream);
+/// Track appended to one of the sinks
+struct Appended {
+    path: PathBuf,
+    span: Option<Span>,
+    finished: Receiver<()>,
+    fade: FadeHandle,
+    position: PositionHandle,
+    cancel: CancelHandle,
+}
+
+impl Appended {
+    /// Whether the appended source plays the track, the tracks of a CUE sheet share their file
+    fn plays(&self, track: &Track) -> bool {
+        self.path == *track.path() && self.span == track.span()
+    }
+}
 
-unsafe impl Send for StreamWrapper {}
-unsafe impl Sync for StreamWrapper {}
+/// What happens when the current track or the whole queue ends
+#[derive(Clone, Copy, Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Repeat {
+    #[default]
+    Off,
+    /// Starts the queue over once it ends
+    All,
+    /// Plays the current track again
+    One,
+}
+
+impl Repeat {
+    /// Returns the mode following this one, in the order off, all, one
+    pub fn cycle(self) -> Self {
+        match self {
+            Repeat::Off => Repeat::All,
+            Repeat::All => Repeat::One,
+            Repeat::One => Repeat::Off,
+        }
+    }
+}
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Event {
     TrackChanged(usize),
     PlaybackStopped,
+    PlaybackPaused,
+    PlaybackResumed,
+    RepeatChanged(Repeat),
+    ShuffleChanged(bool),
+    PlaylistChanged,
+    VolumeChanged(Volume),
 }
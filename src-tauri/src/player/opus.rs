@@ -0,0 +1,119 @@
+use std::sync::Mutex;
+
+use audiopus::{
+    coder::Decoder as LibOpus, packet::Packet as OpusPacket, Channels, MutSignals, SampleRate,
+};
+use log::warn;
+use symphonia::core::{
+    audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Signal, SignalSpec},
+    codecs::{
+        CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult, CODEC_TYPE_OPUS,
+    },
+    errors::{decode_error, unsupported_error, Error, Result},
+    formats::Packet,
+    support_codec,
+};
+
+/// Opus always decodes at 48 kHz, the sample rate of the input is only informative
+const SAMPLE_RATE: u32 = 48_000;
+/// Frames of the longest packet, 120 ms at 48 kHz
+const MAX_FRAMES: usize = 5760;
+
+/// Opus decoder for symphonia, which has none, backed by libopus.
+/// Only mono and stereo streams are supported, surround ones need the multistream API.
+pub struct OpusDecoder {
+    params: CodecParameters,
+    /// Locked only to be `Sync`, decoding takes `&mut self` anyway
+    decoder: Mutex<LibOpus>,
+    channels: Channels,
+    buffer: AudioBuffer<f32>,
+    /// Interleaved samples libopus decodes into
+    samples: Vec<f32>,
+}
+
+impl OpusDecoder {
+    fn open(channels: Channels) -> Result<LibOpus> {
+        LibOpus::new(SampleRate::Hz48000, channels).map_err(|err| {
+            warn!("Unable to create the Opus decoder: {}", err);
+            Error::Unsupported("opus: unable to create the decoder")
+        })
+    }
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        let layout = match params.channels {
+            Some(layout) => layout,
+            None => return unsupported_error("opus: channels are missing"),
+        };
+        let channels = match layout.count() {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            _ => return unsupported_error("opus: more than two channels"),
+        };
+
+        Ok(Self {
+            params: params.clone(),
+            decoder: Mutex::new(Self::open(channels)?),
+            channels,
+            buffer: AudioBuffer::new(MAX_FRAMES as u64, SignalSpec::new(SAMPLE_RATE, layout)),
+            samples: vec![0.0; MAX_FRAMES * layout.count()],
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        //libopus keeps the state of the previous packets, seeking starts over
+        match Self::open(self.channels) {
+            Ok(decoder) => self.decoder = Mutex::new(decoder),
+            Err(err) => warn!("{}", err),
+        }
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        let decoded = OpusPacket::try_from(packet.buf()).and_then(|input| {
+            let output = MutSignals::try_from(&mut self.samples[..])?;
+            self.decoder
+                .get_mut()
+                .unwrap()
+                .decode_float(Some(input), output, false)
+        });
+        let n_frames = match decoded {
+            Ok(n_frames) => n_frames,
+            Err(err) => {
+                warn!("Unable to decode an Opus packet: {}", err);
+                return decode_error("opus: invalid packet");
+            }
+        };
+
+        let count = self.buffer.spec().channels.count();
+        self.buffer.clear();
+        self.buffer.render_reserved(Some(n_frames));
+        for channel in 0..count {
+            let plane = self.buffer.chan_mut(channel);
+            for (frame, sample) in plane.iter_mut().enumerate() {
+                *sample = self.samples[frame * count + channel];
+            }
+        }
+        //drops the encoder delay and padding, the format reader marks them on the packets
+        self.buffer
+            .trim(packet.trim_start() as usize, packet.trim_end() as usize);
+
+        Ok(self.buffer.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buffer.as_audio_buffer_ref()
+    }
+}
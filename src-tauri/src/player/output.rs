@@ -0,0 +1,239 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::bail;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use log::{debug, error, info};
+use rodio::{OutputStream, OutputStreamHandle, Source};
+
+/// Length of the chunk consumed by `NullOutput` and `FileOutput` on every tick
+const OUTPUT_TICK: Duration = Duration::from_millis(10);
+
+/// Mixed output of the `Player` sinks
+pub type PlayerSource = Box<dyn Source<Item = f32> + Send>;
+
+/// Audio backend the `Player` sinks are attached to.
+pub trait Output: Send + Sync {
+    /// Starts consuming samples produced by the player.
+    fn attach(&mut self, source: PlayerSource) -> anyhow::Result<()>;
+
+    /// Returns how long the samples pulled from the source take to be heard, the
+    /// position of the player is behind the pulled samples by this much.
+    /// Backends that cannot tell their buffering report zero.
+    fn latency(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Plays audio through the default device using rodio and cpal.
+pub struct RodioOutput {
+    _stream: StreamWrapper,
+    handle: OutputStreamHandle,
+}
+
+impl RodioOutput {
+    pub fn try_default() -> anyhow::Result<Self> {
+        let (stream, handle) = OutputStream::try_default()?;
+
+        Ok(Self {
+            _stream: StreamWrapper(stream),
+            handle,
+        })
+    }
+}
+
+//rodio does not expose the buffer size cpal picked for the device, so no latency is reported
+impl Output for RodioOutput {
+    fn attach(&mut self, source: PlayerSource) -> anyhow::Result<()> {
+        self.handle.play_raw(source)?;
+        info!("Attached to the default output device");
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+struct StreamWrapper(OutputStream);
+
+unsafe impl Send for StreamWrapper {}
+unsafe impl Sync for StreamWrapper {}
+
+/// Pulls the samples of the source on a thread, one tick at a time, at wall-clock speed
+/// multiplied by `speed`.
+struct Ticker {
+    speed: f32,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Ticker {
+    fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            running: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        }
+    }
+
+    fn with_speed(speed: f32) -> anyhow::Result<Self> {
+        if !(speed.is_finite() && speed > 0.0) {
+            bail!("Output speed must be positive, got {}", speed);
+        }
+
+        Ok(Self::new(speed))
+    }
+
+    /// Starts pulling the samples until the ticker is dropped or the source is exhausted
+    ///
+    /// # Arguments
+    ///
+    /// * 'name' - Name of the output for the logs
+    /// * 'source' - The source to pull the samples from
+    /// * 'consume' - Called with the samples of every tick, stops the ticker on error
+    fn start(
+        &mut self,
+        name: &'static str,
+        mut source: PlayerSource,
+        mut consume: impl FnMut(&[f32]) -> anyhow::Result<()> + Send + 'static,
+    ) -> anyhow::Result<()> {
+        if self.running.swap(true, Ordering::Relaxed) {
+            bail!("{} is already attached", name);
+        }
+
+        let running = self.running.clone();
+        let tick = OUTPUT_TICK.div_f32(self.speed);
+        self.thread = Some(thread::spawn(move || {
+            let mut chunk = Vec::new();
+            while running.load(Ordering::Relaxed) {
+                let frames =
+                    source.sample_rate() as usize * OUTPUT_TICK.as_millis() as usize / 1000;
+                let samples = frames * source.channels() as usize;
+
+                chunk.clear();
+                chunk.extend(source.by_ref().take(samples));
+                if let Err(err) = consume(&chunk) {
+                    error!("{} failed: {}", name, err);
+                    break;
+                }
+                if chunk.len() < samples {
+                    debug!("{} source exhausted", name);
+                    break;
+                }
+
+                thread::sleep(tick);
+            }
+        }));
+        info!("Attached to the {} with speed {}", name, self.speed);
+
+        Ok(())
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Discards samples at wall-clock speed multiplied by `speed`.
+/// Used when no sound card is available and in tests.
+pub struct NullOutput {
+    ticker: Ticker,
+}
+
+impl NullOutput {
+    /// Creates an output that consumes samples `speed` times faster than real time.
+    ///
+    /// # Arguments
+    ///
+    /// * 'speed' - Playback speed multiplier, must be positive
+    pub fn with_speed(speed: f32) -> anyhow::Result<Self> {
+        Ok(Self {
+            ticker: Ticker::with_speed(speed)?,
+        })
+    }
+}
+
+impl Default for NullOutput {
+    fn default() -> Self {
+        Self {
+            ticker: Ticker::new(1.0),
+        }
+    }
+}
+
+impl Output for NullOutput {
+    fn attach(&mut self, source: PlayerSource) -> anyhow::Result<()> {
+        self.ticker.start("null output", source, |_| Ok(()))
+    }
+
+    /// Every tick pulls the samples of the whole tick before sleeping through it
+    fn latency(&self) -> Duration {
+        OUTPUT_TICK
+    }
+}
+
+/// Writes samples to a 32-bit float WAV file at wall-clock speed multiplied by `speed`.
+/// Used to record what the player outputs, e.g. to check it in tests.
+pub struct FileOutput {
+    ticker: Ticker,
+    file: Option<BufWriter<File>>,
+}
+
+impl FileOutput {
+    /// Creates the file and an output writing to it `speed` times faster than real time.
+    /// The file is complete once the output is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - Path of the WAV file, replaced if it exists
+    /// * 'speed' - Playback speed multiplier, must be positive
+    pub fn create(path: impl AsRef<Path>, speed: f32) -> anyhow::Result<Self> {
+        let ticker = Ticker::with_speed(speed)?;
+        let file = BufWriter::new(File::create(path)?);
+
+        Ok(Self {
+            ticker,
+            file: Some(file),
+        })
+    }
+}
+
+impl Output for FileOutput {
+    fn attach(&mut self, source: PlayerSource) -> anyhow::Result<()> {
+        let Some(file) = self.file.take() else {
+            bail!("File output is already attached");
+        };
+        let spec = WavSpec {
+            channels: source.channels(),
+            sample_rate: source.sample_rate(),
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = WavWriter::new(file, spec)?;
+
+        self.ticker.start("file output", source, move |samples| {
+            for &sample in samples {
+                writer.write_sample(sample)?;
+            }
+            //keeps the header up to date, so the file is readable while it is written
+            writer.flush()?;
+            Ok(())
+        })
+    }
+
+    fn latency(&self) -> Duration {
+        OUTPUT_TICK
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+    sync::OnceLock,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use log::debug;
+use symphonia::core::{
+    audio::{SampleBuffer, SignalSpec},
+    codecs::{CodecParameters, CodecRegistry, DecoderOptions, CODEC_TYPE_MP3},
+    errors::Error,
+    formats::{FormatOptions, SeekMode, SeekTo},
+    io::MediaSourceStream,
+    meta::{MetadataOptions, Tag},
+    probe::{Hint, ProbeResult},
+    units::TimeBase,
+};
+
+use super::opus::OpusDecoder;
+
+/// Returns the codecs of symphonia along with the Opus decoder it lacks
+pub fn codecs() -> &'static CodecRegistry {
+    static CODECS: OnceLock<CodecRegistry> = OnceLock::new();
+
+    CODECS.get_or_init(|| {
+        let mut registry = CodecRegistry::new();
+        symphonia::default::register_enabled_codecs(&mut registry);
+        registry.register_all::<OpusDecoder>();
+        registry
+    })
+}
+
+/// Opens the file and detects its container format using symphonia
+///
+/// # Arguments
+///
+/// * 'path' - The path to the audio file
+pub fn probe(path: impl AsRef<Path>) -> anyhow::Result<ProbeResult> {
+    let path = path.as_ref();
+    let stream = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            //trims the encoder delay and padding
+            &FormatOptions {
+                enable_gapless: true,
+                ..Default::default()
+            },
+            &MetadataOptions::default(),
+        )
+        .map_err(|err| anyhow!("Unable to probe {}: {}", path.display(), err))
+}
+
+/// Returns the tags found before the container (e.g. ID3v2) and inside of it (e.g. Vorbis comments)
+pub fn tags(probed: &mut ProbeResult) -> Vec<Tag> {
+    let mut tags = Vec::new();
+
+    if let Some(metadata) = probed.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            tags.extend_from_slice(revision.tags());
+        }
+    }
+
+    if let Some(revision) = probed.format.metadata().current() {
+        tags.extend_from_slice(revision.tags());
+    }
+
+    tags
+}
+
+/// How many bytes after the ID3v2 tag are searched for the first MPEG frame
+const FRAME_SEARCH: u64 = 4096;
+
+/// Returns the duration of the default track. The headers are used if they state the exact
+/// length and the file reaches it, otherwise the packets are counted, and decoded if they
+/// carry no duration.
+///
+/// # Arguments
+///
+/// * 'path' - The path to the audio file
+pub fn duration(path: impl AsRef<Path>) -> anyhow::Result<Duration> {
+    let path = path.as_ref();
+    let mut probed = probe(path)?;
+    let track = probed
+        .format
+        .default_track()
+        .ok_or(anyhow!("No audio track found"))?;
+    let (track_id, params) = (track.id, track.codec_params.clone());
+
+    if let Some(n_frames) = params.n_frames.filter(|n_frames| *n_frames > 0) {
+        let exact = if params.codec == CODEC_TYPE_MP3 {
+            //seeking a mp3 is based on the file size, so the VBR header is checked instead
+            has_exact_vbr_header(path)?
+        } else {
+            //a truncated file has no packets where the headers say it ends
+            let seek = SeekTo::TimeStamp {
+                ts: n_frames - n_frames / 100,
+                track_id,
+            };
+            probed.format.seek(SeekMode::Coarse, seek).is_ok()
+                && probed.format.next_packet().is_ok()
+        };
+
+        if exact {
+            if let Some(duration) = to_duration(&params, n_frames) {
+                return Ok(duration);
+            }
+        }
+        debug!("Length in the headers of {} is wrong", path.display());
+    }
+
+    debug!("Counting packets of {} for its duration", path.display());
+    if let Some(duration) = count_packets(&mut probe(path)?, &params)? {
+        return Ok(duration);
+    }
+
+    debug!("Decoding {} for its duration", path.display());
+    let mut n_frames = 0;
+    decode(&mut probe(path)?, |spec, samples| {
+        n_frames += (samples.len() / spec.channels.count()) as u64;
+    })?;
+
+    to_duration(&params, n_frames).ok_or(anyhow!("Unknown sample rate"))
+}
+
+fn to_duration(params: &CodecParameters, n_frames: u64) -> Option<Duration> {
+    let time_base = match params.time_base {
+        Some(time_base) => time_base,
+        None => TimeBase::new(1, params.sample_rate?),
+    };
+    let time = time_base.calc_time(n_frames);
+
+    Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+}
+
+/// Sums the durations of the packets of the default track without decoding them,
+/// returns `None` if a packet does not state its duration
+fn count_packets(
+    probed: &mut ProbeResult,
+    params: &CodecParameters,
+) -> anyhow::Result<Option<Duration>> {
+    let track_id = probed
+        .format
+        .default_track()
+        .ok_or(anyhow!("No audio track found"))?
+        .id;
+    let mut n_frames = 0;
+
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(err)) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(err) => return Err(err.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+        if packet.dur == 0 {
+            return Ok(None);
+        }
+        n_frames += packet.dur;
+    }
+
+    Ok(to_duration(params, n_frames).filter(|duration| !duration.is_zero()))
+}
+
+/// Decodes the default track of the probed file, passing every decoded
+/// chunk as interleaved samples to the callback
+///
+/// # Arguments
+///
+/// * 'probed' - The probed file
+/// * 'f' - Callback receiving the signal spec and the samples of each decoded packet
+pub fn decode(
+    probed: &mut ProbeResult,
+    mut f: impl FnMut(SignalSpec, &[f32]),
+) -> anyhow::Result<()> {
+    let track = probed
+        .format
+        .default_track()
+        .ok_or(anyhow!("No audio track found"))?;
+    let track_id = track.id;
+    let mut decoder = codecs().make(&track.codec_params, &DecoderOptions::default())?;
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(err)) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(err) => return Err(err.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            //corrupted packets are skipped
+            Err(Error::DecodeError(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        let spec = *decoded.spec();
+        let samples = match &mut buffer {
+            Some(buffer) if buffer.capacity() >= decoded.capacity() * spec.channels.count() => {
+                buffer
+            }
+            _ => buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        samples.copy_interleaved_ref(decoded);
+
+        f(spec, samples.samples());
+    }
+
+    Ok(())
+}
+
+/// Xing, Info or VBRI header in the first MPEG frame, which states the exact number of frames
+#[derive(Debug, PartialEq)]
+struct VbrHeader {
+    /// Size of the audio stream in bytes
+    bytes: Option<u64>,
+}
+
+/// Checks whether the mp3 has a VBR header and the file is as long as the header says.
+/// Without one the length of the file is only estimated from its first frames.
+fn has_exact_vbr_header(path: &Path) -> anyhow::Result<bool> {
+    let mut file = File::open(path)?;
+    let mut header = [0; 10];
+    file.read_exact(&mut header)?;
+
+    let offset = match &header[..3] {
+        b"ID3" => {
+            //the size is a syncsafe integer, 7 bits per byte
+            let size = header[6..10]
+                .iter()
+                .fold(0, |size, byte| size << 7 | u64::from(byte & 0x7f));
+            let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+            10 + size + footer
+        }
+        _ => 0,
+    };
+    let stream_length = file.metadata()?.len().saturating_sub(offset);
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut buffer = Vec::new();
+    file.take(FRAME_SEARCH).read_to_end(&mut buffer)?;
+
+    Ok(match find_vbr_header(&buffer) {
+        Some(VbrHeader { bytes: Some(bytes) }) => stream_length >= bytes - bytes / 100,
+        Some(VbrHeader { bytes: None }) => true,
+        None => false,
+    })
+}
+
+fn find_vbr_header(buffer: &[u8]) -> Option<VbrHeader> {
+    let start = buffer
+        .windows(4)
+        .position(|header| header[0] == 0xff && header[1] & 0xe0 == 0xe0)?;
+    let frame = &buffer[start..];
+    let read_u32 = |offset: usize| {
+        frame
+            .get(offset..offset + 4)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    //the Xing header follows the side information, which depends on the version and channels
+    let mpeg1 = frame[1] & 0x18 == 0x18;
+    let mono = frame[3] & 0xc0 == 0xc0;
+    let xing = 4 + match (mpeg1, mono) {
+        (true, true) => 17,
+        (true, false) => 32,
+        (false, true) => 9,
+        (false, false) => 17,
+    };
+    if let Some(b"Xing" | b"Info") = frame.get(xing..xing + 4) {
+        let flags = read_u32(xing + 4)?;
+        //the byte count follows the frame count if both are present
+        let bytes = match (flags & 0x1 != 0, flags & 0x2 != 0) {
+            (true, true) => read_u32(xing + 12),
+            (false, true) => read_u32(xing + 8),
+            _ => None,
+        };
+        return Some(VbrHeader {
+            bytes: bytes.map(u64::from),
+        });
+    }
+
+    //the VBRI header is always 32 bytes after the frame header
+    match frame.get(36..40) {
+        Some(b"VBRI") => Some(VbrHeader {
+            bytes: read_u32(46).map(u64::from),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use temp_dir::TempDir;
+
+    use super::{duration, find_vbr_header, VbrHeader};
+
+    /// MPEG 1 Layer III, 128 kbps, 44.1 kHz
+    fn frame(mono: bool, header: &[u8], offset: usize) -> Vec<u8> {
+        let mut frame = vec![0xff, 0xfb, 0x90, if mono { 0xc0 } else { 0x00 }];
+        frame.resize(offset, 0);
+        frame.extend_from_slice(header);
+        frame.resize(417, 0);
+        frame
+    }
+
+    #[test]
+    fn xing_header() {
+        let header = [b"Xing".as_slice(), &[0, 0, 0, 3, 0, 0, 0, 10, 0, 0, 16, 0]].concat();
+
+        assert_eq!(
+            Some(VbrHeader { bytes: Some(4096) }),
+            find_vbr_header(&frame(false, &header, 36))
+        );
+    }
+
+    #[test]
+    fn xing_header_mono() {
+        let header = [b"Xing".as_slice(), &[0, 0, 0, 2, 0, 0, 16, 0]].concat();
+
+        assert_eq!(
+            Some(VbrHeader { bytes: Some(4096) }),
+            find_vbr_header(&frame(true, &header, 21))
+        );
+    }
+
+    #[test]
+    fn info_header_without_bytes() {
+        let header = [b"Info".as_slice(), &[0, 0, 0, 1, 0, 0, 0, 10]].concat();
+
+        assert_eq!(
+            Some(VbrHeader { bytes: None }),
+            find_vbr_header(&frame(false, &header, 36))
+        );
+    }
+
+    #[test]
+    fn vbri_header() {
+        let header = [b"VBRI".as_slice(), &[0, 1, 0, 0, 0, 0, 0, 0, 16, 0]].concat();
+
+        assert_eq!(
+            Some(VbrHeader { bytes: Some(4096) }),
+            find_vbr_header(&frame(false, &header, 36))
+        );
+    }
+
+    #[test]
+    fn vbr_header_after_garbage() {
+        let mut buffer = vec![0; 10];
+        buffer.extend(frame(false, b"Xing\0\0\0\0", 36));
+
+        assert_eq!(Some(VbrHeader { bytes: None }), find_vbr_header(&buffer));
+    }
+
+    #[test]
+    fn no_vbr_header() {
+        assert_eq!(None, find_vbr_header(&frame(false, b"", 36)));
+        assert_eq!(None, find_vbr_header(&frame(true, b"Xing", 36)));
+        assert_eq!(None, find_vbr_header(&[]));
+    }
+
+    #[test]
+    fn duration_mp3() {
+        let actual = duration("tests/assets/track.mp3").unwrap();
+
+        assert_eq!(4, actual.as_secs_f64().round() as u64);
+    }
+
+    #[test]
+    fn duration_flac() {
+        let actual = duration("tests/assets/track.flac").unwrap();
+
+        assert_eq!(6, actual.as_secs_f64().round() as u64);
+    }
+
+    #[test]
+    fn duration_wav() {
+        let actual = duration("tests/assets/track.wav").unwrap();
+
+        assert_eq!(Duration::from_secs(2), actual);
+    }
+
+    #[test]
+    fn duration_truncated() {
+        let dir = TempDir::new().unwrap();
+        for asset in ["track.flac", "track.mp3", "track.wav"] {
+            let path = dir.path().join(asset);
+            let content = fs::read(Path::new("tests/assets").join(asset)).unwrap();
+            fs::write(&path, &content[..content.len() / 2]).unwrap();
+            let full = duration(Path::new("tests/assets").join(asset)).unwrap();
+
+            let actual = duration(&path).unwrap();
+
+            assert!(actual < full.mul_f32(0.6), "{}: {:?}", asset, actual);
+        }
+    }
+}
@@ -1,47 +1,236 @@
 use std::{
     collections::VecDeque,
-    sync::atomic::{AtomicUsize, Ordering},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex as SyncMutex,
+    },
 };
 
 use log::info;
 use tauri::async_runtime::Mutex;
 
-use crate::util::AtomicSub;
+use crate::util::{AtomicSub, Random};
 
 use super::track::Track;
 
 pub struct Queue {
     tracks: Mutex<VecDeque<Track>>,
+    /// Ids of the tracks, in the same order. An id stays with its entry while the queue is
+    /// edited, unlike the index. Only changed while the tracks are locked.
+    ids: SyncMutex<VecDeque<u64>>,
+    /// Id of the next track added to the queue
+    next_id: AtomicU64,
+    /// Position in the play order of the track `next` returns
     current: AtomicUsize,
+    shuffle: SyncMutex<Option<Shuffle>>,
+}
+
+/// Play order of the track indices while shuffle is enabled,
+/// the tracks themselves keep their original order
+struct Shuffle {
+    order: Vec<usize>,
+    random: Random,
 }
 
 impl Queue {
     pub fn new() -> Self {
         Self {
             tracks: Mutex::new(VecDeque::new()),
+            ids: SyncMutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(0),
             current: AtomicUsize::new(0),
+            shuffle: SyncMutex::new(None),
         }
     }
 
     pub async fn add(&self, track: Track) {
         info!("Adding track to queue: {:?}", track.path());
-        self.tracks.lock().await.push_back(track);
+        let mut tracks = self.tracks.lock().await;
+
+        //new tracks are shuffled into the part of the order that is not played yet
+        if let Some(shuffle) = self.shuffle.lock().unwrap().as_mut() {
+            let start = self
+                .current
+                .load(Ordering::Relaxed)
+                .min(shuffle.order.len());
+            let position = start + shuffle.random.below(shuffle.order.len() - start + 1);
+            shuffle.order.insert(position, tracks.len());
+        }
+
+        tracks.push_back(track);
+        self.ids.lock().unwrap().push_back(self.new_id());
+    }
+
+    fn new_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Queues the track to play right after the current one and returns its index
+    pub async fn insert_after_current(&self, track: Track) -> usize {
+        info!("Inserting track after current: {:?}", track.path());
+        let mut tracks = self.tracks.lock().await;
+        let position = self.current.load(Ordering::Relaxed);
+
+        let index = match self.shuffle.lock().unwrap().as_mut() {
+            Some(shuffle) => {
+                let index = match position {
+                    0 => 0,
+                    _ => shuffle
+                        .order
+                        .get(position - 1)
+                        .map_or(tracks.len(), |current| current + 1),
+                };
+                shuffle
+                    .order
+                    .iter_mut()
+                    .filter(|i| **i >= index)
+                    .for_each(|i| *i += 1);
+                shuffle
+                    .order
+                    .insert(position.min(shuffle.order.len()), index);
+                index
+            }
+            None => position.min(tracks.len()),
+        };
+
+        tracks.insert(index, track);
+        self.ids.lock().unwrap().insert(index, self.new_id());
+        index
+    }
+
+    /// Removes the track at the given index, the cursor keeps pointing at the same track.
+    /// If the current track is removed, `next` returns the one that followed it.
+    pub async fn remove(&self, index: usize) -> anyhow::Result<Track> {
+        let mut tracks = self.tracks.lock().await;
+        let track = tracks
+            .remove(index)
+            .ok_or(anyhow::anyhow!("Invalid index: {}", index))?;
+        self.ids.lock().unwrap().remove(index);
+
+        let mut position = self.current.load(Ordering::Relaxed);
+        match self.shuffle.lock().unwrap().as_mut() {
+            Some(shuffle) => {
+                if let Some(removed) = shuffle.order.iter().position(|i| *i == index) {
+                    shuffle.order.remove(removed);
+                    if removed < position {
+                        position -= 1;
+                    }
+                }
+                shuffle
+                    .order
+                    .iter_mut()
+                    .filter(|i| **i > index)
+                    .for_each(|i| *i -= 1);
+            }
+            None if index < position => position -= 1,
+            None => {}
+        }
+        self.current.store(position, Ordering::Relaxed);
+
+        Ok(track)
+    }
+
+    /// Moves the track to another index, the cursor keeps pointing at the same track
+    ///
+    /// # Arguments
+    ///
+    /// * 'from' - Index of the track to move
+    /// * 'to' - Index the track ends up at
+    pub async fn move_track(&self, from: usize, to: usize) -> anyhow::Result<()> {
+        let mut tracks = self.tracks.lock().await;
+        let len = tracks.len();
+        if from >= len || to >= len {
+            anyhow::bail!("Invalid index: {}", from.max(to));
+        }
+
+        let mut ids = self.ids.lock().unwrap();
+        if let (Some(track), Some(id)) = (tracks.remove(from), ids.remove(from)) {
+            tracks.insert(to, track);
+            ids.insert(to, id);
+        }
+
+        //new index of the track that was at the given index
+        let moved = |index: usize| {
+            if index == from {
+                to
+            } else if from < index && index <= to {
+                index - 1
+            } else if to <= index && index < from {
+                index + 1
+            } else {
+                index
+            }
+        };
+
+        match self.shuffle.lock().unwrap().as_mut() {
+            Some(shuffle) => shuffle.order.iter_mut().for_each(|i| *i = moved(*i)),
+            None => {
+                let position = self.current.load(Ordering::Relaxed);
+                if (1..=len).contains(&position) {
+                    self.current
+                        .store(moved(position - 1) + 1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes every track and moves back to the start of the queue
+    pub async fn clear(&self) {
+        let mut tracks = self.tracks.lock().await;
+        tracks.clear();
+        self.ids.lock().unwrap().clear();
+        self.current.store(0, Ordering::Relaxed);
+        if let Some(shuffle) = self.shuffle.lock().unwrap().as_mut() {
+            shuffle.order.clear();
+        }
+    }
+
+    /// Maps a position in the play order to the index of the track
+    fn index(&self, position: usize) -> Option<usize> {
+        match self.shuffle.lock().unwrap().as_ref() {
+            Some(shuffle) => shuffle.order.get(position).copied(),
+            None => Some(position),
+        }
     }
 
     pub async fn next(&self) -> Option<Track> {
+        let tracks = self.tracks.lock().await;
+        let position = self.current.fetch_add(1, Ordering::Relaxed);
+
+        self.index(position)
+            .and_then(|index| tracks.get(index))
+            .cloned()
+    }
+
+    /// Returns the track `next` would return along with its index, without advancing the queue
+    pub async fn peek(&self) -> Option<(usize, Track)> {
+        let tracks = self.tracks.lock().await;
+        let index = self.index(self.current.load(Ordering::Relaxed))?;
+
+        tracks.get(index).cloned().map(|track| (index, track))
+    }
+
+    /// Returns the track `next` returns after `reset` along with its index, `None` while
+    /// shuffled as `reset` reorders the queue
+    pub async fn first(&self) -> Option<(usize, Track)> {
+        if self.shuffle.lock().unwrap().is_some() {
+            return None;
+        }
+
         self.tracks
             .lock()
             .await
-            .get(self.current.fetch_add(1, Ordering::Relaxed))
+            .front()
             .cloned()
+            .map(|track| (0, track))
     }
 
     pub fn current(&self) -> usize {
-        let index = self.current.load(Ordering::Relaxed);
-        if index == 0 {
-            return index;
-        }
-        index - 1
+        let position = self.current.load(Ordering::Relaxed).saturating_sub(1);
+        self.index(position).unwrap_or(position)
     }
 
     pub async fn change_current(&self, index: usize) -> anyhow::Result<()> {
@@ -49,7 +238,23 @@ impl Queue {
             anyhow::bail!("Invalid index: {}", index);
         }
 
-        self.current.store(index, Ordering::Relaxed);
+        let mut position = self.current.load(Ordering::Relaxed);
+        match self.shuffle.lock().unwrap().as_mut() {
+            //moves the track right after the already played ones, so the history stays intact
+            Some(shuffle) => {
+                if let Some(old_position) = shuffle.order.iter().position(|i| *i == index) {
+                    shuffle.order.remove(old_position);
+                    if old_position < position {
+                        position -= 1;
+                    }
+                }
+                position = position.min(shuffle.order.len());
+                shuffle.order.insert(position, index);
+            }
+            None => position = index,
+        }
+
+        self.current.store(position, Ordering::Relaxed);
 
         Ok(())
     }
@@ -60,17 +265,145 @@ impl Queue {
         self.current.saturating_sub(2);
     }
 
+    /// Moves back by one track, so `next` returns the current track again
+    pub fn replay_current(&self) {
+        self.current.saturating_sub(1);
+    }
+
+    /// Enables or disables shuffle, the current track keeps playing
+    ///
+    /// # Arguments
+    ///
+    /// * 'enabled' - Whether the queue should be shuffled
+    /// * 'seed' - Seed of the play order, a random one is used if `None`
+    pub async fn set_shuffle(&self, enabled: bool, seed: Option<u64>) {
+        let len = self.tracks.lock().await.len();
+        let playing = (self.current.load(Ordering::Relaxed) > 0)
+            .then(|| self.current())
+            .filter(|index| *index < len);
+
+        let mut shuffle = self.shuffle.lock().unwrap();
+        if enabled {
+            let mut random = seed.map(Random::new).unwrap_or_default();
+            let mut order: Vec<usize> = (0..len).filter(|i| Some(*i) != playing).collect();
+            random.shuffle(&mut order);
+
+            //the current track becomes the start of the history
+            if let Some(index) = playing {
+                order.insert(0, index);
+            }
+            self.current
+                .store(playing.map_or(0, |_| 1), Ordering::Relaxed);
+            *shuffle = Some(Shuffle { order, random });
+        } else {
+            *shuffle = None;
+            self.current
+                .store(playing.map_or(0, |index| index + 1), Ordering::Relaxed);
+        }
+        info!("Shuffle {}", if enabled { "enabled" } else { "disabled" });
+    }
+
+    pub fn is_shuffle(&self) -> bool {
+        self.shuffle.lock().unwrap().is_some()
+    }
+
+    /// Modifies the tracks playing the same part of the same file as the given one in place,
+    /// returns the number of modified tracks. The queue may have been edited since the track
+    /// was read from it, so it is looked up again rather than by its index.
+    pub async fn update(&self, target: &Track, f: impl Fn(&mut Track)) -> usize {
+        let mut tracks = self.tracks.lock().await;
+        let mut updated = 0;
+
+        for track in tracks
+            .iter_mut()
+            .filter(|track| track.path() == target.path() && track.span() == target.span())
+        {
+            f(track);
+            updated += 1;
+        }
+
+        updated
+    }
+
+    /// Marks the tracks whose file is at or under the given path as available or not,
+    /// returns the number of tracks that changed
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The path to a file, or to a folder for every track in it
+    /// * 'available' - Whether the files can be played
+    pub async fn set_available(&self, path: &Path, available: bool) -> usize {
+        let mut tracks = self.tracks.lock().await;
+        let mut changed = 0;
+
+        for track in tracks
+            .iter_mut()
+            .filter(|track| track.path().starts_with(path) && track.is_available() != available)
+        {
+            track.set_available(available);
+            changed += 1;
+        }
+
+        changed
+    }
+
+    /// Moves the tracks whose file is at or under the given path to its new location,
+    /// returns the number of moved tracks
+    ///
+    /// # Arguments
+    ///
+    /// * 'from' - The old path to a file or folder
+    /// * 'to' - The path the file or folder was moved to
+    pub async fn relocate(&self, from: &Path, to: &Path) -> usize {
+        let mut tracks = self.tracks.lock().await;
+        let mut moved = 0;
+
+        for track in tracks.iter_mut() {
+            let Ok(relative) = track.path().strip_prefix(from) else {
+                continue;
+            };
+            //joining an empty path would append a trailing separator
+            let path = if relative.as_os_str().is_empty() {
+                to.to_path_buf()
+            } else {
+                to.join(relative)
+            };
+            track.set_path(path);
+            track.set_available(true);
+            moved += 1;
+        }
+
+        moved
+    }
+
     pub async fn current_track(&self) -> Option<Track> {
         self.tracks.lock().await.get(self.current()).cloned()
     }
 
+    /// Returns the ids of the tracks in the order of the queue
+    pub async fn ids(&self) -> Vec<u64> {
+        let _tracks = self.tracks.lock().await;
+        self.ids.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Returns the index of the track with the id, `None` once it was removed
+    pub async fn index_of(&self, id: u64) -> Option<usize> {
+        let _tracks = self.tracks.lock().await;
+        self.ids.lock().unwrap().iter().position(|i| *i == id)
+    }
+
     //TODO: Remove clone
     pub async fn get_playlist(&self) -> VecDeque<Track> {
         self.tracks.lock().await.clone()
     }
 
+    /// Moves back to the start of the queue, a shuffled queue gets a new play order
     pub fn reset(&self) {
         self.current.store(0, Ordering::Relaxed);
+        if let Some(shuffle) = self.shuffle.lock().unwrap().as_mut() {
+            let Shuffle { order, random } = shuffle;
+            random.shuffle(order);
+        }
     }
 }
 
@@ -78,8 +411,8 @@ impl Queue {
 mod tests {
     use tokio::test;
 
-    use crate::player::track::Track;
-    use std::collections::VecDeque;
+    use crate::player::{replaygain::ReplayGain, track::Track};
+    use std::{collections::VecDeque, path::Path};
 
     use super::Queue;
 
@@ -91,6 +424,27 @@ mod tests {
         assert_eq!(Track::default(), queue.next().await.unwrap());
     }
 
+    #[test]
+    async fn ids() {
+        let queue = Queue::new();
+        for _ in 0..3 {
+            queue.add(Track::default()).await;
+        }
+
+        queue.remove(0).await.unwrap();
+        queue.insert_after_current(Track::default()).await;
+        queue.move_track(0, 2).await.unwrap();
+
+        assert_eq!(vec![1, 2, 3], queue.ids().await);
+        assert_eq!(Some(2), queue.index_of(3).await);
+        assert_eq!(None, queue.index_of(0).await);
+
+        queue.clear().await;
+        queue.add(Track::default()).await;
+
+        assert_eq!(vec![4], queue.ids().await);
+    }
+
     #[test]
     async fn next() {
         let queue = Queue::new();
@@ -109,6 +463,42 @@ mod tests {
         assert_eq!(None, queue.next().await);
     }
 
+    #[test]
+    async fn peek() {
+        let queue = Queue::new();
+        queue.add(Track::default()).await;
+        queue.add(Track::default()).await;
+
+        queue.next().await;
+
+        assert_eq!(Some((1, Track::default())), queue.peek().await);
+        assert_eq!(0, queue.current());
+    }
+
+    #[test]
+    async fn peek_end_of_queue() {
+        let queue = Queue::new();
+        queue.add(Track::default()).await;
+
+        queue.next().await;
+
+        assert_eq!(None, queue.peek().await);
+    }
+
+    #[test]
+    async fn first() {
+        let queue = Queue::new();
+        queue.add(Track::default()).await;
+        queue.add(Track::default()).await;
+
+        queue.next().await;
+        queue.next().await;
+
+        assert_eq!(Some((0, Track::default())), queue.first().await);
+        queue.set_shuffle(true, Some(1)).await;
+        assert_eq!(None, queue.first().await);
+    }
+
     #[test]
     async fn current() {
         let queue = Queue::new();
@@ -188,6 +578,98 @@ mod tests {
         assert!(queue.change_current(1).await.is_err());
     }
 
+    #[test]
+    async fn update() {
+        let queue = Queue::new();
+        let replay_gain = ReplayGain {
+            track_gain: Some(-6.0),
+            ..Default::default()
+        };
+        let target = track_at("/music/b.mp3");
+        for path in ["/music/a.mp3", "/music/b.mp3"] {
+            queue.add(track_at(path)).await;
+        }
+        //the queue is edited while the track is worked on
+        queue.remove(0).await.unwrap();
+
+        let updated = queue
+            .update(&target, |track| track.set_replay_gain(replay_gain))
+            .await;
+
+        assert_eq!(1, updated);
+        assert_eq!(&replay_gain, queue.get_playlist().await[0].replay_gain());
+    }
+
+    fn track_at(path: &str) -> Track {
+        let mut track = Track::default();
+        track.set_path(path);
+        track
+    }
+
+    #[test]
+    async fn set_available() {
+        let queue = Queue::new();
+        queue.add(track_at("/music/album/track.mp3")).await;
+        queue.add(track_at("/music/other/track.mp3")).await;
+
+        assert_eq!(
+            1,
+            queue.set_available(Path::new("/music/album"), false).await
+        );
+        assert_eq!(
+            0,
+            queue.set_available(Path::new("/music/album"), false).await
+        );
+
+        let playlist = queue.get_playlist().await;
+        assert!(!playlist[0].is_available());
+        assert!(playlist[1].is_available());
+    }
+
+    #[test]
+    async fn relocate_file() {
+        let queue = Queue::new();
+        queue.add(track_at("/music/track.mp3")).await;
+        queue
+            .set_available(Path::new("/music/track.mp3"), false)
+            .await;
+
+        let moved = queue
+            .relocate(
+                Path::new("/music/track.mp3"),
+                Path::new("/music/renamed.mp3"),
+            )
+            .await;
+
+        assert_eq!(1, moved);
+        let track = &queue.get_playlist().await[0];
+        assert_eq!(Path::new("/music/renamed.mp3"), track.path());
+        assert!(track.is_available());
+    }
+
+    #[test]
+    async fn relocate_folder() {
+        let queue = Queue::new();
+        queue.add(track_at("/music/album/track.mp3")).await;
+        queue.add(track_at("/music/other/track.mp3")).await;
+
+        queue
+            .relocate(Path::new("/music/album"), Path::new("/music/renamed"))
+            .await;
+
+        let playlist = queue.get_playlist().await;
+        assert_eq!(Path::new("/music/renamed/track.mp3"), playlist[0].path());
+        assert_eq!(Path::new("/music/other/track.mp3"), playlist[1].path());
+    }
+
+    #[test]
+    async fn update_removed() {
+        let queue = Queue::new();
+        queue.add(track_at("/music/a.mp3")).await;
+
+        assert_eq!(0, queue.update(&track_at("/music/b.mp3"), |_| {}).await);
+    }
+
     #[test]
     async fn current_track() {
         let queue = Queue::new();
@@ -243,4 +725,313 @@ mod tests {
 
         assert_eq!(0, index);
     }
+
+    #[test]
+    async fn replay_current() {
+        let queue = Queue::new();
+        queue.add(Track::default()).await;
+        queue.add(Track::default()).await;
+
+        queue.next().await;
+        queue.next().await;
+        queue.replay_current();
+        queue.next().await;
+
+        assert_eq!(1, queue.current());
+    }
+
+    #[test]
+    async fn replay_current_shuffled() {
+        let queue = queue_of(10).await;
+        queue.set_shuffle(true, Some(42)).await;
+
+        queue.next().await;
+        let current = queue.current();
+        queue.replay_current();
+        queue.next().await;
+
+        assert_eq!(current, queue.current());
+    }
+
+    #[test]
+    async fn remove_before_current() {
+        let queue = queue_of(3).await;
+        queue.next().await;
+        queue.next().await;
+
+        queue.remove(0).await.unwrap();
+
+        assert_eq!(0, queue.current());
+        assert_eq!(2, queue.get_playlist().await.len());
+    }
+
+    #[test]
+    async fn remove_current() {
+        let queue = queue_of(3).await;
+        queue.next().await;
+        queue.next().await;
+
+        queue.remove(1).await.unwrap();
+        queue.next().await;
+
+        assert_eq!(1, queue.current());
+    }
+
+    #[test]
+    async fn remove_after_current() {
+        let queue = queue_of(3).await;
+        queue.next().await;
+
+        queue.remove(2).await.unwrap();
+
+        assert_eq!(0, queue.current());
+        assert_eq!(Some(1), queue.peek().await.map(|(index, _)| index));
+    }
+
+    #[test]
+    async fn remove_invalid_index() {
+        let queue = queue_of(1).await;
+
+        assert!(queue.remove(1).await.is_err());
+    }
+
+    #[test]
+    async fn remove_shuffled() {
+        let queue = queue_of(10).await;
+        queue.set_shuffle(true, Some(42)).await;
+        queue.next().await;
+        let current = queue.current();
+
+        queue.remove(9).await.unwrap();
+        let mut order = vec![queue.current()];
+        order.extend(play_order(&queue).await);
+
+        assert_eq!(if current == 9 { 8 } else { current }, order[0]);
+        order.sort();
+        order.dedup();
+        assert_eq!(9, order.len());
+    }
+
+    #[test]
+    async fn move_current_track() {
+        let queue = queue_of(3).await;
+        queue.next().await;
+
+        queue.move_track(0, 2).await.unwrap();
+
+        assert_eq!(2, queue.current());
+    }
+
+    #[test]
+    async fn move_track_over_current() {
+        let queue = queue_of(3).await;
+        queue.next().await;
+        queue.next().await;
+
+        queue.move_track(2, 0).await.unwrap();
+        assert_eq!(2, queue.current());
+
+        queue.move_track(0, 2).await.unwrap();
+        assert_eq!(1, queue.current());
+    }
+
+    #[test]
+    async fn move_track_invalid_index() {
+        let queue = queue_of(3).await;
+
+        assert!(queue.move_track(0, 3).await.is_err());
+        assert!(queue.move_track(3, 0).await.is_err());
+    }
+
+    #[test]
+    async fn move_track_shuffled() {
+        let queue = queue_of(10).await;
+        queue.set_shuffle(true, Some(42)).await;
+        queue.next().await;
+        let current = queue.current();
+
+        queue.move_track(current, 5).await.unwrap();
+
+        assert_eq!(5, queue.current());
+    }
+
+    #[test]
+    async fn insert_after_current() {
+        let queue = queue_of(3).await;
+        queue.next().await;
+
+        let index = queue.insert_after_current(Track::default()).await;
+        queue.next().await;
+
+        assert_eq!(1, index);
+        assert_eq!(1, queue.current());
+        assert_eq!(4, queue.get_playlist().await.len());
+    }
+
+    #[test]
+    async fn insert_after_current_not_started() {
+        let queue = queue_of(3).await;
+
+        let index = queue.insert_after_current(Track::default()).await;
+        queue.next().await;
+
+        assert_eq!(0, index);
+        assert_eq!(0, queue.current());
+    }
+
+    #[test]
+    async fn insert_after_current_shuffled() {
+        let queue = queue_of(10).await;
+        queue.set_shuffle(true, Some(42)).await;
+        queue.next().await;
+        let current = queue.current();
+
+        let index = queue.insert_after_current(Track::default()).await;
+        queue.next().await;
+
+        assert_eq!(current + 1, index);
+        assert_eq!(index, queue.current());
+    }
+
+    #[test]
+    async fn clear() {
+        let queue = queue_of(3).await;
+        queue.next().await;
+
+        queue.clear().await;
+
+        assert!(queue.get_playlist().await.is_empty());
+        assert_eq!(None, queue.next().await);
+    }
+
+    async fn queue_of(len: usize) -> Queue {
+        let queue = Queue::new();
+        for _ in 0..len {
+            queue.add(Track::default()).await;
+        }
+        queue
+    }
+
+    async fn play_order(queue: &Queue) -> Vec<usize> {
+        let mut order = Vec::new();
+        while queue.next().await.is_some() {
+            order.push(queue.current());
+        }
+        order
+    }
+
+    #[test]
+    async fn shuffle_same_seed_same_order() {
+        let first = queue_of(10).await;
+        let second = queue_of(10).await;
+
+        first.set_shuffle(true, Some(42)).await;
+        second.set_shuffle(true, Some(42)).await;
+
+        assert_eq!(play_order(&first).await, play_order(&second).await);
+    }
+
+    #[test]
+    async fn shuffle_plays_every_track_once() {
+        let queue = queue_of(10).await;
+
+        queue.set_shuffle(true, Some(42)).await;
+        let mut order = play_order(&queue).await;
+
+        assert_ne!((0..10).collect::<Vec<usize>>(), order);
+        order.sort();
+        assert_eq!((0..10).collect::<Vec<usize>>(), order);
+    }
+
+    #[test]
+    async fn shuffle_keeps_playlist_order() {
+        let queue = queue_of(10).await;
+        let expected = queue.get_playlist().await;
+
+        queue.set_shuffle(true, Some(42)).await;
+
+        assert_eq!(expected, queue.get_playlist().await);
+    }
+
+    #[test]
+    async fn shuffle_keeps_current_track() {
+        let queue = queue_of(10).await;
+        queue.next().await;
+        queue.next().await;
+
+        queue.set_shuffle(true, Some(42)).await;
+
+        assert!(queue.is_shuffle());
+        assert_eq!(1, queue.current());
+    }
+
+    #[test]
+    async fn shuffle_previous_walks_back_history() {
+        let queue = queue_of(10).await;
+        queue.set_shuffle(true, Some(42)).await;
+
+        queue.next().await;
+        let first = queue.current();
+        queue.next().await;
+        queue.change_to_previous().await;
+        queue.next().await;
+
+        assert_eq!(first, queue.current());
+    }
+
+    #[test]
+    async fn shuffle_change_current() {
+        let queue = queue_of(10).await;
+        queue.set_shuffle(true, Some(42)).await;
+        queue.next().await;
+        let first = queue.current();
+
+        queue.change_current(7).await.unwrap();
+        queue.next().await;
+        assert_eq!(7, queue.current());
+
+        queue.change_to_previous().await;
+        queue.next().await;
+        assert_eq!(first, queue.current());
+    }
+
+    #[test]
+    async fn shuffle_add() {
+        let queue = queue_of(5).await;
+        queue.set_shuffle(true, Some(42)).await;
+
+        queue.add(Track::default()).await;
+        let mut order = play_order(&queue).await;
+
+        order.sort();
+        assert_eq!((0..6).collect::<Vec<usize>>(), order);
+    }
+
+    #[test]
+    async fn shuffle_reshuffles_on_reset() {
+        let queue = queue_of(10).await;
+        queue.set_shuffle(true, Some(42)).await;
+
+        let first = play_order(&queue).await;
+        queue.reset();
+        let second = play_order(&queue).await;
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    async fn unshuffle_keeps_current_track() {
+        let queue = queue_of(10).await;
+        queue.set_shuffle(true, Some(42)).await;
+        queue.next().await;
+        queue.next().await;
+        let current = queue.current();
+
+        queue.set_shuffle(false, None).await;
+
+        assert!(!queue.is_shuffle());
+        assert_eq!(current, queue.current());
+        queue.next().await;
+        assert_eq!(current + 1, queue.current());
+    }
 }
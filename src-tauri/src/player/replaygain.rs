@@ -0,0 +1,283 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use symphonia::core::meta::{StandardTagKey, Tag};
+
+use super::{loudness::LoudnessMeter, probe};
+
+/// Loudness ReplayGain 2.0 normalizes to, in LUFS
+const REFERENCE_LOUDNESS: f64 = -18.0;
+/// Difference between the ReplayGain reference and the -23 LUFS R128 reference of Opus tags
+const R128_OFFSET: f32 = 5.0;
+
+/// Which of the ReplayGain values is applied during playback
+#[derive(Clone, Copy, Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct ReplayGainSettings {
+    pub mode: ReplayGainMode,
+    /// Additional gain in dB applied on top of the ReplayGain value
+    pub preamp: f32,
+}
+
+/// ReplayGain values of a track, gains are in dB and peaks are linear
+#[derive(Clone, Copy, Serialize, Default, Debug, PartialEq)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Reads ReplayGain values from ID3v2 TXXX frames, Vorbis comments and R128 tags
+    pub fn from_tags(tags: &[Tag]) -> Self {
+        let mut replay_gain = Self::default();
+
+        for tag in tags {
+            let value = tag.value.to_string();
+            match (tag.std_key, tag.key.to_ascii_uppercase().as_str()) {
+                (Some(StandardTagKey::ReplayGainTrackGain), _) | (_, "REPLAYGAIN_TRACK_GAIN") => {
+                    replay_gain.track_gain = parse_number(&value)
+                }
+                (Some(StandardTagKey::ReplayGainTrackPeak), _) | (_, "REPLAYGAIN_TRACK_PEAK") => {
+                    replay_gain.track_peak = parse_number(&value)
+                }
+                (Some(StandardTagKey::ReplayGainAlbumGain), _) | (_, "REPLAYGAIN_ALBUM_GAIN") => {
+                    replay_gain.album_gain = parse_number(&value)
+                }
+                (Some(StandardTagKey::ReplayGainAlbumPeak), _) | (_, "REPLAYGAIN_ALBUM_PEAK") => {
+                    replay_gain.album_peak = parse_number(&value)
+                }
+                (_, "R128_TRACK_GAIN") => {
+                    replay_gain.track_gain = replay_gain.track_gain.or(parse_r128(&value))
+                }
+                (_, "R128_ALBUM_GAIN") => {
+                    replay_gain.album_gain = replay_gain.album_gain.or(parse_r128(&value))
+                }
+                _ => {}
+            }
+        }
+
+        replay_gain
+    }
+
+    /// Reads ReplayGain values from the tags of the file
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut probed = probe::probe(path)?;
+        Ok(Self::from_tags(&probe::tags(&mut probed)))
+    }
+
+    /// Measures the loudness of the file and computes the track gain and peak
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The path to the audio file
+    pub fn analyze(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut probed = probe::probe(path.as_ref())?;
+        let mut meter: Option<LoudnessMeter> = None;
+
+        probe::decode(&mut probed, |spec, samples| {
+            meter
+                .get_or_insert_with(|| LoudnessMeter::new(spec.channels.count(), spec.rate))
+                .process(samples)
+        })?;
+
+        let meter = meter.ok_or(anyhow!("Nothing decoded"))?;
+        let loudness = meter
+            .integrated()
+            .ok_or(anyhow!("Track is too short or silent"))?;
+
+        Ok(Self {
+            track_gain: Some((REFERENCE_LOUDNESS - loudness) as f32),
+            track_peak: Some(meter.peak()),
+            ..Default::default()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.track_gain.is_none() && self.album_gain.is_none()
+    }
+
+    /// Returns the linear gain to apply to the track, lowered if needed so the peak does not clip.
+    /// Tracks without ReplayGain values are played unchanged.
+    pub fn factor(&self, settings: &ReplayGainSettings) -> f32 {
+        let (gain, peak) = match settings.mode {
+            ReplayGainMode::Off => return 1.0,
+            ReplayGainMode::Track => (
+                self.track_gain.or(self.album_gain),
+                self.track_peak.or(self.album_peak),
+            ),
+            ReplayGainMode::Album => (
+                self.album_gain.or(self.track_gain),
+                self.album_peak.or(self.track_peak),
+            ),
+        };
+
+        let Some(gain) = gain else {
+            return 1.0;
+        };
+
+        let factor = 10f32.powf((gain + settings.preamp) / 20.0);
+        match peak {
+            Some(peak) if peak > 0.0 && factor * peak > 1.0 => 1.0 / peak,
+            _ => factor,
+        }
+    }
+}
+
+/// Parses values like "-6.54 dB" or "0.988553"
+fn parse_number(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .trim_end_matches(|c: char| c.is_alphabetic())
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Parses a R128 gain, a Q7.8 fixed point number relative to -23 LUFS
+fn parse_r128(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .parse::<i16>()
+        .ok()
+        .map(|gain| gain as f32 / 256.0 + R128_OFFSET)
+}
+
+#[cfg(test)]
+mod tests {
+    use symphonia::core::meta::{StandardTagKey, Tag, Value};
+
+    use super::{ReplayGain, ReplayGainMode, ReplayGainSettings};
+
+    fn tag(std_key: Option<StandardTagKey>, key: &str, value: &str) -> Tag {
+        Tag::new(std_key, key, Value::String(value.to_owned()))
+    }
+
+    fn settings(mode: ReplayGainMode, preamp: f32) -> ReplayGainSettings {
+        ReplayGainSettings { mode, preamp }
+    }
+
+    #[test]
+    fn from_tags() {
+        let tags = vec![
+            tag(
+                Some(StandardTagKey::ReplayGainTrackGain),
+                "REPLAYGAIN_TRACK_GAIN",
+                "-6.54 dB",
+            ),
+            tag(None, "replaygain_track_peak", "0.988553"),
+            tag(None, "REPLAYGAIN_ALBUM_GAIN", "+1.00 dB"),
+            tag(
+                Some(StandardTagKey::ReplayGainAlbumPeak),
+                "REPLAYGAIN_ALBUM_PEAK",
+                "1.0",
+            ),
+        ];
+
+        let expected = ReplayGain {
+            track_gain: Some(-6.54),
+            track_peak: Some(0.988553),
+            album_gain: Some(1.0),
+            album_peak: Some(1.0),
+        };
+
+        assert_eq!(expected, ReplayGain::from_tags(&tags));
+    }
+
+    #[test]
+    fn from_tags_r128() {
+        let tags = vec![
+            tag(None, "R128_TRACK_GAIN", "-512"),
+            tag(None, "R128_ALBUM_GAIN", "256"),
+        ];
+
+        let actual = ReplayGain::from_tags(&tags);
+
+        assert_eq!(Some(3.0), actual.track_gain);
+        assert_eq!(Some(6.0), actual.album_gain);
+    }
+
+    #[test]
+    fn from_tags_invalid() {
+        let tags = vec![tag(None, "REPLAYGAIN_TRACK_GAIN", "loud")];
+
+        assert!(ReplayGain::from_tags(&tags).is_empty());
+    }
+
+    #[test]
+    fn factor_off() {
+        let replay_gain = ReplayGain {
+            track_gain: Some(-6.0),
+            ..Default::default()
+        };
+
+        assert_eq!(1.0, replay_gain.factor(&settings(ReplayGainMode::Off, 0.0)));
+    }
+
+    #[test]
+    fn factor_track() {
+        let replay_gain = ReplayGain {
+            track_gain: Some(-20.0),
+            album_gain: Some(0.0),
+            ..Default::default()
+        };
+
+        let actual = replay_gain.factor(&settings(ReplayGainMode::Track, 0.0));
+
+        assert!((actual - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn factor_album_falls_back_to_track() {
+        let replay_gain = ReplayGain {
+            track_gain: Some(-20.0),
+            ..Default::default()
+        };
+
+        let actual = replay_gain.factor(&settings(ReplayGainMode::Album, 0.0));
+
+        assert!((actual - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn factor_preamp() {
+        let replay_gain = ReplayGain {
+            track_gain: Some(-26.0),
+            ..Default::default()
+        };
+
+        let actual = replay_gain.factor(&settings(ReplayGainMode::Track, 6.0));
+
+        assert!((actual - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn factor_prevents_clipping() {
+        let replay_gain = ReplayGain {
+            track_gain: Some(6.0),
+            track_peak: Some(0.8),
+            ..Default::default()
+        };
+
+        let actual = replay_gain.factor(&settings(ReplayGainMode::Track, 0.0));
+
+        assert_eq!(1.25, actual);
+    }
+
+    #[test]
+    fn factor_untagged() {
+        let actual = ReplayGain::default().factor(&settings(ReplayGainMode::Album, 6.0));
+
+        assert_eq!(1.0, actual);
+    }
+}
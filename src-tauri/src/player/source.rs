@@ -0,0 +1,640 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Sample, Source};
+
+/// Source wrapper that signals once the inner source is exhausted or dropped,
+/// e.g. when the sink skips or stops it.
+pub struct Signalled<S> {
+    inner: S,
+    signal: Option<Sender<()>>,
+}
+
+impl<S> Signalled<S> {
+    /// Wraps the source and returns the receiver of the end signal
+    pub fn new(inner: S) -> (Self, Receiver<()>) {
+        let (tx, rx) = mpsc::channel();
+        let source = Self {
+            inner,
+            signal: Some(tx),
+        };
+
+        (source, rx)
+    }
+
+    fn send(&mut self) {
+        if let Some(signal) = self.signal.take() {
+            let _ = signal.send(());
+        }
+    }
+}
+
+impl<S> Iterator for Signalled<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<S::Item> {
+        let next = self.inner.next();
+        if next.is_none() {
+            self.send();
+        }
+        next
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S> Source for Signalled<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
+
+impl<S> Drop for Signalled<S> {
+    fn drop(&mut self) {
+        self.send();
+    }
+}
+
+/// Handle used to drop a `Queued` source from the sink before it starts playing
+#[derive(Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    /// Ends the source, the sink moves past it without playing a sample
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Source wrapper for a source waiting in the sink queue, it reports when the sink
+/// starts playing it and can be cancelled through its `CancelHandle` until then.
+pub struct Queued<S> {
+    inner: S,
+    cancel: CancelHandle,
+    on_start: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl<S> Queued<S> {
+    /// Wraps the source and returns the handle cancelling it
+    ///
+    /// # Arguments
+    ///
+    /// * 'inner' - The source to wrap
+    /// * 'on_start' - Called once the first sample is pulled
+    pub fn new(inner: S, on_start: impl FnOnce() + Send + 'static) -> (Self, CancelHandle) {
+        let cancel = CancelHandle::default();
+        let source = Self {
+            inner,
+            cancel: cancel.clone(),
+            on_start: Some(Box::new(on_start)),
+        };
+
+        (source, cancel)
+    }
+}
+
+impl<S> Iterator for Queued<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<S::Item> {
+        if self.cancel.is_cancelled() {
+            return None;
+        }
+        if let Some(on_start) = self.on_start.take() {
+            on_start();
+        }
+        self.inner.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S> Source for Queued<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
+
+/// Handle reading the position of a playing `Counted` source
+#[derive(Clone, Default)]
+pub struct PositionHandle(Arc<AtomicU64>);
+
+impl PositionHandle {
+    /// Returns the position of the last sample pulled by the output
+    pub fn get(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, position: Duration) {
+        self.0.store(position.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Source wrapper counting the samples pulled from the inner source, so its position
+/// follows what reaches the output, including pauses, seeks and underruns.
+pub struct Counted<S> {
+    inner: S,
+    /// Position of the last seek
+    offset: Duration,
+    /// Duration pulled since the offset with the formats before the current one
+    pulled: Duration,
+    /// Samples pulled with the current sample rate and channel count
+    samples: u64,
+    sample_rate: u32,
+    channels: u16,
+    /// How long the output holds the pulled samples before they are heard
+    latency: Duration,
+    handle: PositionHandle,
+}
+
+impl<S> Counted<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    /// Wraps the source and returns the handle reading its position
+    ///
+    /// # Arguments
+    ///
+    /// * 'inner' - The source to count the samples of
+    /// * 'latency' - Latency of the output, subtracted from the pulled samples
+    pub fn new(inner: S, latency: Duration) -> (Self, PositionHandle) {
+        let handle = PositionHandle::default();
+        let source = Self {
+            sample_rate: inner.sample_rate(),
+            channels: inner.channels(),
+            inner,
+            offset: Duration::ZERO,
+            pulled: Duration::ZERO,
+            samples: 0,
+            latency,
+            handle: handle.clone(),
+        };
+
+        (source, handle)
+    }
+
+    /// Returns the duration of the samples pulled with the current format
+    fn counted(&self) -> Duration {
+        let per_second = self.sample_rate as u128 * self.channels as u128;
+        if per_second == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = self.samples as u128 * 1_000_000_000 / per_second;
+        Duration::from_nanos(nanos as u64)
+    }
+
+    fn elapsed(&self) -> Duration {
+        //the position never goes back before the seek
+        self.offset + (self.pulled + self.counted()).saturating_sub(self.latency)
+    }
+
+    fn reset(&mut self, offset: Duration) {
+        self.offset = offset;
+        self.pulled = Duration::ZERO;
+        self.samples = 0;
+        self.handle.set(offset);
+    }
+}
+
+impl<S> Iterator for Counted<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<S::Item> {
+        //the decoder may change its format between frames
+        let (sample_rate, channels) = (self.inner.sample_rate(), self.inner.channels());
+        if (sample_rate, channels) != (self.sample_rate, self.channels) {
+            self.pulled += self.counted();
+            self.samples = 0;
+            self.sample_rate = sample_rate;
+            self.channels = channels;
+        }
+
+        let next = self.inner.next()?;
+        self.samples += 1;
+        self.handle.set(self.elapsed());
+        Some(next)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S> Source for Counted<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.reset(pos);
+        Ok(())
+    }
+}
+
+/// Handle used to request a fade out of a playing `Fader`
+#[derive(Clone, Default)]
+pub struct FadeHandle(Arc<AtomicU64>);
+
+impl FadeHandle {
+    /// Fades the source out over the given duration, after which it ends
+    pub fn fade_out(&self, duration: Duration) {
+        self.0
+            .store(duration.as_millis().max(1) as u64, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> Option<Duration> {
+        match self.0.swap(0, Ordering::Relaxed) {
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+}
+
+/// Linear gain ramp measured in samples
+struct Ramp {
+    position: u64,
+    length: u64,
+}
+
+impl Ramp {
+    fn new(length: u64) -> Self {
+        Self {
+            position: 0,
+            length: length.max(1),
+        }
+    }
+
+    fn progress(&self) -> f32 {
+        self.position as f32 / self.length as f32
+    }
+
+    fn is_done(&self) -> bool {
+        self.position >= self.length
+    }
+
+    fn advance(&mut self) {
+        self.position += 1;
+    }
+}
+
+/// Source wrapper applying a linear fade in at the start and a fade out
+/// requested through its `FadeHandle`.
+pub struct Fader<S> {
+    inner: S,
+    fade_in: Option<Ramp>,
+    fade_out: Option<Ramp>,
+    handle: FadeHandle,
+}
+
+impl<S> Fader<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    /// Wraps the source and returns the handle controlling its fade out
+    ///
+    /// # Arguments
+    ///
+    /// * 'inner' - The source to fade
+    /// * 'fade_in' - Duration of the fade in, `Duration::ZERO` to start at full volume
+    pub fn new(inner: S, fade_in: Duration) -> (Self, FadeHandle) {
+        let handle = FadeHandle::default();
+        let fade_in = (!fade_in.is_zero()).then(|| Ramp::new(samples(&inner, fade_in)));
+        let source = Self {
+            inner,
+            fade_in,
+            fade_out: None,
+            handle: handle.clone(),
+        };
+
+        (source, handle)
+    }
+}
+
+/// Number of samples the source produces over the given duration
+fn samples<S: Source>(source: &S, duration: Duration) -> u64
+where
+    S::Item: Sample,
+{
+    let per_second = source.sample_rate() as u64 * source.channels() as u64;
+    duration.as_millis() as u64 * per_second / 1000
+}
+
+impl<S> Iterator for Fader<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<S::Item> {
+        if self.fade_out.is_none() {
+            if let Some(duration) = self.handle.take() {
+                self.fade_out = Some(Ramp::new(samples(&self.inner, duration)));
+            }
+        }
+
+        let mut gain = 1.0;
+        if let Some(ramp) = &mut self.fade_in {
+            gain *= ramp.progress();
+            ramp.advance();
+            if ramp.is_done() {
+                self.fade_in = None;
+            }
+        }
+        if let Some(ramp) = &mut self.fade_out {
+            if ramp.is_done() {
+                return None;
+            }
+            gain *= 1.0 - ramp.progress();
+            ramp.advance();
+        }
+
+        self.inner.next().map(|sample| sample.amplify(gain))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S> Source for Fader<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rodio::{buffer::SamplesBuffer, Source};
+
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use super::{Counted, Fader, Queued, Signalled};
+
+    #[test]
+    fn signal_on_end() {
+        let (mut source, rx) = Signalled::new(SamplesBuffer::new(1, 1, vec![1i16, 2]));
+
+        source.next();
+        assert!(rx.try_recv().is_err());
+        source.next();
+        assert!(rx.try_recv().is_err());
+        source.next();
+
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn signal_on_drop() {
+        let (source, rx) = Signalled::new(SamplesBuffer::new(1, 1, vec![1i16, 2]));
+
+        drop(source);
+
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn queued_start() {
+        let started = Arc::new(AtomicBool::new(false));
+        let flag = started.clone();
+        let (mut source, _) = Queued::new(SamplesBuffer::new(1, 1, vec![1i16, 2]), move || {
+            flag.store(true, Ordering::Relaxed)
+        });
+
+        assert!(!started.load(Ordering::Relaxed));
+        assert_eq!(Some(1), source.next());
+        assert!(started.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn queued_cancel() {
+        let started = Arc::new(AtomicBool::new(false));
+        let flag = started.clone();
+        let (mut source, cancel) =
+            Queued::new(SamplesBuffer::new(1, 1, vec![1i16, 2]), move || {
+                flag.store(true, Ordering::Relaxed)
+            });
+
+        cancel.cancel();
+
+        assert_eq!(None, source.next());
+        assert!(!started.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn fade_in() {
+        let (source, _) = Fader::new(
+            SamplesBuffer::new(1, 1000, vec![1.0f32; 8]),
+            Duration::from_millis(4),
+        );
+
+        let actual: Vec<f32> = source.collect();
+
+        assert_eq!(vec![0.0, 0.25, 0.5, 0.75, 1.0, 1.0, 1.0, 1.0], actual);
+    }
+
+    #[test]
+    fn fade_out() {
+        let (mut source, handle) =
+            Fader::new(SamplesBuffer::new(1, 1000, vec![1.0f32; 8]), Duration::ZERO);
+
+        assert_eq!(Some(1.0), source.next());
+        handle.fade_out(Duration::from_millis(4));
+        let actual: Vec<f32> = source.collect();
+
+        assert_eq!(vec![1.0, 0.75, 0.5, 0.25], actual);
+    }
+
+    #[test]
+    fn count_position() {
+        let (mut source, position) =
+            Counted::new(SamplesBuffer::new(2, 1000, vec![0i16; 8]), Duration::ZERO);
+
+        source.next();
+        source.next();
+        assert_eq!(Duration::from_millis(1), position.get());
+        source.next();
+
+        assert_eq!(Duration::from_micros(1500), position.get());
+    }
+
+    #[test]
+    fn count_position_after_seek() {
+        let (mut source, position) = Counted::new(
+            SamplesBuffer::new(1, 1000, vec![0i16; 8000]),
+            Duration::ZERO,
+        );
+        source.next();
+
+        source.try_seek(Duration::from_secs(2)).unwrap();
+        assert_eq!(Duration::from_secs(2), position.get());
+        source.next();
+
+        assert_eq!(Duration::from_millis(2001), position.get());
+    }
+
+    #[test]
+    fn count_position_at_end() {
+        let (source, position) =
+            Counted::new(SamplesBuffer::new(1, 1000, vec![0i16; 8]), Duration::ZERO);
+
+        source.for_each(drop);
+
+        assert_eq!(Duration::from_millis(8), position.get());
+    }
+
+    #[test]
+    fn count_position_with_latency() {
+        let latency = Duration::from_millis(3);
+        let (mut source, position) =
+            Counted::new(SamplesBuffer::new(1, 1000, vec![0i16; 8000]), latency);
+
+        source.by_ref().take(2).for_each(drop);
+        assert_eq!(Duration::ZERO, position.get());
+        source.by_ref().take(3).for_each(drop);
+        assert_eq!(Duration::from_millis(2), position.get());
+
+        source.try_seek(Duration::from_secs(2)).unwrap();
+        source.next();
+
+        assert_eq!(Duration::from_secs(2), position.get());
+    }
+}
@@ -1,18 +1,41 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::{anyhow, bail};
-use audiotags::Id3v2Tag;
 use audiotags::Picture;
 use log::{debug, error, warn};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use symphonia::core::meta::StandardTagKey;
+
+use super::{probe, replaygain::ReplayGain};
 
 #[derive(Clone, Serialize, Default, Debug, PartialEq)]
 pub struct Track {
     title: String,
     artist: Option<String>,
     album: Option<String>,
+    album_artist: Option<String>,
+    track_number: Option<u16>,
+    disc_number: Option<u16>,
+    year: Option<i32>,
+    genre: Option<String>,
     path: PathBuf,
     length: Option<f64>,
+    replay_gain: ReplayGain,
+    /// Set once the file was deleted or moved away, the queue skips the track
+    unavailable: bool,
+    /// Part of the file the track plays, `None` for the whole file
+    span: Option<Span>,
+}
+
+/// Part of a file played as a track of its own, e.g. a track of a CUE sheet
+#[derive(Clone, Copy, Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct Span {
+    pub start: Duration,
+    /// `None` plays until the end of the file
+    pub end: Option<Duration>,
 }
 
 impl Track {
@@ -23,7 +46,7 @@ impl Track {
             bail!("File does not exists: {}", path_to_file.to_string_lossy())
         }
 
-        let track = match audiotags::Tag::new().read_from_path(&path_to_file) {
+        let mut track = match audiotags::Tag::new().read_from_path(&path_to_file) {
             Ok(tags) => {
                 let title = tags
                     .title()
@@ -36,58 +59,132 @@ impl Track {
                     )
                     .to_string();
 
-                let duration = if tags.to_any().downcast_ref::<Id3v2Tag>().is_some() {
-                    debug!("Id3v2Tag found, using mp3_duration for duration");
-                    mp3_duration::from_path(&path_to_file)
-                        .inspect_err(|err| error!("{}", err))
-                        .ok()
-                        .map(|d| d.as_secs_f64())
-                } else {
-                    tags.duration()
-                };
-
                 let track = Track {
                     title,
                     artist: tags.artist().map(|artist| artist.to_string()),
                     album: tags.album().map(|album| album.title.to_string()),
-                    length: duration,
+                    album_artist: tags.album_artist().map(|artist| artist.to_string()),
+                    track_number: tags.track_number(),
+                    disc_number: tags.disc_number(),
+                    year: tags.year(),
+                    genre: tags.genre().map(|genre| genre.to_string()),
                     path: path_to_file,
+                    ..Default::default()
                 };
 
                 Ok::<Track, anyhow::Error>(track)
             }
             Err(err) => {
-                error!(
-                    "Unable to read track metadata for {}: {}",
+                debug!(
+                    "Unable to read tags of {}, probing with symphonia: {}",
                     &path_to_file.display(),
                     err
                 );
-                let track = Track {
-                    title: path_to_file
-                        .file_stem()
-                        .unwrap_or_default()
-                        .to_str()
-                        .unwrap_or_default()
-                        .to_string(),
-                    path: path_to_file,
-                    ..Default::default()
-                };
+                let track = Self::read_probed(&path_to_file).unwrap_or_else(|err| {
+                    error!(
+                        "Unable to read track metadata for {}: {}",
+                        &path_to_file.display(),
+                        err
+                    );
+                    Track {
+                        title: path_to_file
+                            .file_stem()
+                            .unwrap_or_default()
+                            .to_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                        path: path_to_file.clone(),
+                        ..Default::default()
+                    }
+                });
 
                 Ok(track)
             }
         }?;
 
-        if track.length.is_none() {
-            warn!("Unable to read track length for {}", &track.title);
-        }
+        track.length = probe::duration(&track.path)
+            .inspect_err(|err| warn!("Unable to read track length for {}: {}", &track.title, err))
+            .ok()
+            .map(|duration| duration.as_secs_f64());
+
+        track.replay_gain = ReplayGain::read(&track.path)
+            .inspect_err(|err| debug!("Unable to read ReplayGain tags: {}", err))
+            .unwrap_or_default();
 
         Ok(track)
     }
 
+    /// Reads the metadata with symphonia, for formats audiotags does not support
+    fn read_probed(path: &Path) -> anyhow::Result<Self> {
+        let mut probed = probe::probe(path)?;
+        let tags = probe::tags(&mut probed);
+        let tag = |key: StandardTagKey| {
+            tags.iter()
+                .find(|tag| tag.std_key == Some(key))
+                //RIFF INFO values keep their null terminator
+                .map(|tag| tag.value.to_string().trim_end_matches('\0').to_string())
+        };
+
+        Ok(Track {
+            title: tag(StandardTagKey::TrackTitle).unwrap_or_else(|| {
+                path.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            }),
+            artist: tag(StandardTagKey::Artist),
+            album: tag(StandardTagKey::Album),
+            album_artist: tag(StandardTagKey::AlbumArtist),
+            track_number: tag(StandardTagKey::TrackNumber).and_then(|number| parse_number(&number)),
+            disc_number: tag(StandardTagKey::DiscNumber).and_then(|number| parse_number(&number)),
+            year: tag(StandardTagKey::Date).and_then(|date| parse_year(&date)),
+            genre: tag(StandardTagKey::Genre),
+            path: path.to_path_buf(),
+            ..Default::default()
+        })
+    }
+
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
 
+    /// Points the track to the new location of its file, e.g. after it was renamed
+    pub fn set_path(&mut self, path: impl Into<PathBuf>) {
+        self.path = path.into();
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn artist(&self) -> Option<&str> {
+        self.artist.as_deref()
+    }
+
+    pub fn album(&self) -> Option<&str> {
+        self.album.as_deref()
+    }
+
+    pub fn album_artist(&self) -> Option<&str> {
+        self.album_artist.as_deref()
+    }
+
+    pub fn track_number(&self) -> Option<u16> {
+        self.track_number
+    }
+
+    pub fn disc_number(&self) -> Option<u16> {
+        self.disc_number
+    }
+
+    pub fn year(&self) -> Option<i32> {
+        self.year
+    }
+
+    pub fn genre(&self) -> Option<&str> {
+        self.genre.as_deref()
+    }
+
     pub fn album_cover(&self) -> anyhow::Result<AlbumCover> {
         audiotags::Tag::new()
             .read_from_path(self.path())?
@@ -96,10 +193,83 @@ impl Track {
             .map(AlbumCover::from)
     }
 
-    #[allow(dead_code)]
     pub fn length(&self) -> Option<f64> {
         self.length
     }
+
+    pub fn replay_gain(&self) -> &ReplayGain {
+        &self.replay_gain
+    }
+
+    pub fn set_replay_gain(&mut self, replay_gain: ReplayGain) {
+        self.replay_gain = replay_gain;
+    }
+
+    pub fn is_available(&self) -> bool {
+        !self.unavailable
+    }
+
+    pub fn set_available(&mut self, available: bool) {
+        self.unavailable = !available;
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Narrows the track down to a part of its file, the tags of the file describe
+    /// the whole file so the title and artist of the part replace them
+    ///
+    /// # Arguments
+    ///
+    /// * 'span' - The part of the file to play
+    /// * 'title' - Title of the part
+    /// * 'artist' - Artist of the part
+    pub fn set_span(&mut self, span: Span, title: Option<&str>, artist: Option<&str>) {
+        let end = span.end.map(|end| end.as_secs_f64()).or(self.length);
+        self.length = end.map(|end| (end - span.start.as_secs_f64()).max(0.0));
+        if let Some(title) = title {
+            self.title = title.to_string();
+        }
+        if let Some(artist) = artist {
+            self.artist = Some(artist.to_string());
+        }
+        self.span = Some(span);
+    }
+
+    /// Fills in the metadata the file has no tags for, e.g. from the playlist listing it
+    ///
+    /// # Arguments
+    ///
+    /// * 'title' - Title to use if the file has no title tag
+    /// * 'artist' - Artist to use if the file has no artist tag
+    /// * 'length' - Length in seconds to use if the file could not be probed
+    pub fn fill_missing(&mut self, title: Option<&str>, artist: Option<&str>, length: Option<f64>) {
+        //without a title tag the title is the file name
+        let untitled = self
+            .path
+            .file_stem()
+            .is_some_and(|stem| stem.to_string_lossy() == self.title);
+        if let Some(title) = title.filter(|_| untitled) {
+            self.title = title.to_string();
+        }
+        if self.artist.is_none() {
+            self.artist = artist.map(str::to_string);
+        }
+        if self.length.is_none() {
+            self.length = length;
+        }
+    }
+}
+
+/// Parses a track or disc number, which may be followed by the total, e.g. "3/12"
+fn parse_number(value: &str) -> Option<u16> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+/// Parses the year of a date, e.g. "2004" or "2004-12-13"
+fn parse_year(value: &str) -> Option<i32> {
+    value.trim().get(..4)?.parse().ok()
 }
 
 #[derive(Serialize)]
@@ -113,63 +283,66 @@ impl From<Picture<'_>> for AlbumCover {
 
 #[cfg(test)]
 mod tests {
-    use std::{fs::File, path::PathBuf, str::FromStr};
+    use std::{
+        fs::{self, File},
+        path::{Path, PathBuf},
+        str::FromStr,
+        time::Duration,
+    };
 
     use audiotags::{Album, AudioTagEdit, AudioTagWrite, MimeType, Picture};
     use temp_dir::TempDir;
 
-    use super::Track;
+    use super::{parse_number, parse_year, Span, Track};
 
-    #[test]
-    fn try_new_track_mp3() {
+    /// Copies the asset and tags it with every field the track reads
+    fn assert_tagged(asset: &str, length: f64) {
         let dir = TempDir::new().unwrap();
-        let file_path = dir.path().join("track.mp3");
-        let mut file = File::create(&file_path).unwrap();
+        let file_path = dir.path().join(Path::new(asset).file_name().unwrap());
+        fs::copy(asset, &file_path).unwrap();
 
         let expected = Track {
             title: "title".to_owned(),
             artist: Some("artist".to_owned()),
             album: Some("album".to_owned()),
+            album_artist: Some("album artist".to_owned()),
+            track_number: Some(3),
+            disc_number: Some(2),
+            year: Some(1999),
+            genre: Some("genre".to_owned()),
             path: file_path.clone(),
-            //Duration is ignored
-            length: Some(0.0),
+            length: Some(length),
+            ..Default::default()
         };
 
-        let mut tags = audiotags::Id3v2Tag::new();
+        let mut tags = audiotags::Tag::new().read_from_path(&file_path).unwrap();
         tags.set_title(&expected.title);
         tags.set_artist(&expected.artist.clone().unwrap());
         tags.set_album(Album::with_title(&expected.album.clone().unwrap()));
-        tags.write_to(&mut file).unwrap();
-
-        let actual = Track::try_new(&file_path).unwrap();
+        tags.set_album_artist(&expected.album_artist.clone().unwrap());
+        tags.set_track_number(3);
+        tags.set_disc_number(2);
+        tags.set_year(1999);
+        tags.set_genre(&expected.genre.clone().unwrap());
+        tags.write_to_path(file_path.to_str().unwrap()).unwrap();
+
+        let track = Track::try_new(&file_path).unwrap();
+        let actual = Track {
+            length: track.length.map(f64::round),
+            ..track
+        };
 
         assert_eq!(expected, actual);
     }
 
     #[test]
-    fn try_new_track_flac() {
-        let dir = TempDir::new().unwrap();
-        let file_path = dir.path().join("track.flac");
-        let mut file = File::create(&file_path).unwrap();
-
-        let expected = Track {
-            title: "title".to_owned(),
-            artist: Some("artist".to_owned()),
-            album: Some("album".to_owned()),
-            path: file_path.clone(),
-            //Duration is ignored
-            length: None,
-        };
-
-        let mut tags = audiotags::FlacTag::new();
-        tags.set_title(&expected.title);
-        tags.set_artist(&expected.artist.clone().unwrap());
-        tags.set_album(Album::with_title(&expected.album.clone().unwrap()));
-        tags.write_to(&mut file).unwrap();
-
-        let actual = Track::try_new(&file_path).unwrap();
+    fn try_new_track_mp3() {
+        assert_tagged("tests/assets/track.mp3", 4.0);
+    }
 
-        assert_eq!(expected, actual);
+    #[test]
+    fn try_new_track_flac() {
+        assert_tagged("tests/assets/track.flac", 6.0);
     }
 
     #[test]
@@ -183,7 +356,8 @@ mod tests {
             artist: None,
             album: None,
             path: file_path.clone(),
-            length: Some(0.0),
+            length: None,
+            ..Default::default()
         };
 
         let mut tags = audiotags::Id3v2Tag::new();
@@ -206,6 +380,7 @@ mod tests {
             album: None,
             path: file_path.clone(),
             length: None,
+            ..Default::default()
         };
 
         let mut tags = audiotags::FlacTag::new();
@@ -228,6 +403,7 @@ mod tests {
             album: None,
             path: file_path.clone(),
             length: None,
+            ..Default::default()
         };
 
         let actual = Track::try_new(&file_path).unwrap();
@@ -235,6 +411,119 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn fill_missing() {
+        let mut untagged = Track {
+            title: "track".to_owned(),
+            path: PathBuf::from("/music/track.mp3"),
+            ..Default::default()
+        };
+        let mut tagged = Track {
+            title: "title".to_owned(),
+            artist: Some("artist".to_owned()),
+            path: PathBuf::from("/music/track.mp3"),
+            length: Some(10.0),
+            ..Default::default()
+        };
+
+        untagged.fill_missing(Some("Title"), Some("Artist"), Some(61.0));
+        tagged.fill_missing(Some("Title"), Some("Artist"), Some(61.0));
+
+        assert_eq!("Title", untagged.title);
+        assert_eq!(Some("Artist".to_owned()), untagged.artist);
+        assert_eq!(Some(61.0), untagged.length);
+        assert_eq!("title", tagged.title);
+        assert_eq!(Some("artist".to_owned()), tagged.artist);
+        assert_eq!(Some(10.0), tagged.length);
+    }
+
+    #[test]
+    fn set_span() {
+        let mut track = Track {
+            title: "album".to_owned(),
+            artist: Some("artist".to_owned()),
+            path: PathBuf::from("/music/album.flac"),
+            length: Some(300.0),
+            ..Default::default()
+        };
+        let mut last = track.clone();
+
+        track.set_span(
+            Span {
+                start: Duration::from_secs(60),
+                end: Some(Duration::from_secs(150)),
+            },
+            Some("Title"),
+            None,
+        );
+        last.set_span(
+            Span {
+                start: Duration::from_secs(150),
+                end: None,
+            },
+            None,
+            Some("Guest"),
+        );
+
+        assert_eq!("Title", track.title);
+        assert_eq!(Some("artist".to_owned()), track.artist);
+        assert_eq!(Some(90.0), track.length);
+        assert_eq!(Some(150.0), last.length);
+        assert_eq!(Some("Guest".to_owned()), last.artist);
+    }
+
+    fn assert_tags(path: &str) {
+        let expected = Track {
+            title: "title".to_owned(),
+            artist: Some("artist".to_owned()),
+            album: Some("album".to_owned()),
+            path: PathBuf::from(path),
+            //Duration is ignored
+            length: None,
+            ..Default::default()
+        };
+
+        let actual = Track {
+            length: None,
+            ..Track::try_new(path).unwrap()
+        };
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn try_new_track_wav() {
+        assert_tags("tests/assets/track.wav");
+    }
+
+    #[test]
+    fn try_new_track_ogg() {
+        assert_tags("tests/assets/track.ogg");
+    }
+
+    #[test]
+    fn try_new_track_opus() {
+        assert_tags("tests/assets/track.opus");
+    }
+
+    #[test]
+    fn try_new_track_m4a() {
+        assert_tags("tests/assets/track.m4a");
+    }
+
+    #[test]
+    fn try_new_track_alac() {
+        assert_tags("tests/assets/track_alac.m4a");
+    }
+
+    #[test]
+    fn try_new_track_aac_without_tags() {
+        let actual = Track::try_new("tests/assets/track.aac").unwrap();
+
+        assert_eq!("track", actual.title);
+        assert_eq!(None, actual.artist);
+    }
+
     #[test]
     fn try_new_track_file_not_found() {
         assert!(Track::try_new(PathBuf::from_str("track.mp3").unwrap()).is_err());
@@ -271,4 +560,18 @@ mod tests {
 
         assert!(actual.album_cover().is_err());
     }
+
+    #[test]
+    fn parse_track_number() {
+        assert_eq!(Some(3), parse_number("3"));
+        assert_eq!(Some(3), parse_number("03/12"));
+        assert_eq!(None, parse_number(""));
+    }
+
+    #[test]
+    fn parse_date_year() {
+        assert_eq!(Some(2004), parse_year("2004"));
+        assert_eq!(Some(2004), parse_year("2004-12-13"));
+        assert_eq!(None, parse_year("04"));
+    }
 }
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+/// Range the logarithmic curve spans between the lowest level above zero and full volume
+const LOGARITHMIC_RANGE_DB: f32 = 60.0;
+
+/// How the level of the volume control maps to the gain of the samples
+#[derive(Clone, Copy, Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeCurve {
+    #[default]
+    Linear,
+    /// Every step of the level changes the loudness by the same number of dB, which is
+    /// closer to how loudness is perceived
+    Logarithmic,
+}
+
+impl VolumeCurve {
+    /// Returns the gain of the samples at the level
+    ///
+    /// # Arguments
+    ///
+    /// * 'level' - Level of the volume control, between 0 and 1
+    pub fn gain(self, level: f32) -> f32 {
+        let level = level.clamp(0.0, 1.0);
+        match self {
+            VolumeCurve::Linear => level,
+            VolumeCurve::Logarithmic if level == 0.0 => 0.0,
+            VolumeCurve::Logarithmic => 10f32.powf((level - 1.0) * LOGARITHMIC_RANGE_DB / 20.0),
+        }
+    }
+}
+
+/// Volume of the player, the level is kept while muted so unmuting restores it
+#[derive(Clone, Copy, Serialize, Debug, PartialEq)]
+pub struct Volume {
+    /// Level of the volume control, between 0 and 1
+    pub level: f32,
+    pub muted: bool,
+    pub curve: VolumeCurve,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            level: 1.0,
+            muted: false,
+            curve: VolumeCurve::default(),
+        }
+    }
+}
+
+impl Volume {
+    /// Returns the gain the sinks play at
+    pub fn gain(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.curve.gain(self.level)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Volume, VolumeCurve};
+
+    #[test]
+    fn linear_gain() {
+        assert_eq!(0.0, VolumeCurve::Linear.gain(0.0));
+        assert_eq!(0.5, VolumeCurve::Linear.gain(0.5));
+        assert_eq!(1.0, VolumeCurve::Linear.gain(1.5));
+    }
+
+    #[test]
+    fn logarithmic_gain() {
+        let curve = VolumeCurve::Logarithmic;
+
+        assert_eq!(0.0, curve.gain(0.0));
+        assert_eq!(1.0, curve.gain(1.0));
+        //half the level is 30 dB below full volume
+        assert!((curve.gain(0.5) - 0.031_622_776).abs() < 1e-6);
+        assert!(curve.gain(0.25) < curve.gain(0.5));
+    }
+
+    #[test]
+    fn muted_gain() {
+        let volume = Volume {
+            level: 0.8,
+            muted: true,
+            curve: VolumeCurve::Linear,
+        };
+
+        assert_eq!(0.0, volume.gain());
+        assert_eq!(
+            0.8,
+            Volume {
+                muted: false,
+                ..volume
+            }
+            .gain()
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use log::warn;
+
+use super::{resolve, Entry};
+use crate::player::track::Span;
+
+/// Frames per second of the `mm:ss:ff` timestamps
+const FRAMES_PER_SECOND: u64 = 75;
+
+/// Track of the sheet while it is parsed
+struct CueTrack {
+    file: PathBuf,
+    title: Option<String>,
+    performer: Option<String>,
+    start: Option<Duration>,
+}
+
+/// Parses a CUE sheet, every `INDEX 01` starts a track that ends where the next one
+/// in the same file starts
+///
+/// # Arguments
+///
+/// * 'content' - The decoded sheet
+/// * 'base' - The folder of the sheet, relative file names are resolved against it
+pub fn parse(content: &str, base: &Path) -> Vec<Entry> {
+    let mut file: Option<PathBuf> = None;
+    //the performer of the sheet is the one of the tracks without their own
+    let mut performer: Option<String> = None;
+    let mut tracks: Vec<CueTrack> = Vec::new();
+    //data tracks of mixed mode discs are not played
+    let mut in_audio_track = false;
+
+    for line in content.lines().map(str::trim) {
+        let (command, arguments) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let arguments = arguments.trim();
+
+        match command.to_uppercase().as_str() {
+            "FILE" => {
+                file = resolve(&unquote(arguments), base);
+                in_audio_track = false;
+            }
+            "TRACK" => {
+                in_audio_track = arguments.to_uppercase().ends_with("AUDIO");
+                if let (true, Some(file)) = (in_audio_track, &file) {
+                    tracks.push(CueTrack {
+                        file: file.clone(),
+                        title: None,
+                        performer: None,
+                        start: None,
+                    });
+                }
+            }
+            "TITLE" if in_audio_track => {
+                if let Some(track) = tracks.last_mut() {
+                    track.title = Some(unquote(arguments));
+                }
+            }
+            "PERFORMER" => {
+                let value = Some(unquote(arguments));
+                if tracks.is_empty() {
+                    performer = value;
+                } else if let (true, Some(track)) = (in_audio_track, tracks.last_mut()) {
+                    track.performer = value;
+                }
+            }
+            "INDEX" if in_audio_track => {
+                let Some(("01", time)) = arguments.split_once(char::is_whitespace) else {
+                    continue;
+                };
+                match (parse_time(time.trim()), tracks.last_mut()) {
+                    (Some(start), Some(track)) => track.start = Some(start),
+                    _ => warn!("Invalid index: {}", line),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let tracks: Vec<CueTrack> = tracks
+        .into_iter()
+        .filter(|track| track.start.is_some())
+        .collect();
+
+    tracks
+        .iter()
+        .enumerate()
+        .map(|(index, track)| {
+            let start = track.start.unwrap_or_default();
+            let end = tracks
+                .get(index + 1)
+                .filter(|next| next.file == track.file)
+                .and_then(|next| next.start);
+
+            Entry {
+                path: track.file.clone(),
+                title: track.title.clone(),
+                artist: track.performer.clone().or(performer.clone()),
+                length: end.map(|end| end.saturating_sub(start).as_secs_f64()),
+                span: Some(Span { start, end }),
+            }
+        })
+        .collect()
+}
+
+/// Removes the quotes around a value, unquoted values end at the first whitespace
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default().to_string(),
+        None => value
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Parses a `mm:ss:ff` timestamp, the minutes may exceed an hour
+fn parse_time(time: &str) -> Option<Duration> {
+    let mut parts = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (Some(Some(minutes)), Some(Some(seconds)), Some(Some(frames)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+
+    Some(
+        Duration::from_secs(minutes * 60 + seconds)
+            + Duration::from_secs(frames) / FRAMES_PER_SECOND as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use super::{parse, parse_time, unquote};
+
+    #[test]
+    fn time() {
+        assert_eq!(Some(Duration::from_secs(63)), parse_time("01:03:00"));
+        assert_eq!(Some(Duration::from_millis(6000)), parse_time("00:05:75"));
+        assert_eq!(Some(Duration::from_secs(4200)), parse_time("70:00:00"));
+        assert_eq!(None, parse_time("01:03"));
+    }
+
+    #[test]
+    fn unquoted() {
+        assert_eq!("My Album.flac", unquote("\"My Album.flac\" WAVE"));
+        assert_eq!("album.flac", unquote("album.flac WAVE"));
+    }
+
+    #[test]
+    fn parse_sheet() {
+        let content = "REM GENRE Rock\n\
+            PERFORMER \"Band\"\n\
+            TITLE \"Album\"\n\
+            FILE \"album.flac\" WAVE\n\
+            \x20 TRACK 01 AUDIO\n\
+            \x20   TITLE \"Intro\"\n\
+            \x20   INDEX 01 00:00:00\n\
+            \x20 TRACK 02 AUDIO\n\
+            \x20   TITLE \"Song\"\n\
+            \x20   PERFORMER \"Band feat. Guest\"\n\
+            \x20   INDEX 00 01:29:00\n\
+            \x20   INDEX 01 01:30:00\n\
+            \x20 TRACK 03 AUDIO\n\
+            \x20   TITLE \"Outro\"\n\
+            \x20   INDEX 01 04:00:37\n\
+            FILE \"bonus.flac\" WAVE\n\
+            \x20 TRACK 04 AUDIO\n\
+            \x20   INDEX 01 00:00:00\n";
+
+        let entries = parse(content, Path::new("/music"));
+
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                let span = entry.span.unwrap();
+                (
+                    entry.path.to_string_lossy().to_string(),
+                    entry.title.as_deref(),
+                    entry.artist.as_deref(),
+                    span.start,
+                    span.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "/music/album.flac".to_string(),
+                    Some("Intro"),
+                    Some("Band"),
+                    Duration::ZERO,
+                    Some(Duration::from_secs(90))
+                ),
+                (
+                    "/music/album.flac".to_string(),
+                    Some("Song"),
+                    Some("Band feat. Guest"),
+                    Duration::from_secs(90),
+                    Some(Duration::from_secs(240) + Duration::from_secs(37) / 75)
+                ),
+                (
+                    "/music/album.flac".to_string(),
+                    Some("Outro"),
+                    Some("Band"),
+                    Duration::from_secs(240) + Duration::from_secs(37) / 75,
+                    None
+                ),
+                (
+                    "/music/bonus.flac".to_string(),
+                    None,
+                    Some("Band"),
+                    Duration::ZERO,
+                    None
+                ),
+            ],
+            summary
+        );
+        assert_eq!(Some(90.0), entries[0].length);
+        assert_eq!(None, entries[2].length);
+    }
+
+    #[test]
+    fn parse_skips_data_tracks() {
+        let content = "FILE \"disc.bin\" BINARY\n\
+            TRACK 01 MODE1/2352\n\
+            INDEX 01 00:00:00\n\
+            TRACK 02 AUDIO\n\
+            TITLE \"Audio\"\n\
+            INDEX 01 10:00:00\n";
+
+        let entries = parse(content, Path::new("/music"));
+
+        assert_eq!(1, entries.len());
+        assert_eq!(Some("Audio".to_string()), entries[0].title);
+    }
+}
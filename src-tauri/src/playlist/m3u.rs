@@ -0,0 +1,206 @@
+use std::path::Path;
+
+use super::{locate, resolve, split_title, Entry};
+
+const HEADER: &str = "#EXTM3U";
+const INFO: &str = "#EXTINF:";
+/// Artist of the displayed title, only written when splitting the title would get it wrong.
+/// Other players skip it like any unknown directive.
+const ARTIST: &str = "#EXTARTIST:";
+
+/// Parses an M3U playlist, the `#EXTINF` line before a path gives its length and title
+///
+/// # Arguments
+///
+/// * 'content' - The decoded playlist
+/// * 'base' - The folder of the playlist file, relative paths are resolved against it
+pub fn parse(content: &str, base: &Path) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut info: Option<(Option<f64>, String)> = None;
+    let mut stated_artist = None;
+
+    for line in content.lines().map(str::trim) {
+        if let Some(extinf) = line.strip_prefix(INFO) {
+            info = Some(parse_info(extinf));
+        } else if let Some(artist) = line.strip_prefix(ARTIST) {
+            stated_artist = Some(artist);
+        } else if line.starts_with('#') || line.is_empty() {
+            continue;
+        } else {
+            let (length, display) = info.take().unwrap_or_default();
+            let (artist, title) = split_title(&display, stated_artist.take());
+            if let Some(path) = resolve(line, base) {
+                entries.push(Entry {
+                    path,
+                    title,
+                    artist,
+                    length,
+                    span: None,
+                });
+            }
+        }
+    }
+
+    entries
+}
+
+/// Parses the length and displayed title of `#EXTINF:<length> [attributes],<title>`
+fn parse_info(extinf: &str) -> (Option<f64>, String) {
+    let (attributes, title) = extinf.split_once(',').unwrap_or((extinf, ""));
+    let length = attributes
+        .split_whitespace()
+        .next()
+        .and_then(|length| length.parse::<f64>().ok())
+        //-1 stands for an unknown length
+        .filter(|length| *length >= 0.0);
+
+    (length, title.to_string())
+}
+
+/// Formats the entries as an extended M3U playlist
+///
+/// # Arguments
+///
+/// * 'entries' - The tracks to list
+/// * 'base' - The folder of the playlist file if the paths should be relative to it
+pub fn format(entries: &[Entry], base: Option<&Path>) -> String {
+    let mut content = format!("{}\n", HEADER);
+
+    for entry in entries {
+        let length = entry.length.map_or(-1, |length| length.round() as i64);
+        content.push_str(&format!(
+            "{}{},{}\n",
+            INFO,
+            length,
+            entry.display_title().unwrap_or_default()
+        ));
+        if let Some(artist) = entry.stated_artist() {
+            content.push_str(&format!("{}{}\n", ARTIST, artist));
+        }
+        content.push_str(&format!(
+            "{}\n",
+            locate(&entry.path, base).to_string_lossy()
+        ));
+    }
+
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{format, parse, Entry};
+
+    #[test]
+    fn parse_extended() {
+        let content = "#EXTM3U\n\
+            #EXTINF:123,Artist - Title\n\
+            album/track.mp3\n\
+            \n\
+            #EXTINF:-1 tvg-id=\"id\",Unknown\n\
+            /other/track.flac\n\
+            #EXTINF:5,Radio\n\
+            http://radio/stream\n";
+
+        let entries = parse(content, Path::new("/music"));
+
+        assert_eq!(
+            vec![
+                Entry {
+                    path: PathBuf::from("/music/album/track.mp3"),
+                    title: Some("Title".to_string()),
+                    artist: Some("Artist".to_string()),
+                    length: Some(123.0),
+                    span: None,
+                },
+                Entry {
+                    path: PathBuf::from("/other/track.flac"),
+                    title: Some("Unknown".to_string()),
+                    artist: None,
+                    length: None,
+                    span: None,
+                },
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn parse_simple() {
+        let content = "track.mp3\r\n# comment\r\n..\\album\\track.flac\r\n";
+
+        let entries = parse(content, Path::new("/music/playlists"));
+
+        assert_eq!(
+            vec![
+                PathBuf::from("/music/playlists/track.mp3"),
+                PathBuf::from("/music/album/track.flac")
+            ],
+            entries
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn format_absolute() {
+        let entries = vec![
+            Entry {
+                path: PathBuf::from("/music/track.mp3"),
+                title: Some("Title".to_string()),
+                artist: Some("Artist".to_string()),
+                length: Some(61.4),
+                span: None,
+            },
+            Entry {
+                path: PathBuf::from("/music/other.mp3"),
+                ..Default::default()
+            },
+        ];
+
+        let content = format(&entries, None);
+
+        assert_eq!(
+            "#EXTM3U\n\
+            #EXTINF:61,Artist - Title\n\
+            /music/track.mp3\n\
+            #EXTINF:-1,\n\
+            /music/other.mp3\n",
+            content
+        );
+    }
+
+    #[test]
+    fn format_stated_artist() {
+        let entries = vec![Entry {
+            path: PathBuf::from("/music/track.mp3"),
+            title: Some("Song - Live".to_string()),
+            ..Default::default()
+        }];
+
+        let content = format(&entries, None);
+
+        assert_eq!(
+            "#EXTM3U\n\
+            #EXTINF:-1,Song - Live\n\
+            #EXTARTIST:\n\
+            /music/track.mp3\n",
+            content
+        );
+        assert_eq!(entries, parse(&content, Path::new("/music")));
+    }
+
+    #[test]
+    fn format_relative() {
+        let entries = vec![Entry {
+            path: PathBuf::from("/music/album/track.mp3"),
+            ..Default::default()
+        }];
+
+        let content = format(&entries, Some(Path::new("/music/playlists")));
+
+        assert!(content.ends_with("\n../album/track.mp3\n"));
+    }
+}
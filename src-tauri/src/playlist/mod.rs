@@ -0,0 +1,505 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use encoding_rs::WINDOWS_1252;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::player::track::{Span, Track};
+
+mod cue;
+mod m3u;
+mod pls;
+mod xspf;
+
+/// Extensions of the playlist formats that can be imported
+pub const EXTENSIONS: [&str; 5] = ["m3u", "m3u8", "pls", "xspf", "cue"];
+
+/// Track listed in a playlist file
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    /// Length in seconds
+    pub length: Option<f64>,
+    /// Part of the file the track plays, set by CUE sheets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+impl Entry {
+    /// Opens the track, the playlist fills in the metadata its file has no tags for
+    pub fn open(&self) -> anyhow::Result<Track> {
+        let mut track = Track::try_new(&self.path)?;
+        match self.span {
+            Some(span) => track.set_span(span, self.title.as_deref(), self.artist.as_deref()),
+            None => track.fill_missing(self.title.as_deref(), self.artist.as_deref(), self.length),
+        }
+
+        Ok(track)
+    }
+
+    /// Returns the title to display, "Artist - Title" if the artist is known
+    fn display_title(&self) -> Option<String> {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+            (None, title) => title.clone(),
+            (artist, None) => artist.clone(),
+        }
+    }
+
+    /// Returns the artist to state next to the displayed title in formats without an artist
+    /// field, empty for no artist. `None` if splitting the displayed title gives it back.
+    fn stated_artist(&self) -> Option<&str> {
+        let display = self.display_title()?;
+        if split_title(&display, None) == (self.artist.clone(), self.title.clone()) {
+            return None;
+        }
+
+        Some(self.artist.as_deref().unwrap_or_default())
+    }
+}
+
+impl From<&Track> for Entry {
+    fn from(track: &Track) -> Self {
+        Self {
+            path: track.path().clone(),
+            title: Some(track.title().to_string()),
+            artist: track.artist().map(str::to_string),
+            length: track.length(),
+            span: track.span(),
+        }
+    }
+}
+
+/// Splits a displayed "Artist - Title" into the artist and the title
+///
+/// # Arguments
+///
+/// * 'display' - The displayed title
+/// * 'artist' - The artist the playlist states, empty for none. If `None` the title is split
+///   at the first " - ", which is only a guess for titles containing it.
+fn split_title(display: &str, artist: Option<&str>) -> (Option<String>, Option<String>) {
+    let display = display.trim();
+    let non_empty = |text: &str| (!text.is_empty()).then(|| text.to_string());
+    match artist {
+        None => match display.split_once(" - ") {
+            Some((artist, title)) => (Some(artist.to_string()), Some(title.to_string())),
+            None => (None, non_empty(display)),
+        },
+        Some("") => (None, non_empty(display)),
+        Some(artist) => {
+            //only the artist is displayed if the title is missing
+            let title = match display.strip_prefix(artist) {
+                Some(rest) if rest.is_empty() => rest,
+                Some(rest) => rest.strip_prefix(" - ").unwrap_or(display),
+                None => display,
+            };
+            (Some(artist.to_string()), non_empty(title))
+        }
+    }
+}
+
+/// Reads the tracks of the playlist file, the format is picked by its extension
+///
+/// # Arguments
+///
+/// * 'path' - The path to the playlist file
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Vec<Entry>> {
+    let path = path.as_ref();
+    let content = decode(&fs::read(path)?);
+    let base = path
+        .parent()
+        .ok_or(anyhow!("Invalid playlist path: {}", path.display()))?;
+
+    let entries = match extension(path).as_str() {
+        "m3u" | "m3u8" => m3u::parse(&content, base),
+        "pls" => pls::parse(&content, base),
+        "xspf" => xspf::parse(&content, base)?,
+        "cue" => cue::parse(&content, base),
+        extension => bail!("Unsupported playlist format: {}", extension),
+    };
+    info!("Read {} tracks from {}", entries.len(), path.display());
+
+    Ok(entries)
+}
+
+/// Returns whether the file is a playlist that can be imported, judging by its extension
+pub fn is_playlist(path: impl AsRef<Path>) -> bool {
+    EXTENSIONS.contains(&extension(path.as_ref()).as_str())
+}
+
+/// Writes the tracks to the playlist file, the format is picked by its extension
+///
+/// # Arguments
+///
+/// * 'path' - The path to the playlist file
+/// * 'entries' - The tracks to write
+/// * 'relative' - Whether the paths are written relative to the playlist file
+pub fn write(path: impl AsRef<Path>, entries: &[Entry], relative: bool) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let base = if relative {
+        Some(
+            path.parent()
+                .ok_or(anyhow!("Invalid playlist path: {}", path.display()))?,
+        )
+    } else {
+        None
+    };
+
+    let content = match extension(path).as_str() {
+        "m3u" | "m3u8" => m3u::format(entries, base),
+        "pls" => pls::format(entries, base),
+        "xspf" => xspf::format(entries, base),
+        extension => bail!("Unsupported playlist format: {}", extension),
+    };
+    fs::write(path, content)?;
+    info!("Wrote {} tracks to {}", entries.len(), path.display());
+
+    Ok(())
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Decodes the file as UTF-8, falling back to Windows-1252 which older players wrote
+fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+
+    match std::str::from_utf8(bytes) {
+        Ok(content) => content.to_string(),
+        Err(_) => WINDOWS_1252
+            .decode_without_bom_handling(bytes)
+            .0
+            .into_owned(),
+    }
+}
+
+/// Resolves a location listed in a playlist to a file path, `None` for streams
+///
+/// # Arguments
+///
+/// * 'location' - A path, absolute or relative to the playlist, or a file URL
+/// * 'base' - The folder of the playlist file
+fn resolve(location: &str, base: &Path) -> Option<PathBuf> {
+    let location = location.trim();
+    if location.is_empty() {
+        return None;
+    }
+
+    if let Some((scheme, rest)) = location.split_once("://") {
+        if scheme.eq_ignore_ascii_case("file") {
+            //the host of a local file URL is empty or localhost
+            let path = rest.strip_prefix("localhost").unwrap_or(rest);
+            return Some(PathBuf::from(percent_decode(path)));
+        }
+        if scheme.chars().all(|char| char.is_ascii_alphabetic()) {
+            warn!("Skipping stream {}", location);
+            return None;
+        }
+    }
+
+    //playlists written on Windows separate the folders with backslashes
+    let location = if cfg!(windows) {
+        location.to_string()
+    } else {
+        location.replace('\\', "/")
+    };
+
+    Some(normalize(&base.join(location)))
+}
+
+/// Removes the `.` and `..` components without following symlinks
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Escapes the characters of a path that are not allowed in a URI, the separators are kept
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+/// Returns the location to write for the track
+///
+/// # Arguments
+///
+/// * 'path' - The path to the track
+/// * 'base' - The folder of the playlist file if the path should be relative to it
+fn locate(path: &Path, base: Option<&Path>) -> PathBuf {
+    base.and_then(|base| relative_path(path, base))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Returns the path relative to the base folder, `None` if they do not share a root
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path
+        .iter()
+        .zip(&base)
+        .take_while(|(path, base)| path == base)
+        .count();
+
+    //paths on different drives have nothing in common
+    if common == 0 {
+        return None;
+    }
+
+    let parents = base[common..].iter().map(|_| Component::ParentDir);
+    Some(parents.chain(path[common..].iter().copied()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn decode_utf8() {
+        assert_eq!("Motörhead", decode("\u{feff}Motörhead".as_bytes()));
+    }
+
+    #[test]
+    fn decode_windows_1252() {
+        assert_eq!("Motörhead – Ace", decode(b"Mot\xF6rhead \x96 Ace"));
+    }
+
+    #[test]
+    fn playlist_extensions() {
+        assert!(is_playlist("/music/mix.M3U8"));
+        assert!(is_playlist("/music/album.cue"));
+        assert!(!is_playlist("/music/track.flac"));
+        assert!(!is_playlist("/music/album"));
+    }
+
+    #[test]
+    fn resolve_relative() {
+        let base = Path::new("/music/playlists");
+
+        assert_eq!(
+            Some(PathBuf::from("/music/album/track.mp3")),
+            resolve("../album/track.mp3", base)
+        );
+        assert_eq!(
+            Some(PathBuf::from("/music/album/track.mp3")),
+            resolve("/music/album/track.mp3", base)
+        );
+    }
+
+    #[test]
+    fn resolve_file_url() {
+        assert_eq!(
+            Some(PathBuf::from("/music/my track.mp3")),
+            resolve("file:///music/my%20track.mp3", Path::new("/"))
+        );
+    }
+
+    #[test]
+    fn resolve_stream() {
+        assert_eq!(None, resolve("http://radio/stream", Path::new("/")));
+    }
+
+    #[test]
+    fn percent_round_trip() {
+        let path = "/music/Motörhead/#1 100%.mp3";
+
+        assert_eq!(
+            "/music/Mot%C3%B6rhead/%231%20100%25.mp3",
+            percent_encode(path)
+        );
+        assert_eq!(path, percent_decode(&percent_encode(path)));
+    }
+
+    #[test]
+    fn relative() {
+        assert_eq!(
+            Some(PathBuf::from("../album/track.mp3")),
+            relative_path(
+                Path::new("/music/album/track.mp3"),
+                Path::new("/music/playlists")
+            )
+        );
+        assert_eq!(
+            Some(PathBuf::from("track.mp3")),
+            relative_path(Path::new("/music/track.mp3"), Path::new("/music"))
+        );
+    }
+
+    #[test]
+    fn split_display_title() {
+        assert_eq!(
+            (Some("Artist".to_string()), Some("Title - Live".to_string())),
+            split_title("Artist - Title - Live", None)
+        );
+        assert_eq!(
+            (None, Some("Title".to_string())),
+            split_title("Title", None)
+        );
+        assert_eq!((None, None), split_title(" ", None));
+    }
+
+    #[test]
+    fn split_display_title_stated_artist() {
+        assert_eq!(
+            (None, Some("Title - Live".to_string())),
+            split_title("Title - Live", Some(""))
+        );
+        assert_eq!(
+            (Some("Artist - Band".to_string()), Some("Title".to_string())),
+            split_title("Artist - Band - Title", Some("Artist - Band"))
+        );
+        assert_eq!(
+            (Some("Artist".to_string()), None),
+            split_title("Artist", Some("Artist"))
+        );
+    }
+
+    #[test]
+    fn write_and_read() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("playlist.m3u8");
+        let entries = vec![Entry {
+            path: dir.path().join("album").join("track.mp3"),
+            title: Some("Title".to_string()),
+            artist: Some("Artist".to_string()),
+            length: Some(61.0),
+            span: None,
+        }];
+
+        write(&path, &entries, true).unwrap();
+
+        assert_eq!(entries, read(&path).unwrap());
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("\nalbum/track.mp3\n"));
+    }
+
+    #[test]
+    fn write_absolute() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("playlist.m3u8");
+        let track = dir.path().join("album").join("track.mp3");
+        let entries = vec![Entry {
+            path: track.clone(),
+            ..Default::default()
+        }];
+
+        write(&path, &entries, false).unwrap();
+
+        assert_eq!(entries, read(&path).unwrap());
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains(&format!("\n{}\n", track.display())));
+    }
+
+    #[test]
+    fn write_and_read_formats() {
+        let dir = TempDir::new().unwrap();
+        let entries = vec![
+            Entry {
+                path: dir.path().join("album").join("track 1.mp3"),
+                title: Some("Title".to_string()),
+                artist: Some("Artist".to_string()),
+                length: Some(61.0),
+                span: None,
+            },
+            Entry {
+                path: PathBuf::from("/other/track.flac"),
+                ..Default::default()
+            },
+            //the displayed titles of these have a " - " that does not separate the artist
+            Entry {
+                path: dir.path().join("untagged.mp3"),
+                title: Some("Song - Live".to_string()),
+                ..Default::default()
+            },
+            Entry {
+                path: dir.path().join("track 2.mp3"),
+                title: Some("Title".to_string()),
+                artist: Some("Artist - Band, \"The\"".to_string()),
+                ..Default::default()
+            },
+            Entry {
+                path: dir.path().join("track 3.mp3"),
+                artist: Some("Artist".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        for extension in ["m3u", "m3u8", "pls", "xspf"] {
+            let path = dir.path().join(format!("playlist.{}", extension));
+            for relative in [false, true] {
+                write(&path, &entries, relative).unwrap();
+
+                assert_eq!(entries, read(&path).unwrap(), "{}", extension);
+            }
+        }
+    }
+
+    #[test]
+    fn read_unsupported() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("playlist.txt");
+        fs::write(&path, "track.mp3").unwrap();
+
+        assert!(read(&path).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::BTreeMap, path::Path};
+
+use super::{locate, resolve, split_title, Entry};
+
+const HEADER: &str = "[playlist]";
+
+/// Values of the keys numbered after a track
+#[derive(Default)]
+struct Keys<'a> {
+    file: Option<&'a str>,
+    title: Option<&'a str>,
+    artist: Option<&'a str>,
+    length: Option<f64>,
+}
+
+/// Parses a PLS playlist, the `FileN`, `TitleN` and `LengthN` keys describe the N-th track.
+/// `ArtistN` is not part of the format, it is only written when splitting the title would
+/// get the artist wrong.
+///
+/// # Arguments
+///
+/// * 'content' - The decoded playlist
+/// * 'base' - The folder of the playlist file, relative paths are resolved against it
+pub fn parse(content: &str, base: &Path) -> Vec<Entry> {
+    //the keys of a track may come in any order
+    let mut tracks: BTreeMap<usize, Keys> = BTreeMap::new();
+
+    for line in content.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        let Some((name, index)) = key
+            .find(|char: char| char.is_ascii_digit())
+            .map(|position| key.split_at(position))
+        else {
+            continue;
+        };
+        let Ok(index) = index.parse() else {
+            continue;
+        };
+
+        let track = tracks.entry(index).or_default();
+        match name {
+            "file" => track.file = Some(value),
+            "title" => track.title = Some(value),
+            "artist" => track.artist = Some(value),
+            //-1 stands for an unknown length
+            "length" => track.length = value.parse().ok().filter(|length| *length >= 0.0),
+            _ => {}
+        }
+    }
+
+    tracks
+        .into_values()
+        .filter_map(|track| {
+            let path = resolve(track.file?, base)?;
+            let (artist, title) = split_title(track.title.unwrap_or_default(), track.artist);
+
+            Some(Entry {
+                path,
+                title,
+                artist,
+                length: track.length,
+                span: None,
+            })
+        })
+        .collect()
+}
+
+/// Formats the entries as a PLS playlist
+///
+/// # Arguments
+///
+/// * 'entries' - The tracks to list
+/// * 'base' - The folder of the playlist file if the paths should be relative to it
+pub fn format(entries: &[Entry], base: Option<&Path>) -> String {
+    let mut content = format!("{}\n", HEADER);
+
+    for (index, entry) in entries.iter().enumerate() {
+        let number = index + 1;
+        content.push_str(&format!(
+            "File{}={}\n",
+            number,
+            locate(&entry.path, base).to_string_lossy()
+        ));
+        if let Some(title) = entry.display_title() {
+            content.push_str(&format!("Title{}={}\n", number, title));
+        }
+        if let Some(artist) = entry.stated_artist() {
+            content.push_str(&format!("Artist{}={}\n", number, artist));
+        }
+        let length = entry.length.map_or(-1, |length| length.round() as i64);
+        content.push_str(&format!("Length{}={}\n", number, length));
+    }
+
+    content.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{format, parse, Entry};
+
+    #[test]
+    fn parse_unordered() {
+        let content = "[playlist]\n\
+            Title2=Second\n\
+            File2=/music/second.mp3\n\
+            File1=album/first.mp3\n\
+            Title1=Artist - First\n\
+            Length1=95\n\
+            Length2=-1\n\
+            NumberOfEntries=2\n\
+            Version=2\n";
+
+        let entries = parse(content, Path::new("/music"));
+
+        assert_eq!(
+            vec![
+                Entry {
+                    path: PathBuf::from("/music/album/first.mp3"),
+                    title: Some("First".to_string()),
+                    artist: Some("Artist".to_string()),
+                    length: Some(95.0),
+                    span: None,
+                },
+                Entry {
+                    path: PathBuf::from("/music/second.mp3"),
+                    title: Some("Second".to_string()),
+                    artist: None,
+                    length: None,
+                    span: None,
+                },
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn parse_without_file() {
+        let content = "[playlist]\nTitle1=Title\nFile2=track.mp3\n";
+
+        let entries = parse(content, Path::new("/music"));
+
+        assert_eq!(1, entries.len());
+        assert_eq!(PathBuf::from("/music/track.mp3"), entries[0].path);
+    }
+
+    #[test]
+    fn round_trip() {
+        let entries = vec![
+            Entry {
+                path: PathBuf::from("/music/album/track.mp3"),
+                title: Some("Title".to_string()),
+                artist: Some("Artist".to_string()),
+                length: Some(120.0),
+                span: None,
+            },
+            Entry {
+                path: PathBuf::from("/other/track.flac"),
+                ..Default::default()
+            },
+        ];
+
+        let base = Path::new("/music/playlists");
+        let content = format(&entries, Some(base));
+
+        assert!(content.contains("File1=../album/track.mp3\n"));
+        assert_eq!(entries, parse(&content, base));
+    }
+}
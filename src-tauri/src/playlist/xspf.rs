@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use quick_xml::{escape::escape, events::Event, Reader};
+
+use super::{locate, percent_decode, percent_encode, resolve, Entry};
+
+/// Parses an XSPF playlist, the title, creator and duration of a track are read along
+/// with its location
+///
+/// # Arguments
+///
+/// * 'content' - The decoded playlist
+/// * 'base' - The folder of the playlist file, relative locations are resolved against it
+pub fn parse(content: &str, base: &Path) -> anyhow::Result<Vec<Entry>> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut entries = Vec::new();
+    //names of the elements the reader is in, without namespace prefixes
+    let mut elements: Vec<String> = Vec::new();
+    let mut location: Option<String> = None;
+    let mut entry = Entry::default();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                if name == "track" {
+                    location = None;
+                    entry = Entry::default();
+                }
+                elements.push(name);
+            }
+            Event::End(_) => {
+                let ended = elements.pop();
+                if ended.as_deref() == Some("track") {
+                    let path = location
+                        .take()
+                        .and_then(|location| resolve_location(&location, base));
+                    if let Some(path) = path {
+                        entries.push(Entry {
+                            path,
+                            ..std::mem::take(&mut entry)
+                        });
+                    }
+                }
+            }
+            Event::Text(text) => {
+                //only the fields right inside a track, not the ones of its extensions
+                if elements.len() < 2 || elements[elements.len() - 2] != "track" {
+                    continue;
+                }
+                let text = text.unescape()?.into_owned();
+                match elements.last().map(String::as_str) {
+                    //a track may list several locations, the first one is played
+                    Some("location") if location.is_none() => location = Some(text),
+                    Some("title") => entry.title = Some(text),
+                    Some("creator") => entry.artist = Some(text),
+                    Some("duration") => {
+                        entry.length = text
+                            .parse::<u64>()
+                            .ok()
+                            .map(|millis| millis as f64 / 1000.0)
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Resolves the URI of a track, relative URIs are percent-encoded like absolute ones
+fn resolve_location(location: &str, base: &Path) -> Option<PathBuf> {
+    if location.contains("://") {
+        resolve(location, base)
+    } else {
+        resolve(&percent_decode(location), base)
+    }
+}
+
+/// Formats the entries as an XSPF playlist
+///
+/// # Arguments
+///
+/// * 'entries' - The tracks to list
+/// * 'base' - The folder of the playlist file if the locations should be relative to it
+pub fn format(entries: &[Entry], base: Option<&Path>) -> String {
+    let mut content = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <trackList>\n",
+    );
+
+    for entry in entries {
+        let path = locate(&entry.path, base);
+        let location = if path.is_absolute() {
+            format!("file://{}", percent_encode(&path.to_string_lossy()))
+        } else {
+            percent_encode(&path.to_string_lossy())
+        };
+
+        content.push_str("    <track>\n");
+        content.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape(&location)
+        ));
+        if let Some(title) = &entry.title {
+            content.push_str(&format!("      <title>{}</title>\n", escape(title)));
+        }
+        if let Some(artist) = &entry.artist {
+            content.push_str(&format!("      <creator>{}</creator>\n", escape(artist)));
+        }
+        if let Some(length) = entry.length {
+            content.push_str(&format!(
+                "      <duration>{}</duration>\n",
+                (length * 1000.0).round() as u64
+            ));
+        }
+        content.push_str("    </track>\n");
+    }
+
+    content.push_str("  </trackList>\n</playlist>\n");
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{format, parse, Entry};
+
+    #[test]
+    fn parse_tracks() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <title>Playlist</title>
+              <trackList>
+                <track>
+                  <location>file:///music/my%20track.mp3</location>
+                  <location>file:///music/other.mp3</location>
+                  <title>Rock &amp; Roll</title>
+                  <creator>Artist</creator>
+                  <duration>61500</duration>
+                  <extension application="http://example.com">
+                    <title>Ignored</title>
+                  </extension>
+                </track>
+                <track>
+                  <location>album/track%201.flac</location>
+                </track>
+                <track>
+                  <title>Without location</title>
+                </track>
+              </trackList>
+            </playlist>"#;
+
+        let entries = parse(content, Path::new("/music")).unwrap();
+
+        assert_eq!(
+            vec![
+                Entry {
+                    path: PathBuf::from("/music/my track.mp3"),
+                    title: Some("Rock & Roll".to_string()),
+                    artist: Some("Artist".to_string()),
+                    length: Some(61.5),
+                    span: None,
+                },
+                Entry {
+                    path: PathBuf::from("/music/album/track 1.flac"),
+                    ..Default::default()
+                },
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse("<playlist><trackList></playlist>", Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let entries = vec![
+            Entry {
+                path: PathBuf::from("/music/album/track #1.mp3"),
+                title: Some("<Title>".to_string()),
+                artist: Some("Artist & Band".to_string()),
+                length: Some(120.25),
+                span: None,
+            },
+            Entry {
+                path: PathBuf::from("/other/track.flac"),
+                ..Default::default()
+            },
+        ];
+
+        for base in [None, Some(Path::new("/music/playlists"))] {
+            let content = format(&entries, base);
+
+            assert_eq!(
+                entries,
+                parse(&content, Path::new("/music/playlists")).unwrap()
+            );
+        }
+    }
+}
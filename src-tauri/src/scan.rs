@@ -0,0 +1,384 @@
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::Chars,
+};
+
+use log::{debug, warn};
+
+/// Extensions of the formats symphonia can decode
+pub const EXTENSIONS: [&str; 8] = ["aac", "flac", "m4a", "mp3", "oga", "ogg", "opus", "wav"];
+
+/// Limits of a folder scan
+#[derive(Clone, Copy, Debug)]
+pub struct ScanOptions {
+    /// How many levels of subfolders are entered, 0 only reads the folder itself
+    pub max_depth: usize,
+    /// Whether files and folders starting with a dot are included
+    pub hidden: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 8,
+            hidden: false,
+        }
+    }
+}
+
+/// Audio file found by the scan along with its position in the album
+struct Entry {
+    path: PathBuf,
+    disc: Option<u16>,
+    track: Option<u16>,
+}
+
+/// Scans the folder and its subfolders for audio files with a supported extension.
+/// Files are sorted by folder, then by disc and track number, and by their name if not tagged.
+///
+/// # Arguments
+///
+/// * 'path' - The path to the folder
+/// * 'options' - Depth limit and hidden file policy of the scan
+pub fn scan(path: impl AsRef<Path>, options: &ScanOptions) -> anyhow::Result<Vec<PathBuf>> {
+    let mut entries: Vec<Entry> = list(path, options)?
+        .into_iter()
+        .map(|path| {
+            let (disc, track) = position(&path);
+            Entry { path, disc, track }
+        })
+        .collect();
+    entries.sort_by(compare);
+
+    Ok(entries.into_iter().map(|entry| entry.path).collect())
+}
+
+/// Lists the audio files in the folder and its subfolders in no particular order
+///
+/// # Arguments
+///
+/// * 'path' - The path to the folder
+/// * 'options' - Depth limit and hidden file policy of the scan
+pub fn list(path: impl AsRef<Path>, options: &ScanOptions) -> anyhow::Result<Vec<PathBuf>> {
+    let mut visited = HashSet::new();
+    let mut paths = Vec::new();
+    visit(path.as_ref(), 0, options, &mut visited, &mut paths)?;
+
+    Ok(paths)
+}
+
+fn visit(
+    dir: &Path,
+    depth: usize,
+    options: &ScanOptions,
+    visited: &mut HashSet<PathBuf>,
+    paths: &mut Vec<PathBuf>,
+) -> anyhow::Result<()> {
+    //symlinks may point back to a parent folder
+    if !visited.insert(dir.canonicalize()?) {
+        debug!("Skipping already visited folder {}", dir.display());
+        return Ok(());
+    }
+
+    for entry in dir.read_dir()?.filter_map(Result::ok) {
+        let path = entry.path();
+        if !options.hidden && is_hidden(&path) {
+            continue;
+        }
+
+        if path.is_dir() {
+            if depth < options.max_depth {
+                if let Err(err) = visit(&path, depth + 1, options, visited, paths) {
+                    warn!("Unable to scan {}: {}", path.display(), err);
+                }
+            }
+        } else if has_supported_extension(&path) {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether a scan of the root would list the file, without reading the folders
+///
+/// # Arguments
+///
+/// * 'root' - The path to the scanned folder
+/// * 'path' - The path to the file
+/// * 'options' - Depth limit and hidden file policy of the scan
+pub fn is_listed(root: &Path, path: &Path, options: &ScanOptions) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+    let depth = relative.components().count().saturating_sub(1);
+    let hidden = relative
+        .components()
+        .any(|component| is_hidden(Path::new(component.as_os_str())));
+
+    depth <= options.max_depth && (options.hidden || !hidden) && has_supported_extension(path)
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+fn has_supported_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Reads the disc and track number tags of the file
+fn position(path: &Path) -> (Option<u16>, Option<u16>) {
+    match audiotags::Tag::new().read_from_path(path) {
+        Ok(tags) => (tags.disc_number(), tags.track_number()),
+        Err(_) => (None, None),
+    }
+}
+
+fn compare(a: &Entry, b: &Entry) -> Ordering {
+    compare_paths(
+        a.path.parent().unwrap_or(Path::new("")),
+        b.path.parent().unwrap_or(Path::new("")),
+    )
+    //untagged tracks go after the tagged ones
+    .then(a.track.is_none().cmp(&b.track.is_none()))
+    .then(a.disc.unwrap_or(1).cmp(&b.disc.unwrap_or(1)))
+    .then(a.track.cmp(&b.track))
+    .then_with(|| compare_paths(&a.path, &b.path))
+}
+
+/// Compares the paths component by component in natural order
+fn compare_paths(a: &Path, b: &Path) -> Ordering {
+    let mut a = a.iter().map(|component| component.to_string_lossy());
+    let mut b = b.iter().map(|component| component.to_string_lossy());
+
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match natural_cmp(&x, &y) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            },
+        }
+    }
+}
+
+/// Compares the strings treating runs of digits as numbers, so "2" goes before "10"
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                //leading zeros only matter if the numbers are equal
+                let ordering = x
+                    .trim_start_matches('0')
+                    .len()
+                    .cmp(&y.trim_start_matches('0').len())
+                    .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0')))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        number.push(digit);
+    }
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cmp::Ordering,
+        fs::{self, create_dir_all, File},
+        path::Path,
+    };
+
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    fn touch(dir: &Path, path: &str) -> PathBuf {
+        let path = dir.join(path);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(&path).unwrap();
+        path
+    }
+
+    fn tagged(dir: &Path, path: &str, disc: u16, track: u16) -> PathBuf {
+        let path = dir.join(path);
+        fs::copy("tests/assets/track.mp3", &path).unwrap();
+        let mut tags = audiotags::Tag::new().read_from_path(&path).unwrap();
+        tags.set_disc_number(disc);
+        tags.set_track_number(track);
+        tags.write_to_path(path.to_str().unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn scan_recursive() {
+        let dir = TempDir::new().unwrap();
+        let expected = vec![
+            touch(dir.path(), "track.mp3"),
+            touch(dir.path(), "Artist/track.mp3"),
+            touch(dir.path(), "Artist/Album/disc 2/01.mp3"),
+            touch(dir.path(), "Artist/Album/disc 10/01.flac"),
+        ];
+        touch(dir.path(), "Artist/cover.jpg");
+
+        let actual = scan(dir.path(), &ScanOptions::default()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn scan_natural_order() {
+        let dir = TempDir::new().unwrap();
+        let expected = vec![
+            touch(dir.path(), "2 track.mp3"),
+            touch(dir.path(), "10 track.mp3"),
+            touch(dir.path(), "Track 3.mp3"),
+            touch(dir.path(), "track 20.mp3"),
+        ];
+
+        let actual = scan(dir.path(), &ScanOptions::default()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn scan_sorted_by_tags() {
+        let dir = TempDir::new().unwrap();
+        let expected = vec![
+            tagged(dir.path(), "c.mp3", 1, 2),
+            tagged(dir.path(), "b.mp3", 2, 1),
+            tagged(dir.path(), "a.mp3", 2, 3),
+            touch(dir.path(), "untagged.mp3"),
+        ];
+
+        let actual = scan(dir.path(), &ScanOptions::default()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn scan_depth_limit() {
+        let dir = TempDir::new().unwrap();
+        let expected = vec![
+            touch(dir.path(), "track.mp3"),
+            touch(dir.path(), "a/track.mp3"),
+        ];
+        touch(dir.path(), "a/b/track.mp3");
+        let options = ScanOptions {
+            max_depth: 1,
+            ..Default::default()
+        };
+
+        let actual = scan(dir.path(), &options).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn scan_hidden() {
+        let dir = TempDir::new().unwrap();
+        let visible = touch(dir.path(), "track.mp3");
+        let hidden_file = touch(dir.path(), ".track.mp3");
+        let hidden_dir = touch(dir.path(), ".hidden/track.mp3");
+        let options = ScanOptions {
+            hidden: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            vec![visible.clone()],
+            scan(dir.path(), &ScanOptions::default()).unwrap()
+        );
+        assert_eq!(
+            vec![hidden_file, visible, hidden_dir],
+            scan(dir.path(), &options).unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_symlink_loop() {
+        let dir = TempDir::new().unwrap();
+        let expected = vec![touch(dir.path(), "a/track.mp3")];
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("a/loop")).unwrap();
+
+        let actual = scan(dir.path(), &ScanOptions::default()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn scan_uppercase_extension() {
+        let dir = TempDir::new().unwrap();
+        let expected = vec![touch(dir.path(), "track.MP3")];
+
+        let actual = scan(dir.path(), &ScanOptions::default()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn scan_invalid() {
+        assert!(scan("invalid", &ScanOptions::default()).is_err());
+    }
+
+    #[test]
+    fn listed() {
+        let options = ScanOptions {
+            max_depth: 1,
+            hidden: false,
+        };
+        let listed = |path: &str| is_listed(Path::new("/music"), Path::new(path), &options);
+
+        assert!(listed("/music/track.mp3"));
+        assert!(listed("/music/album/track.mp3"));
+        assert!(!listed("/music/a/b/track.mp3"));
+        assert!(!listed("/music/.album/track.mp3"));
+        assert!(!listed("/music/cover.jpg"));
+        assert!(!listed("/other/track.mp3"));
+    }
+
+    #[test]
+    fn natural_order() {
+        assert_eq!(Ordering::Less, natural_cmp("track 2", "track 10"));
+        assert_eq!(Ordering::Greater, natural_cmp("b", "A"));
+        assert_eq!(Ordering::Equal, natural_cmp("Track", "track"));
+        assert_eq!(Ordering::Less, natural_cmp("track", "track 1"));
+        assert_eq!(Ordering::Less, natural_cmp("1", "01"));
+    }
+}
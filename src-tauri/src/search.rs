@@ -0,0 +1,440 @@
+use std::{cmp::Reverse, collections::HashMap, mem, sync::Mutex};
+
+use serde::Serialize;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::{
+    library::{browse::LibraryTrack, Library},
+    player::Player,
+};
+
+/// Number of results if the frontend does not ask for another limit
+pub const SEARCH_LIMIT: usize = 50;
+/// Weights of the title, artist and album when a word of the query matches them
+const FIELD_WEIGHTS: [u32; 3] = [3, 2, 1];
+
+/// How well a word of the query matches a word of a track
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WordMatch {
+    /// The word is misspelled
+    Typo = 1,
+    /// The word is being typed
+    Prefix = 3,
+    Exact = 4,
+}
+
+/// Words of the titles, artists and albums the last search looked at, so typing a query
+/// does not split every track again. Keyed by the text, edited tags are split anew.
+#[derive(Default)]
+pub struct SearchCache {
+    words: Mutex<HashMap<String, Vec<String>>>,
+}
+
+/// Words of the fields a search looked at, taken from the previous search where it had them
+struct Words {
+    previous: HashMap<String, Vec<String>>,
+    current: HashMap<String, Vec<String>>,
+}
+
+impl Words {
+    fn load(&mut self, text: &str) {
+        if self.current.contains_key(text) {
+            return;
+        }
+        let (text, words) = self
+            .previous
+            .remove_entry(text)
+            .unwrap_or_else(|| (text.to_string(), words(text)));
+        self.current.insert(text, words);
+    }
+
+    fn get(&self, text: &str) -> &[String] {
+        &self.current[text]
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub track: LibraryTrack,
+    /// Index of the track in the queue, set if the queue was searched instead of the library
+    pub queue_index: Option<usize>,
+}
+
+/// Searches the titles, artists and albums of the library, or of the queue if no library
+/// folder was added, and returns the best matches first
+///
+/// # Arguments
+///
+/// * 'library' - The library to search
+/// * 'player' - The player whose queue is searched if the library has no roots
+/// * 'cache' - Words of the tracks from the previous search
+/// * 'query' - Words to search for, the last one may be incomplete
+/// * 'limit' - Maximum number of results
+pub async fn search(
+    library: &Library,
+    player: &Player,
+    cache: &SearchCache,
+    query: &str,
+    limit: usize,
+) -> anyhow::Result<Vec<SearchResult>> {
+    let query = Query::new(query);
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let results = if library.roots()?.is_empty() {
+        let playlist = player.get_playlist().await;
+        let tracks = playlist.iter().map(LibraryTrack::from).enumerate();
+        rank(&query, tracks, |(_, track)| track, cache, limit)
+            .into_iter()
+            .map(|(index, track)| SearchResult {
+                track,
+                queue_index: Some(index),
+            })
+            .collect()
+    } else {
+        rank(&query, library.tracks()?, |track| track, cache, limit)
+            .into_iter()
+            .map(|track| SearchResult {
+                track,
+                queue_index: None,
+            })
+            .collect()
+    };
+
+    Ok(results)
+}
+
+/// Returns the items whose track matches every word of the query, best matches first
+///
+/// # Arguments
+///
+/// * 'query' - The parsed query
+/// * 'items' - Items to search
+/// * 'track' - Returns the track of an item
+/// * 'cache' - Words of the tracks, replaced by the ones of the given items
+/// * 'limit' - Maximum number of returned items
+fn rank<T>(
+    query: &Query,
+    items: impl IntoIterator<Item = T>,
+    track: impl Fn(&T) -> &LibraryTrack,
+    cache: &SearchCache,
+    limit: usize,
+) -> Vec<T> {
+    let mut cached = cache.words.lock().unwrap();
+    let mut words = Words {
+        previous: mem::take(&mut *cached),
+        current: HashMap::new(),
+    };
+    let mut scored: Vec<(u32, T)> = items
+        .into_iter()
+        .filter_map(|item| {
+            let score = {
+                let track = track(&item);
+                let fields = [
+                    Some(track.title.as_str()),
+                    track.artist.as_deref(),
+                    track.album.as_deref(),
+                ];
+                fields.iter().flatten().for_each(|text| words.load(text));
+                query.score(fields.map(|field| field.map_or(&[][..], |text| words.get(text))))
+            };
+            score.map(|score| (score, item))
+        })
+        .collect();
+    //the fields no longer in the library are dropped
+    *cached = words.current;
+    drop(cached);
+
+    //a stable sort keeps the order of the items with the same score
+    scored.sort_by_key(|(score, _)| Reverse(*score));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// Normalized words of a search query
+struct Query {
+    words: Vec<String>,
+}
+
+impl Query {
+    fn new(text: &str) -> Self {
+        Self { words: words(text) }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Scores how well the words of the title, artist and album match the query,
+    /// `None` if one of the words is not found in any of them
+    fn score(&self, fields: [&[String]; 3]) -> Option<u32> {
+        self.words.iter().try_fold(0, |total, word| {
+            let best = fields
+                .iter()
+                .zip(FIELD_WEIGHTS)
+                .flat_map(|(field, weight)| {
+                    field
+                        .iter()
+                        .filter_map(|candidate| compare(word, candidate))
+                        .map(move |matched| matched as u32 * weight)
+                })
+                .max()?;
+            Some(total + best)
+        })
+    }
+}
+
+/// Splits the text into lowercase words without diacritics
+fn words(text: &str) -> Vec<String> {
+    let mut normalized = String::with_capacity(text.len());
+    for char in text.nfd().filter(|char| !is_combining_mark(*char)) {
+        //letters that do not decompose into a base letter and a diacritic
+        match char {
+            'ß' => normalized.push_str("ss"),
+            'æ' | 'Æ' => normalized.push_str("ae"),
+            'œ' | 'Œ' => normalized.push_str("oe"),
+            'ø' | 'Ø' => normalized.push('o'),
+            'ł' | 'Ł' => normalized.push('l'),
+            'đ' | 'Đ' => normalized.push('d'),
+            _ => normalized.extend(char.to_lowercase()),
+        }
+    }
+
+    normalized
+        .split(|char: char| !char.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Compares a word of the query to a word of the track
+fn compare(word: &str, candidate: &str) -> Option<WordMatch> {
+    if word == candidate {
+        return Some(WordMatch::Exact);
+    }
+    if candidate.starts_with(word) {
+        return Some(WordMatch::Prefix);
+    }
+
+    //longer words may have more typos, short ones have to be exact
+    let allowed = match word.chars().count() {
+        0..=3 => return None,
+        4..=7 => 1,
+        _ => 2,
+    };
+    let prefix: String = candidate.chars().take(word.chars().count()).collect();
+    let typos = distance(word, candidate).min(distance(word, &prefix));
+
+    (typos <= allowed).then_some(WordMatch::Typo)
+}
+
+/// Number of inserted, deleted, substituted or swapped characters between the words
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>(); 3];
+
+    for i in 1..=a.len() {
+        rows.rotate_left(1);
+        //the current row is the last one, the two before it are the previous rows
+        rows[2][0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut value = (rows[1][j] + 1)
+                .min(rows[2][j - 1] + 1)
+                .min(rows[1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                value = value.min(rows[0][j - 2] + 1);
+            }
+            rows[2][j] = value;
+        }
+    }
+
+    rows[2][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tauri::async_runtime::block_on;
+    use temp_dir::TempDir;
+
+    use super::*;
+    use crate::player::output::NullOutput;
+
+    fn track(title: &str, artist: &str, album: &str) -> LibraryTrack {
+        LibraryTrack {
+            path: PathBuf::from(format!("/music/{}.mp3", title)),
+            title: title.to_string(),
+            artist: Some(artist.to_string()),
+            album: Some(album.to_string()),
+            album_artist: None,
+            track_number: None,
+            disc_number: None,
+            year: None,
+            genre: None,
+            length: None,
+        }
+    }
+
+    fn titles(query: &str, tracks: Vec<LibraryTrack>) -> Vec<String> {
+        rank(
+            &Query::new(query),
+            tracks,
+            |track| track,
+            &SearchCache::default(),
+            10,
+        )
+        .into_iter()
+        .map(|track| track.title)
+        .collect()
+    }
+
+    #[test]
+    fn normalized_words() {
+        assert_eq!(vec!["beyonce", "deja", "vu"], words("Beyoncé - Déjà Vu"));
+        assert_eq!(vec!["strasse", "bjork"], words("Straße, Björk"));
+    }
+
+    #[test]
+    fn word_distance() {
+        assert_eq!(0, distance("queen", "queen"));
+        assert_eq!(1, distance("queen", "qeen"));
+        assert_eq!(1, distance("queen", "quene"));
+        assert_eq!(2, distance("queen", "quiet"));
+    }
+
+    #[test]
+    fn search_every_word() {
+        let tracks = vec![
+            track("Bohemian Rhapsody", "Queen", "A Night at the Opera"),
+            track("Rhapsody in Blue", "Gershwin", "Rhapsody"),
+        ];
+
+        assert_eq!(vec!["Bohemian Rhapsody"], titles("queen rhapsody", tracks));
+    }
+
+    #[test]
+    fn search_ranks_title_first() {
+        let tracks = vec![
+            track("Interlude", "Rhapsody", "Symphony"),
+            track("Rhapsody", "Artist", "Album"),
+        ];
+
+        assert_eq!(vec!["Rhapsody", "Interlude"], titles("rhapsody", tracks));
+    }
+
+    #[test]
+    fn search_diacritics() {
+        let tracks = vec![track("Jóga", "Björk", "Homogenic")];
+
+        assert_eq!(vec!["Jóga"], titles("bjork joga", tracks));
+    }
+
+    #[test]
+    fn search_prefix() {
+        let tracks = vec![
+            track("Paranoid Android", "Radiohead", "OK Computer"),
+            track("Airbag", "Radiohead", "OK Computer"),
+        ];
+
+        assert_eq!(vec!["Paranoid Android"], titles("radiohead para", tracks));
+    }
+
+    #[test]
+    fn search_typo() {
+        let tracks = vec![
+            track("Smells Like Teen Spirit", "Nirvana", "Nevermind"),
+            track("Spirit", "Other", "Album"),
+        ];
+
+        assert_eq!(
+            vec!["Smells Like Teen Spirit"],
+            titles("nirvanna smells", tracks)
+        );
+    }
+
+    #[test]
+    fn search_short_words_exact() {
+        let tracks = vec![track("Abc", "Artist", "Album")];
+
+        assert!(titles("abd", tracks).is_empty());
+    }
+
+    #[test]
+    fn search_queue_without_library() {
+        let dir = TempDir::new().unwrap();
+        let library = Library::open(dir.path().join("library.db")).unwrap();
+        let (player, _rx) = Player::with_output(NullOutput::default()).unwrap();
+
+        let found = block_on(async {
+            player.open("tests/assets/track.ogg").await.unwrap();
+            search(
+                &library,
+                &player,
+                &SearchCache::default(),
+                "titl",
+                SEARCH_LIMIT,
+            )
+            .await
+            .unwrap()
+        });
+
+        assert_eq!(1, found.len());
+        assert_eq!(Some(0), found[0].queue_index);
+        assert_eq!("title", found[0].track.title);
+    }
+
+    #[test]
+    fn search_limit() {
+        let tracks = (0..20).map(|index| track(&format!("Track {}", index), "Artist", "Album"));
+
+        let found = rank(
+            &Query::new("track"),
+            tracks,
+            |track| track,
+            &SearchCache::default(),
+            5,
+        );
+
+        assert_eq!(5, found.len());
+    }
+
+    #[test]
+    fn search_cache_follows_tracks() {
+        let cache = SearchCache::default();
+        let query = Query::new("queen");
+
+        rank(
+            &query,
+            vec![track("Bohemian Rhapsody", "Queen", "A Night at the Opera")],
+            |track| track,
+            &cache,
+            10,
+        );
+        let found = rank(
+            &query,
+            vec![track("Bohemian Rhapsody", "Queen Live", "Live Killers")],
+            |track| track,
+            &cache,
+            10,
+        );
+
+        assert_eq!(1, found.len());
+        let cached = cache.words.lock().unwrap();
+        assert_eq!(
+            vec!["queen", "live"],
+            cached["Queen Live"]
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+        );
+        assert!(!cached.contains_key("Queen"));
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use crate::{
+    handle::PlayerHandle,
+    player::{Event, Player},
+    playlist::Entry,
+    util::{load_json, save_json_atomic},
+};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+
+const SESSION_FILE: &str = "session.json";
+/// How long the volume stays unchanged before it is saved, dragging the volume
+/// slider changes it many times a second
+const VOLUME_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Track of the saved queue, the tracks of a CUE sheet are saved with their part of
+/// the file and the metadata of the sheet
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+enum SavedTrack {
+    File(PathBuf),
+    Part(Entry),
+}
+
+impl SavedTrack {
+    fn path(&self) -> &Path {
+        match self {
+            SavedTrack::File(path) => path,
+            SavedTrack::Part(entry) => &entry.path,
+        }
+    }
+
+    async fn open(&self, player: &Player) -> anyhow::Result<()> {
+        match self {
+            SavedTrack::File(path) => player.open(path).await,
+            SavedTrack::Part(entry) => {
+                player.add(entry.open()?).await;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Playback state persisted between launches
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Session {
+    tracks: Vec<SavedTrack>,
+    current: usize,
+    /// Position in the current track in seconds
+    position: f64,
+    volume: f32,
+    /// Sessions saved before the mute state was kept are not muted
+    #[serde(default)]
+    muted: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            tracks: Vec::new(),
+            current: 0,
+            position: 0.0,
+            volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl Session {
+    /// Captures the current state of the player
+    pub async fn capture(player: &Player) -> Self {
+        let tracks = player
+            .get_playlist()
+            .await
+            .iter()
+            .map(|track| match track.span() {
+                Some(_) => SavedTrack::Part(Entry::from(track)),
+                None => SavedTrack::File(track.path().clone()),
+            })
+            .collect();
+
+        let (current, position) = if player.is_playing() {
+            (player.current_index(), player.playtime().as_secs_f64())
+        } else {
+            (0, 0.0)
+        };
+
+        Self {
+            tracks,
+            current,
+            position,
+            volume: player.volume(),
+            muted: player.is_muted(),
+        }
+    }
+
+    /// Loads the tracks into the player and returns the track and position to resume from.
+    /// Tracks that no longer exist are skipped.
+    pub async fn open(self, player: &Player) -> Option<(usize, Duration)> {
+        player.set_volume(self.volume);
+        player.set_muted(self.muted);
+
+        let mut current = None;
+        let mut opened = 0;
+        for (index, track) in self.tracks.iter().enumerate() {
+            if let Err(err) = track.open(player).await {
+                warn!("Unable to restore {}: {}", track.path().display(), err);
+                continue;
+            }
+
+            if index >= self.current && current.is_none() {
+                let position = if index == self.current {
+                    Duration::try_from_secs_f64(self.position).unwrap_or_default()
+                } else {
+                    Duration::ZERO
+                };
+                current = Some((opened, position));
+            }
+            opened += 1;
+        }
+
+        match current {
+            None if opened > 0 => Some((0, Duration::ZERO)),
+            current => current,
+        }
+    }
+}
+
+fn session_path(handle: &impl PlayerHandle) -> anyhow::Result<PathBuf> {
+    Ok(handle.data_dir()?.join(SESSION_FILE))
+}
+
+/// Saves the state of the player to the data directory
+pub async fn save(handle: &impl PlayerHandle) {
+    let session = Session::capture(handle.player()).await;
+
+    match session_path(handle).and_then(|path| save_json_atomic(&session, path)) {
+        Ok(()) => debug!("Session saved"),
+        Err(err) => error!("Unable to save session: {}", err),
+    }
+}
+
+/// Saves the session after the events of the player that change it
+#[derive(Default)]
+pub struct Autosave {
+    /// Whether the volume changed since the last save
+    volume_changed: bool,
+}
+
+impl Autosave {
+    /// Waits for the next event of the player, a changed volume is saved once no event
+    /// came for `VOLUME_SAVE_DELAY`. Returns `None` once the player is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * 'handle' - Gives access to the player and the data directory
+    /// * 'rx' - Receiver of the events of the player
+    pub async fn recv(
+        &mut self,
+        handle: &impl PlayerHandle,
+        rx: &Receiver<Event>,
+    ) -> Option<Event> {
+        while self.volume_changed {
+            match rx.recv_timeout(VOLUME_SAVE_DELAY) {
+                Ok(event) => return Some(event),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.volume_changed = false;
+                    save(handle).await;
+                }
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+
+        rx.recv().ok()
+    }
+
+    /// Saves the session if the event changed it, volume changes are left to `recv`
+    ///
+    /// # Arguments
+    ///
+    /// * 'handle' - Gives access to the player and the data directory
+    /// * 'event' - The event the player sent
+    pub async fn changed(&mut self, handle: &impl PlayerHandle, event: &Event) {
+        match event {
+            Event::TrackChanged(_) | Event::PlaybackStopped | Event::PlaylistChanged => {
+                //the save includes the volume too
+                self.volume_changed = false;
+                save(handle).await;
+            }
+            Event::VolumeChanged(_) => self.volume_changed = true,
+            _ => {}
+        }
+    }
+}
+
+/// Restores the state of the player saved by `save`, resuming runs until the queue ends
+///
+/// # Arguments
+///
+/// * 'handle' - Gives access to the player and the data directory
+/// * 'resume' - Whether to resume the saved track paused at its position, otherwise
+///   only the queue is restored
+pub async fn restore(handle: &impl PlayerHandle, resume: bool) {
+    let session = match session_path(handle).and_then(load_json::<Session>) {
+        Ok(session) => session,
+        Err(err) => {
+            info!("No session restored: {}", err);
+            return;
+        }
+    };
+
+    let player = handle.player();
+    let Some((index, position)) = session.open(player).await else {
+        return;
+    };
+
+    if let Err(err) = handle.opened() {
+        error!("{}", err);
+    }
+    if !resume {
+        return;
+    }
+
+    info!("Restoring session from track {} at {:?}", index, position);
+    if let Err(err) = player.restore(index, position).await {
+        error!("Unable to restore session: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use tauri::async_runtime::block_on;
+    use temp_dir::TempDir;
+
+    use super::{Autosave, SavedTrack, Session, SESSION_FILE, VOLUME_SAVE_DELAY};
+    use crate::{
+        handle::PlayerHandle,
+        player::{output::NullOutput, track::Span, volume::Volume, Event, Player},
+        playlist::Entry,
+        util::{load_json, save_json_atomic},
+    };
+
+    struct Saved {
+        player: Player,
+        dir: TempDir,
+    }
+
+    impl PlayerHandle for Saved {
+        fn player(&self) -> &Player {
+            &self.player
+        }
+
+        fn data_dir(&self) -> anyhow::Result<PathBuf> {
+            Ok(self.dir.path().to_path_buf())
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("session.json");
+        let session = Session {
+            tracks: vec![
+                SavedTrack::File(PathBuf::from("/music/track.mp3")),
+                SavedTrack::Part(Entry {
+                    path: PathBuf::from("/music/album.flac"),
+                    title: Some("Title".to_string()),
+                    artist: Some("Artist".to_string()),
+                    length: Some(90.0),
+                    span: Some(Span {
+                        start: Duration::from_secs(60),
+                        end: Some(Duration::from_secs(150)),
+                    }),
+                }),
+            ],
+            current: 0,
+            position: 12.5,
+            volume: 0.5,
+            muted: true,
+        };
+
+        save_json_atomic(&session, &path).unwrap();
+
+        assert_eq!(session, load_json(&path).unwrap());
+    }
+
+    #[test]
+    fn load_paths() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("session.json");
+        std::fs::write(
+            &path,
+            r#"{"tracks": ["/music/track.mp3"], "current": 0, "position": 0.0, "volume": 1.0}"#,
+        )
+        .unwrap();
+
+        let session: Session = load_json(&path).unwrap();
+        assert_eq!(
+            vec![SavedTrack::File(PathBuf::from("/music/track.mp3"))],
+            session.tracks
+        );
+        assert!(!session.muted);
+    }
+
+    #[test]
+    fn load_invalid() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("session.json");
+        std::fs::write(&path, "invalid").unwrap();
+
+        assert!(load_json::<Session>(&path).is_err());
+    }
+
+    #[test]
+    fn autosave_waits_for_the_volume() {
+        let (player, _) = Player::with_output(NullOutput::default()).unwrap();
+        let handle = Saved {
+            player,
+            dir: TempDir::new().unwrap(),
+        };
+        let path = handle.dir.path().join(SESSION_FILE);
+        let (tx, rx) = mpsc::channel();
+        let mut autosave = Autosave::default();
+        let started = Instant::now();
+        tx.send(Event::VolumeChanged(Volume::default())).unwrap();
+
+        let event = block_on(autosave.recv(&handle, &rx)).unwrap();
+        block_on(autosave.changed(&handle, &event));
+        assert!(!path.exists());
+        thread::spawn(move || {
+            thread::sleep(VOLUME_SAVE_DELAY * 2);
+            tx.send(Event::PlaybackPaused).unwrap();
+        });
+
+        assert_eq!(
+            Some(Event::PlaybackPaused),
+            block_on(autosave.recv(&handle, &rx))
+        );
+        assert!(path.exists());
+        assert!(started.elapsed() >= VOLUME_SAVE_DELAY);
+    }
+}
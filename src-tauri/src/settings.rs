@@ -0,0 +1,131 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::handle::PlayerHandle;
+use crate::player::{replaygain::ReplayGainSettings, volume::VolumeCurve};
+use crate::shortcut::{default_keymap, Keymap};
+use crate::util::{load_json, save_json_atomic};
+
+const SETTINGS_FILE: &str = "settings.json";
+/// Change of the volume level by the volume up and down actions
+const VOLUME_STEP: f32 = 0.05;
+
+/// Preferences persisted between launches, missing fields keep their defaults
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub shortcuts: Keymap,
+    /// Whether the shortcuts with modifiers and the media keys are registered with the
+    /// system, so they work while the window is not focused
+    pub global_shortcuts: bool,
+    pub volume_step: f32,
+    pub volume_curve: VolumeCurve,
+    pub replay_gain: ReplayGainSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            shortcuts: default_keymap(),
+            global_shortcuts: false,
+            volume_step: VOLUME_STEP,
+            volume_curve: VolumeCurve::default(),
+            replay_gain: ReplayGainSettings::default(),
+        }
+    }
+}
+
+fn settings_path(handle: &impl PlayerHandle) -> anyhow::Result<PathBuf> {
+    Ok(handle.data_dir()?.join(SETTINGS_FILE))
+}
+
+/// Reads the settings from the data directory, the defaults are used if there are none
+pub fn load(handle: &impl PlayerHandle) -> Settings {
+    settings_path(handle)
+        .and_then(load_json::<Settings>)
+        .unwrap_or_else(|err| {
+            info!("Using default settings: {}", err);
+            Settings::default()
+        })
+}
+
+/// Changes the settings managed by the app and saves them
+///
+/// # Arguments
+///
+/// * 'change' - Edits the settings, they are neither changed nor saved if it fails
+///
+/// # Returns
+///
+/// * Settings - The changed settings
+pub fn update<R: Runtime>(
+    app: &AppHandle<R>,
+    change: impl FnOnce(&mut Settings) -> anyhow::Result<()>,
+) -> anyhow::Result<Settings> {
+    let state = app.state::<Mutex<Settings>>();
+    let mut settings = state.lock().unwrap();
+
+    let mut changed = settings.clone();
+    change(&mut changed)?;
+    save_json_atomic(&changed, settings_path(app)?)?;
+    *settings = changed;
+
+    Ok(settings.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use temp_dir::TempDir;
+
+    use super::{Settings, VOLUME_STEP};
+    use crate::{
+        menu::Action,
+        player::{
+            replaygain::{ReplayGainMode, ReplayGainSettings},
+            volume::VolumeCurve,
+        },
+        shortcut::default_keymap,
+        util::{load_json, save_json_atomic},
+    };
+
+    #[test]
+    fn save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("settings.json");
+        let mut settings = Settings::default();
+        settings
+            .shortcuts
+            .insert("CmdOrCtrl+P".to_string(), Action::PlayPause);
+        settings.global_shortcuts = true;
+        settings.volume_step = 0.1;
+        settings.volume_curve = VolumeCurve::Logarithmic;
+        settings.replay_gain = ReplayGainSettings {
+            mode: ReplayGainMode::Album,
+            preamp: 3.0,
+        };
+
+        save_json_atomic(&settings, &path).unwrap();
+
+        assert_eq!(settings, load_json(&path).unwrap());
+    }
+
+    #[test]
+    fn load_missing_fields() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::write(&path, r#"{"global_shortcuts": true}"#).unwrap();
+
+        let settings: Settings = load_json(&path).unwrap();
+
+        assert_eq!(default_keymap(), settings.shortcuts);
+        assert!(settings.global_shortcuts);
+        assert_eq!(VOLUME_STEP, settings.volume_step);
+        assert_eq!(VolumeCurve::Linear, settings.volume_curve);
+        assert_eq!(ReplayGainSettings::default(), settings.replay_gain);
+    }
+}
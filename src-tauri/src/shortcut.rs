@@ -0,0 +1,187 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::anyhow;
+use log::{info, warn};
+use tauri::{AppHandle, Emitter, Runtime};
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Shortcut, ShortcutState};
+
+use crate::{
+    menu::{self, Action},
+    settings::Settings,
+};
+
+/// Bindings of shortcuts, e.g. `CmdOrCtrl+Right`, to the actions they run
+pub type Keymap = BTreeMap<String, Action>;
+
+/// Keys of the media keyboard row, they are not accepted as menu accelerators
+const MEDIA_KEYS: [Code; 9] = [
+    Code::MediaPlay,
+    Code::MediaPause,
+    Code::MediaPlayPause,
+    Code::MediaStop,
+    Code::MediaTrackNext,
+    Code::MediaTrackPrevious,
+    Code::AudioVolumeUp,
+    Code::AudioVolumeDown,
+    Code::AudioVolumeMute,
+];
+
+pub fn default_keymap() -> Keymap {
+    [
+        ("Space", Action::PlayPause),
+        ("Right", Action::SeekForward),
+        ("Left", Action::SeekBackward),
+        ("CmdOrCtrl+Right", Action::Next),
+        ("CmdOrCtrl+Left", Action::Previous),
+        ("CmdOrCtrl+Up", Action::VolumeUp),
+        ("CmdOrCtrl+Down", Action::VolumeDown),
+        ("CmdOrCtrl+M", Action::Mute),
+        ("CmdOrCtrl+S", Action::Shuffle),
+        ("CmdOrCtrl+R", Action::Repeat),
+        ("CmdOrCtrl+O", Action::Open),
+        ("CmdOrCtrl+Q", Action::Quit),
+        ("MediaPlayPause", Action::PlayPause),
+        ("MediaStop", Action::Stop),
+        ("MediaTrackNext", Action::Next),
+        ("MediaTrackPrevious", Action::Previous),
+        ("AudioVolumeUp", Action::VolumeUp),
+        ("AudioVolumeDown", Action::VolumeDown),
+        ("AudioVolumeMute", Action::Mute),
+    ]
+    .into_iter()
+    .map(|(keys, action)| (keys.to_string(), action))
+    .collect()
+}
+
+pub fn parse(keys: &str) -> anyhow::Result<Shortcut> {
+    Shortcut::from_str(keys).map_err(|err| anyhow!("Invalid shortcut {}: {}", keys, err))
+}
+
+fn is_media_key(shortcut: &Shortcut) -> bool {
+    MEDIA_KEYS.contains(&shortcut.key)
+}
+
+/// Returns whether the shortcut is registered with the system, keys without modifiers
+/// would be taken from every other application. Media keys are always registered, the
+/// webview does not receive them.
+///
+/// # Arguments
+///
+/// * 'shortcut' - The shortcut to check
+/// * 'global' - Whether the shortcuts with modifiers work while the window is not focused
+fn is_global(shortcut: &Shortcut, global: bool) -> bool {
+    is_media_key(shortcut) || (global && !shortcut.mods.is_empty())
+}
+
+/// Returns the shortcut the menu item of the action shows and reacts to. Keys without
+/// modifiers are left to the webview, so typing in its text fields does not run actions.
+///
+/// # Arguments
+///
+/// * 'keymap' - The bindings to pick from
+/// * 'action' - The action of the menu item
+pub fn accelerator(keymap: &Keymap, action: Action) -> Option<&str> {
+    keymap
+        .iter()
+        .filter(|(_, bound)| **bound == action)
+        .map(|(keys, _)| keys.as_str())
+        .find(|keys| {
+            parse(keys).is_ok_and(|shortcut| !is_media_key(&shortcut) && !shortcut.mods.is_empty())
+        })
+}
+
+/// Replaces the shortcuts registered with the system by the ones of the keymap, so they
+/// work while the window is not focused
+///
+/// # Arguments
+///
+/// * 'app' - The app handle
+/// * 'keymap' - The bindings to register
+/// * 'global' - Whether to register the shortcuts with modifiers too, not only media keys
+pub fn register_global<R: Runtime>(app: &AppHandle<R>, keymap: &Keymap, global: bool) {
+    unregister_global(app);
+
+    for (keys, action) in keymap {
+        let shortcut = match parse(keys) {
+            Ok(shortcut) if is_global(&shortcut, global) => shortcut,
+            Ok(_) => continue,
+            Err(err) => {
+                warn!("{}", err);
+                continue;
+            }
+        };
+
+        let action = *action;
+        let registered = app
+            .global_shortcut()
+            .on_shortcut(shortcut, move |app, _, event| {
+                if event.state() == ShortcutState::Pressed {
+                    menu::dispatch(app, action);
+                }
+            });
+        //another application may own the shortcut already
+        if let Err(err) = registered {
+            warn!("Unable to register global shortcut {}: {}", keys, err);
+        }
+    }
+    info!("Global shortcuts registered");
+}
+
+/// Rebuilds the menu with the accelerators of the settings, registers the global shortcuts
+/// again and passes the keymap to the webview, which runs the keys without modifiers
+pub fn apply<R: Runtime>(app: &AppHandle<R>, settings: &Settings) -> anyhow::Result<()> {
+    app.set_menu(menu::build(app, &settings.shortcuts)?)?;
+    register_global(app, &settings.shortcuts, settings.global_shortcuts);
+    app.emit("shortcuts_changed", &settings.shortcuts)?;
+
+    Ok(())
+}
+
+pub fn unregister_global<R: Runtime>(app: &AppHandle<R>) {
+    if let Err(err) = app.global_shortcut().unregister_all() {
+        warn!("Unable to unregister global shortcuts: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accelerator, default_keymap, is_global, parse, Keymap};
+    use crate::menu::Action;
+
+    #[test]
+    fn default_keymap_is_valid() {
+        for keys in default_keymap().keys() {
+            assert!(parse(keys).is_ok(), "{}", keys);
+        }
+    }
+
+    #[test]
+    fn accelerator_skips_plain_and_media_keys() {
+        let keymap = default_keymap();
+
+        assert_eq!(Some("CmdOrCtrl+Right"), accelerator(&keymap, Action::Next));
+        assert_eq!(None, accelerator(&keymap, Action::PlayPause));
+        assert_eq!(None, accelerator(&keymap, Action::Stop));
+        assert_eq!(None, accelerator(&Keymap::new(), Action::Next));
+    }
+
+    #[test]
+    fn global_shortcuts() {
+        assert!(is_global(&parse("MediaPlayPause").unwrap(), true));
+        assert!(is_global(&parse("CmdOrCtrl+Right").unwrap(), true));
+        assert!(!is_global(&parse("Space").unwrap(), true));
+    }
+
+    #[test]
+    fn media_keys_always_global() {
+        assert!(is_global(&parse("MediaPlayPause").unwrap(), false));
+        assert!(!is_global(&parse("CmdOrCtrl+Right").unwrap(), false));
+        assert!(!is_global(&parse("Space").unwrap(), false));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse("Ctrl+").is_err());
+        assert!(parse("Ctrl+Shift+C+A").is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    fs,
+    path::Path,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Counts the writes of the process, so writes running at the same time use their
+/// own temporary files
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// Reads the value from the JSON file
+pub fn load_json<T: DeserializeOwned>(path: impl AsRef<Path>) -> anyhow::Result<T> {
+    let content = fs::read_to_string(path.as_ref())
+        .with_context(|| format!("Unable to read {}", path.as_ref().display()))?;
+
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Writes the value to the JSON file, creating its directory. The value is written to a
+/// temporary file first, so an interrupted write does not corrupt the previous file.
+pub fn save_json_atomic<T: Serialize>(value: &T, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut temp_name = path.file_name().context("Invalid path")?.to_os_string();
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp_path = path.with_file_name(temp_name);
+    fs::write(&temp_path, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(temp_path, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, thread};
+
+    use temp_dir::TempDir;
+
+    use super::{load_json, save_json_atomic};
+
+    #[test]
+    fn save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("bragi").join("values.json");
+        let values = HashMap::from([("volume".to_string(), 0.5)]);
+
+        save_json_atomic(&values, &path).unwrap();
+
+        assert_eq!(values, load_json::<HashMap<String, f64>>(&path).unwrap());
+        assert_eq!(1, fs::read_dir(path.parent().unwrap()).unwrap().count());
+    }
+
+    #[test]
+    fn save_concurrently() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("values.json");
+
+        thread::scope(|scope| {
+            for volume in 0..8 {
+                let path = &path;
+                scope.spawn(move || save_json_atomic(&vec![volume; 1000], path).unwrap());
+            }
+        });
+
+        assert_eq!(1000, load_json::<Vec<u32>>(&path).unwrap().len());
+        assert_eq!(1, fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn load_not_found() {
+        let dir = TempDir::new().unwrap();
+
+        assert!(load_json::<Vec<u32>>(dir.path().join("values.json")).is_err());
+    }
+}
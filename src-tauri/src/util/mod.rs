@@ -1,5 +1,11 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+pub use json::{load_json, save_json_atomic};
+pub use random::Random;
+
+mod json;
+mod random;
+
 pub trait AtomicSub<T> {
     fn saturating_sub(&self, val: T);
 }
@@ -0,0 +1,84 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Small seedable pseudo random generator (SplitMix64), good enough for shuffling
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..bound`
+    pub fn below(&mut self, bound: usize) -> usize {
+        if bound == 0 {
+            return 0;
+        }
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// Shuffles the slice in place using Fisher-Yates
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            slice.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+impl Default for Random {
+    /// Seeds the generator from the current time
+    fn default() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        Self::new(seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Random;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut first = Random::new(42);
+        let mut second = Random::new(42);
+
+        for _ in 0..10 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+    }
+
+    #[test]
+    fn below() {
+        let mut random = Random::new(42);
+
+        for bound in 1..100 {
+            assert!(random.below(bound) < bound);
+        }
+        assert_eq!(0, random.below(0));
+    }
+
+    #[test]
+    fn shuffle_is_permutation() {
+        let mut random = Random::new(42);
+        let mut values: Vec<usize> = (0..20).collect();
+
+        random.shuffle(&mut values);
+
+        assert_ne!((0..20).collect::<Vec<usize>>(), values);
+        values.sort();
+        assert_eq!((0..20).collect::<Vec<usize>>(), values);
+    }
+}
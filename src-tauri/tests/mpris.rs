@@ -0,0 +1,147 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bragi::{
+    mpris::{Mpris, BUS_NAME, OBJECT_PATH},
+    player::{output::NullOutput, Player},
+};
+use tauri::async_runtime::block_on;
+use zbus::{
+    connection,
+    fdo::PropertiesProxy,
+    names::InterfaceName,
+    zvariant::{OwnedObjectPath, OwnedValue},
+    Connection,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// Private session bus, killed when the test ends
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    fn start() -> Self {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("dbus-daemon is required to test the MPRIS server");
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+
+        Self {
+            daemon,
+            address: address.trim().to_string(),
+        }
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+async fn property(connection: &Connection, name: &str) -> OwnedValue {
+    PropertiesProxy::builder(connection)
+        .destination(BUS_NAME)
+        .unwrap()
+        .path(OBJECT_PATH)
+        .unwrap()
+        .build()
+        .await
+        .unwrap()
+        .get(
+            InterfaceName::from_static_str_unchecked(PLAYER_INTERFACE),
+            name,
+        )
+        .await
+        .unwrap()
+}
+
+async fn playback_status(connection: &Connection) -> String {
+    String::try_from(property(connection, "PlaybackStatus").await).unwrap()
+}
+
+async fn call(connection: &Connection, method: &str) {
+    connection
+        .call_method(
+            Some(BUS_NAME),
+            OBJECT_PATH,
+            Some(PLAYER_INTERFACE),
+            method,
+            &(),
+        )
+        .await
+        .unwrap();
+}
+
+fn wait_until(condition: impl Fn() -> bool) {
+    let start = Instant::now();
+    while !condition() {
+        assert!(start.elapsed() < TIMEOUT, "timed out");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn test_mpris() {
+    let bus = Bus::start();
+    let (player, _rx) = Player::with_output(NullOutput::default()).unwrap();
+    let player = Arc::new(player);
+
+    block_on(async {
+        player.open("tests/assets/track.mp3").await.unwrap();
+        player.open("tests/assets/track.flac").await.unwrap();
+
+        let _mpris = Mpris::start(Some(&bus.address), player.clone())
+            .await
+            .unwrap();
+        let client = connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!("Stopped", playback_status(&client).await);
+
+        call(&client, "PlayPause").await;
+        wait_until(|| player.is_playing());
+        assert_eq!("Playing", playback_status(&client).await);
+
+        let mut metadata: HashMap<String, OwnedValue> =
+            HashMap::try_from(property(&client, "Metadata").await).unwrap();
+        let track_id =
+            OwnedObjectPath::try_from(metadata.remove("mpris:trackid").unwrap()).unwrap();
+        assert_eq!("/org/bragi/track/0", track_id.as_str());
+        let title = String::try_from(metadata.remove("xesam:title").unwrap()).unwrap();
+        assert_eq!(player.get_playlist().await[0].title(), title);
+
+        call(&client, "PlayPause").await;
+        assert!(player.is_paused());
+        assert_eq!("Paused", playback_status(&client).await);
+
+        call(&client, "PlayPause").await;
+        assert!(!player.is_paused());
+        assert_eq!("Playing", playback_status(&client).await);
+
+        call(&client, "Stop").await;
+        wait_until(|| !player.is_playing());
+        assert_eq!("Stopped", playback_status(&client).await);
+    });
+}
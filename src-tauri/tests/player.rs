@@ -0,0 +1,584 @@
+use std::{
+    sync::{mpsc::Receiver, Arc},
+    time::{Duration, Instant},
+};
+
+use bragi::player::{
+    output::{FileOutput, NullOutput},
+    track::{Span, Track},
+    volume::{Volume, VolumeCurve},
+    Event, Player, Repeat,
+};
+use tauri::async_runtime::{self, block_on};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn player(speed: f32) -> (Arc<Player>, Receiver<Event>) {
+    let (player, rx) = Player::with_output(NullOutput::with_speed(speed).unwrap()).unwrap();
+    let player = Arc::new(player);
+
+    block_on(async {
+        player.open("tests/assets/track.mp3").await.unwrap();
+        player.open("tests/assets/track.flac").await.unwrap();
+    });
+
+    (player, rx)
+}
+
+fn spawn_queue(player: &Arc<Player>) {
+    let player = player.clone();
+    async_runtime::spawn(async move { player.play_queue().await.unwrap() });
+}
+
+#[test]
+fn test_play_queue() {
+    let (player, rx) = player(100.0);
+
+    block_on(player.play_queue()).unwrap();
+
+    let events: Vec<Event> = rx.try_iter().collect();
+    assert_eq!(
+        vec![
+            Event::TrackChanged(0),
+            Event::TrackChanged(1),
+            Event::PlaybackStopped
+        ],
+        events
+    );
+    assert!(!player.is_playing());
+}
+
+#[test]
+fn test_output_speed_must_be_positive() {
+    assert!(NullOutput::with_speed(0.0).is_err());
+    assert!(NullOutput::with_speed(-1.0).is_err());
+    assert!(NullOutput::with_speed(f32::NAN).is_err());
+}
+
+#[test]
+fn test_file_output() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let path = dir.path().join("output.wav");
+    let (player, rx) = Player::with_output(FileOutput::create(&path, 100.0).unwrap()).unwrap();
+    block_on(player.open("tests/assets/track.wav")).unwrap();
+    let length = block_on(player.get_playlist())[0].length().unwrap();
+
+    block_on(player.play_queue()).unwrap();
+    assert_eq!(Event::TrackChanged(0), rx.try_recv().unwrap());
+    drop(player);
+
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    let spec = reader.spec();
+    assert_eq!(hound::SampleFormat::Float, spec.sample_format);
+    //the queue ends once the sinks are drained, which can be a tick before the file has it all
+    assert!(reader.duration() as f64 / spec.sample_rate as f64 >= length - 0.02);
+    assert!(reader.samples::<f32>().any(|sample| sample.unwrap() != 0.0));
+}
+
+#[test]
+fn test_play_queue_formats() {
+    let (player, rx) = Player::with_output(NullOutput::with_speed(100.0).unwrap()).unwrap();
+    let paths = [
+        "tests/assets/track.wav",
+        "tests/assets/track.ogg",
+        "tests/assets/track.aac",
+        "tests/assets/track.m4a",
+        "tests/assets/track_alac.m4a",
+    ];
+    block_on(async {
+        for path in paths {
+            player.open(path).await.unwrap();
+        }
+    });
+
+    block_on(player.play_queue()).unwrap();
+
+    let events: Vec<Event> = rx.try_iter().collect();
+    let mut expected: Vec<Event> = (0..paths.len()).map(Event::TrackChanged).collect();
+    expected.push(Event::PlaybackStopped);
+    assert_eq!(expected, events);
+}
+
+/// Splits the file into two tracks at one second, like a CUE sheet would
+fn spans(player: &Player, path: &str) {
+    block_on(async {
+        for (start, end) in [(0, Some(1)), (1, None)] {
+            let mut track = Track::try_new(path).unwrap();
+            let span = Span {
+                start: Duration::from_secs(start),
+                end: end.map(Duration::from_secs),
+            };
+            track.set_span(span, Some(&format!("Part {}", start)), None);
+            player.add(track).await;
+        }
+    });
+}
+
+#[test]
+fn test_play_queue_spans() {
+    let (player, rx) = Player::with_output(NullOutput::with_speed(100.0).unwrap()).unwrap();
+    spans(&player, "tests/assets/track.flac");
+
+    block_on(player.play_queue()).unwrap();
+
+    let events: Vec<Event> = rx.try_iter().collect();
+    assert_eq!(
+        vec![
+            Event::TrackChanged(0),
+            Event::TrackChanged(1),
+            Event::PlaybackStopped
+        ],
+        events
+    );
+}
+
+#[test]
+fn test_seek_within_span() {
+    let (player, rx) = Player::with_output(NullOutput::with_speed(1.0).unwrap()).unwrap();
+    let player = Arc::new(player);
+    spans(&player, "tests/assets/track.wav");
+    block_on(player.change_track(1)).unwrap();
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(1), rx.recv_timeout(TIMEOUT).unwrap());
+    //the queue resumes the playback right after the track changes
+    std::thread::sleep(Duration::from_millis(200));
+    player.pause();
+
+    player.seek(Duration::from_millis(500)).unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+
+    assert_eq!(Duration::from_millis(500), player.playtime());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_play_queue_skips_undecodable() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let path = dir.path().join("invalid.mp3");
+    std::fs::write(&path, "invalid").unwrap();
+    let (player, rx) = Player::with_output(NullOutput::with_speed(100.0).unwrap()).unwrap();
+    block_on(async {
+        player.open(&path).await.unwrap();
+        player.open("tests/assets/track.wav").await.unwrap();
+    });
+
+    block_on(player.play_queue()).unwrap();
+
+    let events: Vec<Event> = rx.try_iter().collect();
+    assert_eq!(vec![Event::TrackChanged(1), Event::PlaybackStopped], events);
+}
+
+#[test]
+fn test_play_queue_skips_unavailable() {
+    let (player, rx) = player(100.0);
+    let path = block_on(player.get_playlist())[0].path().clone();
+
+    block_on(player.set_available(&path, false)).unwrap();
+    block_on(player.play_queue()).unwrap();
+
+    let events: Vec<Event> = rx.try_iter().collect();
+    assert_eq!(
+        vec![
+            Event::PlaylistChanged,
+            Event::TrackChanged(1),
+            Event::PlaybackStopped
+        ],
+        events
+    );
+}
+
+#[test]
+fn test_play_queue_skips_deleted() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let path = dir.path().join("track.wav");
+    std::fs::copy("tests/assets/track.wav", &path).unwrap();
+    let (player, rx) = Player::with_output(NullOutput::with_speed(100.0).unwrap()).unwrap();
+    block_on(async {
+        player.open(&path).await.unwrap();
+        player.open("tests/assets/track.wav").await.unwrap();
+    });
+
+    std::fs::remove_file(&path).unwrap();
+    block_on(player.play_queue()).unwrap();
+
+    let events: Vec<Event> = rx.try_iter().collect();
+    assert_eq!(
+        vec![
+            Event::PlaylistChanged,
+            Event::TrackChanged(1),
+            Event::PlaybackStopped
+        ],
+        events
+    );
+    assert!(!block_on(player.get_playlist())[0].is_available());
+}
+
+#[test]
+fn test_track_changed_when_preloaded_track_starts() {
+    let speed = 10.0;
+    let (player, rx) = player(speed);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+    let started = Instant::now();
+
+    assert_eq!(Event::TrackChanged(1), rx.recv_timeout(TIMEOUT).unwrap());
+
+    let first_length =
+        Duration::from_secs_f64(block_on(player.get_playlist())[0].length().unwrap());
+    assert!(started.elapsed() >= first_length.mul_f32(0.9 / speed));
+    block_on(player.stop());
+}
+
+#[test]
+fn test_crossfade() {
+    let crossfade = Duration::from_secs(3);
+    let (player, rx) = player(10.0);
+    player.set_crossfade(crossfade).unwrap();
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+
+    assert_eq!(Event::TrackChanged(1), rx.recv_timeout(TIMEOUT).unwrap());
+
+    //the upcoming track takes over once the current one faded out
+    assert!(player.playtime() >= crossfade.mul_f32(0.9));
+    block_on(player.stop());
+}
+
+#[test]
+fn test_crossfade_too_long() {
+    let (player, _rx) = player(1.0);
+
+    assert!(player.set_crossfade(Duration::from_secs(13)).is_err());
+    assert!(player.crossfade().is_zero());
+}
+
+#[test]
+fn test_set_repeat() {
+    let (player, rx) = player(1.0);
+
+    player.set_repeat(Repeat::All).unwrap();
+
+    assert_eq!(Repeat::All, player.repeat());
+    assert_eq!(Event::RepeatChanged(Repeat::All), rx.try_recv().unwrap());
+}
+
+#[test]
+fn test_set_shuffle() {
+    let (player, rx) = player(1.0);
+
+    block_on(player.set_shuffle(true, Some(1)));
+
+    assert!(player.is_shuffle());
+    assert_eq!(Event::ShuffleChanged(true), rx.try_recv().unwrap());
+}
+
+#[test]
+fn test_repeat_all() {
+    let (player, rx) = player(100.0);
+    player.set_repeat(Repeat::All).unwrap();
+    rx.try_recv().unwrap();
+    spawn_queue(&player);
+
+    for index in [0, 1, 0, 1] {
+        assert_eq!(
+            Event::TrackChanged(index),
+            rx.recv_timeout(TIMEOUT).unwrap()
+        );
+    }
+    block_on(player.stop());
+}
+
+#[test]
+fn test_repeat_one() {
+    let (player, rx) = player(100.0);
+    player.set_repeat(Repeat::One).unwrap();
+    rx.try_recv().unwrap();
+    spawn_queue(&player);
+
+    for _ in 0..3 {
+        assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+    }
+    block_on(player.next());
+
+    assert_eq!(Event::TrackChanged(1), rx.recv_timeout(TIMEOUT).unwrap());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_remove() {
+    let (player, rx) = player(1.0);
+
+    block_on(player.remove(0)).unwrap();
+
+    let playlist = block_on(player.get_playlist());
+    assert_eq!(1, playlist.len());
+    assert!(playlist[0].path().ends_with("track.flac"));
+    assert_eq!(Event::PlaylistChanged, rx.try_recv().unwrap());
+}
+
+#[test]
+fn test_remove_current() {
+    let (player, rx) = player(1.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+
+    block_on(player.remove(0)).unwrap();
+
+    //the next track may start before the playlist change is sent
+    let events = [
+        rx.recv_timeout(TIMEOUT).unwrap(),
+        rx.recv_timeout(TIMEOUT).unwrap(),
+    ];
+    assert!(events.contains(&Event::PlaylistChanged));
+    assert!(events.contains(&Event::TrackChanged(0)));
+    assert!(block_on(player.get_playlist())[0]
+        .path()
+        .ends_with("track.flac"));
+    block_on(player.stop());
+}
+
+#[test]
+fn test_remove_preloaded() {
+    let (player, rx) = player(10.0);
+    let started = player.watch_started();
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+    //gives the queue time to preload the next track
+    std::thread::sleep(Duration::from_millis(100));
+
+    block_on(player.remove(1)).unwrap();
+
+    assert_eq!(Event::PlaylistChanged, rx.recv_timeout(TIMEOUT).unwrap());
+    assert_eq!(Event::PlaybackStopped, rx.recv_timeout(TIMEOUT).unwrap());
+    let started: Vec<_> = started.try_iter().collect();
+    assert!(started[0].ends_with("track.mp3"));
+    assert!(!started.iter().any(|path| path.ends_with("track.flac")));
+}
+
+#[test]
+fn test_replace() {
+    let (player, rx) = player(1.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+
+    let track = Track::try_new("tests/assets/track.wav").unwrap();
+    block_on(player.replace(vec![track])).unwrap();
+
+    assert_eq!(Event::PlaylistChanged, rx.recv_timeout(TIMEOUT).unwrap());
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+    let playlist = block_on(player.get_playlist());
+    assert_eq!(1, playlist.len());
+    assert!(playlist[0].path().ends_with("track.wav"));
+    assert!(player.is_playing());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_clear() {
+    let (player, rx) = player(1.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+
+    block_on(player.clear()).unwrap();
+
+    assert!(block_on(player.get_playlist()).is_empty());
+    assert!(!player.is_playing());
+    assert!(rx
+        .iter()
+        .take_while(|event| *event != Event::PlaylistChanged)
+        .all(|event| event == Event::PlaybackStopped));
+}
+
+#[test]
+fn test_next() {
+    let (player, rx) = player(1.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+
+    block_on(player.next());
+
+    assert_eq!(Event::TrackChanged(1), rx.recv_timeout(TIMEOUT).unwrap());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_previous() {
+    let (player, rx) = player(1.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+    block_on(player.next());
+    assert_eq!(Event::TrackChanged(1), rx.recv_timeout(TIMEOUT).unwrap());
+
+    block_on(player.previous());
+
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_change_track() {
+    let (player, rx) = player(1.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+
+    block_on(player.change_track(1)).unwrap();
+
+    assert_eq!(Event::TrackChanged(1), rx.recv_timeout(TIMEOUT).unwrap());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_seek() {
+    let (player, rx) = player(10.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+    let length = Duration::from_secs_f64(block_on(player.get_playlist())[0].length().unwrap());
+    let position = length - Duration::from_secs(1);
+
+    player.seek(position).unwrap();
+
+    assert!(player.playtime() >= position);
+    //the rest of the track is skipped
+    assert_eq!(Event::TrackChanged(1), rx.recv_timeout(TIMEOUT).unwrap());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_playtime_paused() {
+    let (player, rx) = player(1.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+    std::thread::sleep(Duration::from_millis(200));
+
+    player.pause();
+    std::thread::sleep(Duration::from_millis(100));
+    let paused = player.playtime();
+    std::thread::sleep(Duration::from_millis(500));
+
+    assert_eq!(paused, player.playtime());
+    assert!(!paused.is_zero());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_playtime_after_seek_while_paused() {
+    let (player, rx) = player(1.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+    //the queue resumes the playback right after the track changes
+    std::thread::sleep(Duration::from_millis(200));
+    player.pause();
+
+    player.seek(Duration::from_secs(3)).unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+
+    assert_eq!(Duration::from_secs(3), player.playtime());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_pause_and_resume() {
+    let (player, rx) = player(10.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+
+    player.pause();
+    assert!(player.is_paused());
+    block_on(player.play());
+
+    assert_eq!(Event::PlaybackPaused, rx.recv_timeout(TIMEOUT).unwrap());
+    assert_eq!(Event::PlaybackResumed, rx.recv_timeout(TIMEOUT).unwrap());
+    assert!(!player.is_paused());
+    block_on(player.stop());
+}
+
+#[test]
+fn test_mute_restores_volume() {
+    let (player, rx) = player(1.0);
+
+    player.set_volume(0.6);
+    player.set_muted(true);
+    assert_eq!(0.6, player.volume());
+    assert!(player.is_muted());
+    player.set_muted(false);
+
+    let events: Vec<Event> = rx.try_iter().collect();
+    assert_eq!(
+        vec![
+            Event::VolumeChanged(Volume {
+                level: 0.6,
+                muted: false,
+                curve: VolumeCurve::Linear
+            }),
+            Event::VolumeChanged(Volume {
+                level: 0.6,
+                muted: true,
+                curve: VolumeCurve::Linear
+            }),
+            Event::VolumeChanged(Volume {
+                level: 0.6,
+                muted: false,
+                curve: VolumeCurve::Linear
+            }),
+        ],
+        events
+    );
+}
+
+#[test]
+fn test_step_volume() {
+    let (player, rx) = player(1.0);
+
+    player.step_volume(0.25);
+    assert_eq!(1.0, player.volume());
+    player.step_volume(-0.25);
+    assert_eq!(0.75, player.volume());
+    player.set_muted(true);
+    player.step_volume(-1.0);
+    assert_eq!(0.0, player.volume());
+    assert!(!player.is_muted());
+
+    //stepping past full volume changes nothing
+    let events: Vec<Event> = rx.try_iter().collect();
+    assert_eq!(3, events.len());
+}
+
+#[test]
+fn test_stop() {
+    let (player, rx) = player(1.0);
+    spawn_queue(&player);
+    assert_eq!(Event::TrackChanged(0), rx.recv_timeout(TIMEOUT).unwrap());
+
+    block_on(player.stop());
+
+    assert_eq!(Event::PlaybackStopped, rx.recv_timeout(TIMEOUT).unwrap());
+    assert!(!player.is_playing());
+}
+
+#[test]
+fn test_analyze_loudness() {
+    let (player, _rx) = player(1.0);
+
+    let analyzed = block_on(player.analyze_loudness()).unwrap();
+
+    assert_eq!(2, analyzed);
+    for track in block_on(player.get_playlist()) {
+        assert!(track.replay_gain().track_gain.is_some());
+        assert!(track.replay_gain().track_peak.is_some());
+    }
+}
+
+#[test]
+fn test_restore() {
+    let (player, rx) = player(1.0);
+    let restored = player.clone();
+    async_runtime::spawn(async move { restored.restore(1, Duration::from_secs(2)).await.unwrap() });
+
+    assert_eq!(Event::TrackChanged(1), rx.recv_timeout(TIMEOUT).unwrap());
+    assert_eq!(Event::PlaybackPaused, rx.recv_timeout(TIMEOUT).unwrap());
+    std::thread::sleep(Duration::from_millis(500));
+
+    assert_eq!(2, player.playtime().as_secs());
+    assert!(player.is_playing());
+    block_on(player.stop());
+}
@@ -17,3 +17,64 @@ fn test_track_duration_flac() {
 
     assert_eq!(6.0, actual);
 }
+
+#[test]
+fn test_track_duration_wav() {
+    let track = Track::try_new("tests/assets/track.wav").unwrap();
+
+    let actual = track.length().unwrap().round();
+
+    assert_eq!(2.0, actual);
+}
+
+#[test]
+fn test_track_duration_ogg() {
+    let track = Track::try_new("tests/assets/track.ogg").unwrap();
+
+    let actual = track.length().unwrap().round();
+
+    assert_eq!(3.0, actual);
+}
+
+#[test]
+fn test_track_duration_opus() {
+    let track = Track::try_new("tests/assets/track.opus").unwrap();
+
+    let actual = track.length().unwrap().round();
+
+    assert_eq!(3.0, actual);
+}
+
+#[test]
+fn test_track_duration_aac() {
+    let track = Track::try_new("tests/assets/track.aac").unwrap();
+
+    let actual = track.length().unwrap().round();
+
+    assert_eq!(2.0, actual);
+}
+
+#[test]
+fn test_track_duration_m4a() {
+    let track = Track::try_new("tests/assets/track.m4a").unwrap();
+
+    let actual = track.length().unwrap().round();
+
+    assert_eq!(2.0, actual);
+}
+
+#[test]
+fn test_track_duration_alac() {
+    let track = Track::try_new("tests/assets/track_alac.m4a").unwrap();
+
+    let actual = track.length().unwrap().round();
+
+    assert_eq!(2.0, actual);
+}
+
+#[test]
+fn test_track_replay_gain_untagged() {
+    let track = Track::try_new("tests/assets/track.flac").unwrap();
+
+    assert!(track.replay_gain().is_empty());
+}
@@ -1,4 +1,4 @@
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { } from "@tauri-apps/api/webviewWindow"
 import play_icon from './assets/play.svg';
@@ -13,6 +13,9 @@ import { Event, listen } from "@tauri-apps/api/event";
 import Volume from "./components/Volume";
 import ProgressBar from "./components/ProgressBar";
 import TrackInfo from "./components/TrackInfo";
+import { Keymap, findAction, isTyping, webviewShortcuts } from "./util/shortcut";
+
+const MAC = navigator.userAgent.includes("Mac");
 
 function App() {
   const [playList, setPlayList] = useState<ITrack[]>([]);
@@ -32,6 +35,48 @@ function App() {
     setPlaying(false);
   })
 
+  listen('playback_paused', async () => {
+    setPlaying(false);
+  })
+
+  listen('playlist_changed', async () => {
+    setPlayList(await getPlaylist());
+    setCurrentTrack(await invoke("current_index", {}));
+  })
+
+  useEffect(() => {
+    (async () => {
+      setPlayList(await getPlaylist());
+      setCurrentTrack(await invoke("current_index", {}));
+    })();
+  }, [])
+
+  //keys without modifiers are not menu accelerators, the webview runs their actions
+  useEffect(() => {
+    let shortcuts = webviewShortcuts({}, MAC);
+    invoke<Keymap>("get_shortcuts", {}).then(keymap => shortcuts = webviewShortcuts(keymap, MAC));
+    const unlisten = listen('shortcuts_changed', (event: Event<Keymap>) => {
+      shortcuts = webviewShortcuts(event.payload, MAC);
+    });
+
+    function onKeyDown(event: KeyboardEvent) {
+      const plain = !(event.ctrlKey || event.shiftKey || event.altKey || event.metaKey);
+      if (plain && isTyping(event)) {
+        return;
+      }
+      const action = findAction(shortcuts, event);
+      if (action !== undefined) {
+        event.preventDefault();
+        invoke("run_action", { action });
+      }
+    }
+    window.addEventListener("keydown", onKeyDown);
+    return () => {
+      window.removeEventListener("keydown", onKeyDown);
+      unlisten.then(stop => stop());
+    };
+  }, [])
+
   async function stop() {
     await invoke("stop", {});
     setPlaying(false);
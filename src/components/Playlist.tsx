@@ -1,3 +1,4 @@
+import { useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import ITrack from "../interfaces/track";
 import { convertLength } from "../util/time";
@@ -5,10 +6,23 @@ import "./Playlist.css";
 
 function Playlist({ list, currentTrack }: { list: ITrack[], currentTrack: number }) {
 
+    const [dragged, setDragged] = useState<number | null>(null);
+
     async function changeTrack(index: number) {
         await invoke("change_track", { index });
     }
 
+    async function removeTrack(index: number) {
+        await invoke("remove_track", { index });
+    }
+
+    async function moveTrack(to: number) {
+        if (dragged !== null && dragged !== to) {
+            await invoke("move_track", { from: dragged, to });
+        }
+        setDragged(null);
+    }
+
     return (
         <table className="playlist">
             <tbody>
@@ -17,14 +31,17 @@ function Playlist({ list, currentTrack }: { list: ITrack[], currentTrack: number
                     <th>Artist</th>
                     <th>Album</th>
                     <th>Length</th>
+                    <th></th>
                 </tr>
                 {
                     list.map((track, index) => (
-                        <tr key={index} className={index === currentTrack ? "selected" : ""} onDoubleClick={() => changeTrack(index)}>
+                        <tr key={index} className={index === currentTrack ? "selected" : ""} onDoubleClick={() => changeTrack(index)}
+                            draggable onDragStart={() => setDragged(index)} onDragOver={(e) => e.preventDefault()} onDrop={() => moveTrack(index)}>
                             <td>{track.title}</td>
                             <td>{track.artist}</td>
                             <td>{track.album}</td>
                             <td>{convertLength(track.length)}</td>
+                            <td><button className="remove-button" onClick={() => removeTrack(index)}>✕</button></td>
                         </tr>
                     ))
                 }
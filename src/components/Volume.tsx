@@ -1,26 +1,35 @@
 import { invoke } from "@tauri-apps/api/core";
+import { Event, listen } from "@tauri-apps/api/event";
 import { useEffect, useState } from "react";
 import "./Volume.css";
 import volume_max_icon from '../assets/volume-max.svg';
 import volume_min_icon from '../assets/volume-min.svg';
 import volume_mute_icon from '../assets/volume-mute.svg';
+import IVolume from "../interfaces/volume";
 
 function Volume() {
-    const [volume, setVolume] = useState(1);
+    const [volume, setVolume] = useState<IVolume | null>(null);
     const [visible, setVisible] = useState(false);
 
+    //the player owns the volume, shortcuts, the menu and the session change it too
     useEffect(() => {
-        changeVolume();
-    }, [volume])
+        (async () => setVolume(await invoke("get_volume_state", {})))();
+        const unlisten = listen('volume_changed', (event: Event<IVolume>) => {
+            setVolume(event.payload);
+        });
+        return () => {
+            unlisten.then(stop => stop());
+        };
+    }, [])
 
-    async function changeVolume() {
-        await invoke("set_volume", { volume: volume });
+    async function changeVolume(level: number) {
+        await invoke("set_volume", { volume: level });
     }
 
     function selectIcon(): string {
-        if (volume > 0.5) {
+        if (volume === null || (!volume.muted && volume.level > 0.5)) {
             return volume_max_icon;
-        } else if (volume > 0) {
+        } else if (!volume.muted && volume.level > 0) {
             return volume_min_icon;
         } else {
             return volume_mute_icon;
@@ -41,9 +50,9 @@ function Volume() {
                 min={0}
                 max={1}
                 step={0.02}
-                value={volume}
+                value={volume?.level ?? 1}
                 onChange={event => {
-                    setVolume(event.target.valueAsNumber)
+                    changeVolume(event.target.valueAsNumber)
                 }}
             />
         </div>
@@ -0,0 +1,7 @@
+interface IVolume {
+    level: number,
+    muted: boolean,
+    curve: string,
+}
+
+export default IVolume;
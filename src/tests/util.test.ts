@@ -1,5 +1,6 @@
 import { expect, test } from "vitest";
 import { convertLength } from "../util/time";
+import { findAction, parseShortcut, webviewShortcuts } from "../util/shortcut";
 
 test("covertLength", () => {
   const seconds = 180;
@@ -24,3 +25,32 @@ test("covertLengthZero", () => {
   const expected = "0:00";
   expect(convertLength(seconds)).toEqual(expected);
 });
+
+test("webviewShortcutsSkipAcceleratorsAndMediaKeys", () => {
+  const keymap = {
+    "Space": "play_pause",
+    "CmdOrCtrl+Right": "next",
+    "Shift+N": "next",
+    "MediaTrackNext": "next",
+  };
+  const actions = webviewShortcuts(keymap, false).map(([, action]) => action);
+  expect(actions).toEqual(["next", "play_pause"]);
+});
+
+test("findAction", () => {
+  const shortcuts = webviewShortcuts({ "Right": "seek_forward", "Ctrl+Right": "next", "Ctrl+Shift+Right": "next" }, false);
+  const key = (code: string, ctrlKey = false, shiftKey = false) =>
+    ({ code, ctrlKey, shiftKey, altKey: false, metaKey: false }) as KeyboardEvent;
+  const right = key("ArrowRight");
+  const shiftRight = key("ArrowRight", true, true);
+  const a = key("KeyA");
+  expect(findAction(shortcuts, right)).toEqual("seek_forward");
+  expect(findAction(shortcuts, shiftRight)).toEqual("next");
+  expect(findAction(shortcuts, a)).toBeUndefined();
+});
+
+test("parseShortcutCmdOrCtrl", () => {
+  expect(parseShortcut("CmdOrCtrl+M", true)).toEqual({ ctrl: false, shift: false, alt: false, meta: true, code: "keym" });
+  expect(parseShortcut("CmdOrCtrl+M", false)).toEqual({ ctrl: true, shift: false, alt: false, meta: false, code: "keym" });
+  expect(parseShortcut("Hyper+M", false)).toBeNull();
+});
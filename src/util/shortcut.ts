@@ -0,0 +1,137 @@
+/** Bindings of shortcuts, e.g. `CmdOrCtrl+Right`, to the ids of the actions they run */
+export type Keymap = Record<string, string>;
+
+export interface Shortcut {
+    ctrl: boolean;
+    shift: boolean;
+    alt: boolean;
+    meta: boolean;
+    code: string;
+}
+
+const MEDIA_KEYS = [
+    "mediaplay",
+    "mediapause",
+    "mediaplaypause",
+    "mediastop",
+    "mediatracknext",
+    "mediatrackprevious",
+    "audiovolumeup",
+    "audiovolumedown",
+    "audiovolumemute",
+];
+
+const KEY_ALIASES: Record<string, string> = {
+    up: "arrowup",
+    down: "arrowdown",
+    left: "arrowleft",
+    right: "arrowright",
+    esc: "escape",
+    return: "enter",
+};
+
+/**
+ * Parses a shortcut of the keymap, the key is normalized to the lowercase `KeyboardEvent.code`
+ * @param keys The shortcut, e.g. `CmdOrCtrl+Right`
+ * @param mac Whether `CmdOrCtrl` is the command key
+ * @returns The shortcut or `null` if it is invalid
+ */
+export function parseShortcut(keys: string, mac: boolean): Shortcut | null {
+    const tokens = keys.split("+").map(token => token.trim().toLowerCase());
+    let key = tokens.pop();
+    if (!key) {
+        return null;
+    }
+    const shortcut = { ctrl: false, shift: false, alt: false, meta: false, code: "" };
+    for (const modifier of tokens) {
+        switch (modifier) {
+            case "ctrl":
+            case "control":
+                shortcut.ctrl = true;
+                break;
+            case "shift":
+                shortcut.shift = true;
+                break;
+            case "alt":
+            case "option":
+                shortcut.alt = true;
+                break;
+            case "super":
+            case "cmd":
+            case "command":
+            case "meta":
+                shortcut.meta = true;
+                break;
+            case "cmdorctrl":
+            case "cmdorcontrol":
+            case "commandorctrl":
+            case "commandorcontrol":
+                if (mac) {
+                    shortcut.meta = true;
+                } else {
+                    shortcut.ctrl = true;
+                }
+                break;
+            default:
+                return null;
+        }
+    }
+    key = KEY_ALIASES[key] ?? key;
+    if (/^[a-z]$/.test(key)) {
+        key = "key" + key;
+    } else if (/^[0-9]$/.test(key)) {
+        key = "digit" + key;
+    }
+    shortcut.code = key;
+    return shortcut;
+}
+
+function hasModifiers(shortcut: Shortcut): boolean {
+    return shortcut.ctrl || shortcut.shift || shortcut.alt || shortcut.meta;
+}
+
+/**
+ * Returns the shortcuts the webview has to run itself. Media keys are registered with the
+ * system and the first shortcut with modifiers of each action is the accelerator of its menu
+ * item, the same way the backend picks it.
+ * @param keymap The bindings to pick from
+ * @param mac Whether `CmdOrCtrl` is the command key
+ */
+export function webviewShortcuts(keymap: Keymap, mac: boolean): [Shortcut, string][] {
+    const accelerators = new Set<string>();
+    const shortcuts: [Shortcut, string][] = [];
+    for (const [keys, action] of Object.entries(keymap).sort(([a], [b]) => a < b ? -1 : a > b ? 1 : 0)) {
+        const shortcut = parseShortcut(keys, mac);
+        if (shortcut === null || MEDIA_KEYS.includes(shortcut.code)) {
+            continue;
+        }
+        if (hasModifiers(shortcut) && !accelerators.has(action)) {
+            accelerators.add(action);
+            continue;
+        }
+        shortcuts.push([shortcut, action]);
+    }
+    return shortcuts;
+}
+
+/**
+ * Returns the action the key press runs
+ * @param shortcuts The shortcuts of the webview
+ * @param event The key press
+ */
+export function findAction(shortcuts: [Shortcut, string][], event: KeyboardEvent): string | undefined {
+    const code = event.code.toLowerCase();
+    return shortcuts.find(([shortcut]) =>
+        shortcut.code === code &&
+        shortcut.ctrl === event.ctrlKey &&
+        shortcut.shift === event.shiftKey &&
+        shortcut.alt === event.altKey &&
+        shortcut.meta === event.metaKey
+    )?.[1];
+}
+
+/** Returns whether the key press goes to a text field, which takes the keys without modifiers */
+export function isTyping(event: KeyboardEvent): boolean {
+    const target = event.target as HTMLElement | null;
+    return target !== null && (target.tagName === "INPUT" || target.tagName === "TEXTAREA" || target.isContentEditable);
+}